@@ -0,0 +1,1138 @@
+use crate::builtins;
+use crate::cache::{self, Cache, FuncEntry, ModuleEntry};
+use crate::callgraph;
+use crate::config::{Config, Precision};
+use crate::debloat;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::env;
+use crate::error::Result;
+use crate::infer::{self, Props, Summaries};
+use crate::jssyntax::{JSTyp, COMMENT, FUNC_DECL, GENERATOR_FUNC_DECL, PROGRAM};
+use crate::node::{self, Node};
+use crate::parallel;
+use crate::project::{Module, Project};
+use crate::report::{self, Reporter, Types};
+use crate::stubs::Stubs;
+use crate::suppress;
+use crate::trace::{self, Traces};
+use std::collections::{BTreeMap, HashMap};
+use tree_sitter::Tree;
+use tree_sitter_traversal::{traverse, Order};
+
+/// Rounds of inference until the return types of the functions stop changing, e.g., for
+/// recursive functions
+const MAX_ROUNDS: usize = 4;
+
+/// Return types keyed by (filename, function name)
+type Returns = HashMap<(String, String), JSTyp>;
+
+/// Parameter types to infer a function with, the properties of `this` if it is called as a
+/// method, and the first callsite passing them
+type Seed = (Vec<JSTyp>, Props, Option<Span>);
+
+/// A module debloated for inference. Syntax nodes cannot be sent to another thread, so every
+/// thread builds its own units from the shared trees.
+struct Unit<'a> {
+    module: &'a Module,
+    code: &'a str,
+    program: Node<'a>,
+    nodes: Vec<Node<'a>>,
+    /// function declarations and their names
+    funcs: Vec<(Node<'a>, &'a str)>,
+}
+
+impl<'a> Unit<'a> {
+    fn new(module: &'a Module, tree: &'a Tree, code: &'a str) -> Self {
+        let mut nodes = node::get_nodes(tree.walk(), Order::Pre, code);
+        let program = nodes.remove(0);
+        // the functions of debloated code that does not parse are not analyzed
+        let funcs = nodes
+            .iter()
+            .filter(|_| program.kind() == PROGRAM)
+            .filter(|node| node.kind() == FUNC_DECL)
+            .filter_map(|func| {
+                node::get_field(func, "name", code).map(|name| (func.clone(), name.text))
+            })
+            .collect();
+        Self {
+            module,
+            code,
+            program,
+            nodes,
+            funcs,
+        }
+    }
+
+    /// Whether the debloated code parses to a program
+    fn parsed(&self) -> bool {
+        self.program.kind() == PROGRAM
+    }
+}
+
+/// The debloated code of a module and its tree
+type Debloated = (String, Tree);
+
+/// A function inferred in a round
+struct Inferred {
+    unit_idx: usize,
+    name: String,
+    /// hash of the inputs of the inference; see `func_key`
+    key: String,
+    typ: JSTyp,
+    diags: Vec<Diagnostic>,
+    typs: Types,
+}
+
+/// Diagnostics of a project and the types inferred for its variables
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    /// every type a variable was inferred with, e.g., once per distinct callsite argument types
+    pub typs: Types,
+    /// original sources keyed by filename
+    pub sources: BTreeMap<String, String>,
+}
+
+impl Analysis {
+    /// Type of the variable at the 1-based `line` and byte `column` of `file`, `Unknown` if it
+    /// was inferred with several types, `None` if there is no variable or it was not inferred
+    pub fn type_at(&self, file: &str, line: usize, column: usize) -> Option<JSTyp> {
+        let code = self.sources.get(file)?;
+        let line_start = match line {
+            0 => return None,
+            1 => 0,
+            _ => code.match_indices('\n').nth(line - 2)?.0 + 1,
+        };
+        let byte = line_start + column.checked_sub(1)?;
+        let (_, typs) = self.typs_at(file, byte)?;
+        Some(join_typs(typs))
+    }
+
+    /// The variable at `byte` of `file` and every type it was inferred with. A variable the
+    /// inference did not type at its occurrence, e.g., an argument of an unsupported call, has
+    /// the types it had before the innermost statement containing it.
+    pub fn typs_at(&self, file: &str, byte: usize) -> Option<(Span, Vec<JSTyp>)> {
+        let mut typs = vec![];
+        let occurrence = self
+            .typs
+            .occurrences
+            .iter()
+            .map(|occurrence| &occurrence.span)
+            .find(|span| span.file == file && span.start_byte <= byte && byte < span.end_byte);
+        if let Some(span) = occurrence {
+            for occurrence in &self.typs.occurrences {
+                if occurrence.span == *span && !typs.contains(&occurrence.typ) {
+                    typs.push(occurrence.typ.clone());
+                }
+            }
+            return Some((span.clone(), typs));
+        }
+
+        let code = self.sources.get(file)?;
+        let (start_byte, end_byte) = ident_at(code, byte)?;
+        let name = &code[start_byte..end_byte];
+        let stmt = self
+            .typs
+            .envs
+            .iter()
+            .map(|(span, _)| span)
+            .filter(|span| span.file == file && span.start_byte <= byte && byte < span.end_byte)
+            .min_by_key(|span| span.end_byte - span.start_byte)?;
+        for (other, env) in &self.typs.envs {
+            match env.get(name) {
+                Some(typ) if other == stmt && !typs.contains(typ) => typs.push(typ.clone()),
+                _ => {}
+            }
+        }
+        if typs.is_empty() {
+            return None;
+        }
+        Some((report::byte_span(file, code, start_byte, end_byte), typs))
+    }
+}
+
+/// Byte range of the identifier of `code` starting before or at `byte`
+fn ident_at(code: &str, byte: usize) -> Option<(usize, usize)> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    if !code.get(byte..)?.starts_with(is_ident) {
+        return None;
+    }
+    let start_byte = code[..byte]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_ident(*c))
+        .last()
+        .map_or(byte, |(idx, _)| idx);
+    let end_byte = code[byte..]
+        .char_indices()
+        .find(|(_, c)| !is_ident(*c))
+        .map_or(code.len(), |(idx, _)| byte + idx);
+    // a number
+    if code[start_byte..].starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((start_byte, end_byte))
+}
+
+/// Reuses the debloated code of the cache if the module did not change
+fn debloat_module(module: &Module, cached: Option<&ModuleEntry>) -> Result<Debloated> {
+    let code = match cached {
+        Some(entry) => entry.debloated.clone(),
+        None => {
+            let origin_nodes = node::get_nodes(module.tree.walk(), Order::Pre, &module.origin);
+            debloat::debloat_control_flow(&origin_nodes, &module.origin, &module.filename)
+        }
+    };
+    let tree = node::get_tree(&code)?;
+    Ok((code, tree))
+}
+
+fn get_units<'a>(project: &'a Project, debloated: &'a [Debloated]) -> Vec<Unit<'a>> {
+    project
+        .modules
+        .iter()
+        .zip(debloated)
+        .map(|(module, (code, tree))| Unit::new(module, tree, code))
+        .collect()
+}
+
+/// Analyzes `filename` along with the modules it imports
+pub fn analyze_file(filename: &str, config: &Config) -> Result<Vec<Diagnostic>> {
+    let project = Project::load(&[filename.to_string()], config.thread_count())?;
+    analyze_project(&project, config)
+}
+
+/// `analyze_file` for the unsaved content `origin` of `filename`
+pub fn analyze_source(filename: &str, origin: &str, config: &Config) -> Result<Vec<Diagnostic>> {
+    let mut project = Project::default();
+    project
+        .modules
+        .push(Module::parse(filename, origin.to_string())?);
+    project.load_imports(config.thread_count())?;
+    analyze_project(&project, config)
+}
+
+/// Debloats every module in memory and infers every function declaration once per distinct
+/// argument types of its callsites, including the callsites of the modules importing it. A
+/// function that is never called is inferred with the parameter types of the runtime trace of the
+/// configuration, or with `Unknown` parameters if it was not traced. A called function whose
+/// traced parameter types were not inferred is reported as `trace-mismatch`. With
+/// `Precision::Fast`, every function is inferred once with the joined argument types.
+///
+/// A call is typed with the return type its callee had in the previous round, so the modules are
+/// inferred again until the return types reach a fixed point.
+///
+/// With a cache, a function whose inputs did not change since the last run is not inferred again.
+/// The return types of the last run are the starting point, so editing a module re-infers its
+/// functions, the functions it calls and the functions whose callees now return another type.
+pub fn analyze_project(project: &Project, config: &Config) -> Result<Vec<Diagnostic>> {
+    Ok(analyze(project, config, false)?.diagnostics)
+}
+
+/// `analyze_project` that also records the types of the variables to query them by position.
+/// Results of the cache have no types, so every function is inferred again.
+pub fn analyze_project_typs(project: &Project, config: &Config) -> Result<Analysis> {
+    analyze(project, config, true)
+}
+
+fn analyze(project: &Project, config: &Config, record_typs: bool) -> Result<Analysis> {
+    let jobs = config.thread_count();
+    let traces = match config.trace_file() {
+        Some(path) => Traces::load(&path.to_string_lossy())?,
+        None => Traces::default(),
+    };
+    let mut stubs = Stubs::load(&config.stub_dirs())?;
+    env::load(&mut stubs, config.profiles())?;
+    let cache = config.cache_dir().map(|dir| Cache::new(&dir));
+    let hashes = project
+        .modules
+        .iter()
+        .map(|module| cache::hash(&module.origin))
+        .collect::<Vec<_>>();
+    let cached = project
+        .modules
+        .iter()
+        .zip(&hashes)
+        .map(|(module, hash)| {
+            cache
+                .as_ref()
+                .and_then(|cache| cache.load(&module.filename, hash))
+        })
+        .collect::<Vec<_>>();
+    let inputs = project.modules.iter().zip(&cached).collect::<Vec<_>>();
+    let debloated = parallel::map(
+        &inputs,
+        jobs,
+        || (),
+        |_, (module, cached)| debloat_module(module, cached.as_ref()),
+    )
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+    let mut reporter = Reporter::default();
+    if record_typs {
+        reporter.record_typs();
+    }
+    for module in &project.modules {
+        reporter.add_source(&module.filename, &module.origin)?;
+    }
+    for (module, (code, tree)) in project.modules.iter().zip(&debloated) {
+        if tree.root_node().kind() != PROGRAM {
+            reporter.report_unparsed(&module.filename, tree, code);
+            continue;
+        }
+        // generators are not inferred, so calls of them are `Unknown`
+        for name in traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == GENERATOR_FUNC_DECL)
+            .filter_map(|generator| generator.child_by_field_name("name"))
+        {
+            let name = Node {
+                info: name,
+                text: &code[name.byte_range()],
+            };
+            let reason = "generators are not supported";
+            reporter.report_unanalyzed(&name, code, &module.filename, reason);
+        }
+    }
+
+    let mut returns = Returns::new();
+    for entry in cached.iter().flatten() {
+        for func in &entry.funcs {
+            returns.insert(
+                (entry.filename.clone(), func.name.clone()),
+                func.return_typ.clone(),
+            );
+        }
+    }
+    let round = Round {
+        project,
+        debloated: &debloated,
+        hashes: &hashes,
+        cached: &cached,
+        reporter: &reporter,
+        config,
+        traces: &traces,
+        stubs: &stubs,
+        record_typs,
+    };
+    let mut count = 1;
+    let inferred = loop {
+        let inferred = round.infer(&returns)?;
+        let next = inferred
+            .iter()
+            .map(|func| {
+                let filename = project.modules[func.unit_idx].filename.clone();
+                ((filename, func.name.clone()), func.typ.clone())
+            })
+            .collect::<Returns>();
+        if next == returns || count == MAX_ROUNDS {
+            break inferred;
+        }
+        returns = next;
+        count += 1;
+    };
+    if let Some(cache) = &cache {
+        round.store(cache, &inferred)?;
+    }
+
+    let mut typs = Types::default();
+    for func in inferred {
+        reporter.extend(func.diags);
+        typs.extend(func.typs);
+    }
+    let mut diags = reporter.into_diagnostics();
+    let is_declared = |name: &str| {
+        builtins::is_global(name)
+            || stubs.global(name).is_some()
+            || config.globals.contains_key(name)
+    };
+    for module in &project.modules {
+        diags.extend(env::check_undeclared(
+            &module.filename,
+            &module.origin,
+            &module.tree,
+            is_declared,
+        ));
+    }
+    for module in &project.modules {
+        diags = suppress::apply(&module.filename, &module.origin, &module.tree, diags);
+    }
+    // excluded modules are still inferred for the types they export
+    diags.retain(|diag| !config.is_excluded(&diag.span.file));
+    Ok(Analysis {
+        diagnostics: config.apply(diags),
+        typs,
+        sources: project
+            .modules
+            .iter()
+            .map(|module| (module.filename.clone(), module.origin.clone()))
+            .collect(),
+    })
+}
+
+/// What every round of `analyze_project` infers with
+struct Round<'a> {
+    project: &'a Project,
+    debloated: &'a [Debloated],
+    /// of the original sources
+    hashes: &'a [String],
+    cached: &'a [Option<ModuleEntry>],
+    reporter: &'a Reporter,
+    config: &'a Config,
+    traces: &'a Traces,
+    stubs: &'a Stubs,
+    /// cache hits are inferred again for their types
+    record_typs: bool,
+}
+
+impl Round<'_> {
+    /// Infers every function with the return types of the previous round. Functions are inferred
+    /// concurrently, each reporting to a fork of the reporter, and are returned in the order of
+    /// the modules and of the function declarations, whatever the scheduling.
+    fn infer(&self, returns: &Returns) -> Result<Vec<Inferred>> {
+        let units = get_units(self.project, self.debloated);
+        let summaries = units
+            .iter()
+            .map(|unit| get_summaries(self.project, unit, returns, self.stubs))
+            .collect::<Vec<_>>();
+        // (unit index, function index, key) of every function
+        let mut funcs = vec![];
+        for (unit_idx, unit) in units.iter().enumerate() {
+            let callers = units
+                .iter()
+                .enumerate()
+                .filter(|(_, caller)| {
+                    let module = &unit.module.filename;
+                    caller
+                        .module
+                        .imports
+                        .values()
+                        .map(|import| &import.module)
+                        .chain(caller.module.namespaces.values())
+                        .any(|imported| imported == module)
+                })
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            for (func_idx, (_, name)) in unit.funcs.iter().enumerate() {
+                let key = self.func_key(&summaries, &callers, unit_idx, func_idx, name);
+                funcs.push((unit_idx, func_idx, key));
+            }
+        }
+        let globals = self.config.global_typs();
+        parallel::map(
+            &funcs,
+            self.config.thread_count(),
+            || get_units(self.project, self.debloated),
+            |units, (unit_idx, func_idx, key)| {
+                let unit = &units[*unit_idx];
+                let (func, name) = &unit.funcs[*func_idx];
+                let mut inferred = Inferred {
+                    unit_idx: *unit_idx,
+                    name: name.to_string(),
+                    key: key.clone(),
+                    typ: JSTyp::Unknown,
+                    diags: vec![],
+                    typs: Types::default(),
+                };
+                let hit = self.cached[*unit_idx]
+                    .as_ref()
+                    .and_then(|entry| entry.funcs.get(*func_idx))
+                    .filter(|entry| entry.name == *name && entry.key == *key)
+                    .filter(|_| !self.record_typs);
+                if let Some(entry) = hit {
+                    inferred.typ = entry.return_typ.clone();
+                    inferred.diags = entry.diagnostics.clone();
+                    return Ok(inferred);
+                }
+
+                let mut fork = self.reporter.fork();
+                let mut seeds =
+                    get_seeds(&mut fork, units, &summaries, unit, func, name, self.traces);
+                if self.config.precision == Precision::Fast {
+                    seeds = join_seeds(seeds);
+                }
+                let mut infer_seeds = || -> Result<Vec<JSTyp>> {
+                    report_trace_mismatches(&mut fork, unit, func, name, &seeds, self.traces)?;
+                    let mut typs = vec![];
+                    for (param_typs, this, callsite) in &seeds {
+                        let mut vars = HashMap::new();
+                        infer::declare_globals(&mut vars, &globals, func, unit.code);
+                        let call = infer::Call {
+                            summaries: &summaries[*unit_idx],
+                            this,
+                            callsite: callsite.as_ref(),
+                        };
+                        typs.push(infer::run_func(
+                            &mut vars,
+                            &mut fork,
+                            &call,
+                            param_typs,
+                            &unit.nodes,
+                            func,
+                            unit.code,
+                        )?);
+                    }
+                    Ok(typs)
+                };
+                // a function the inference fails on returns `Unknown` rather than aborting the
+                // analysis of the project
+                inferred.typ = match infer_seeds() {
+                    Ok(typs) => join_typs(typs),
+                    Err(e) => {
+                        if let Some(name) = node::get_field(func, "name", unit.code) {
+                            let reason = e.to_string();
+                            fork.report_unanalyzed(
+                                &name,
+                                unit.code,
+                                &unit.module.filename,
+                                &reason,
+                            );
+                        }
+                        JSTyp::Unknown
+                    }
+                };
+                (inferred.diags, inferred.typs) = fork.into_parts();
+                Ok(inferred)
+            },
+        )
+        .into_iter()
+        .collect()
+    }
+
+    /// Hash of everything the inference of a function depends on: the configuration, its runtime
+    /// trace, the source, the callee return types and the stub declarations of its module, and
+    /// those of the modules calling it
+    fn func_key(
+        &self,
+        summaries: &[Summaries],
+        callers: &[usize],
+        unit_idx: usize,
+        func_idx: usize,
+        name: &str,
+    ) -> String {
+        let mut input = format!(
+            "{}\n{:?}\n{:?}\n{}\n{}",
+            cache::version(),
+            self.config.precision,
+            self.config.globals,
+            func_idx,
+            name
+        );
+        input += &format!(
+            "\n{:?}",
+            self.traces
+                .params(&self.project.modules[unit_idx].filename, name)
+        );
+        for idx in std::iter::once(&unit_idx).chain(callers) {
+            let sorted = summaries[*idx].returns.iter().collect::<BTreeMap<_, _>>();
+            input += &format!(
+                "\n{}\n{:?}\n{:?}",
+                self.hashes[*idx], sorted, summaries[*idx].stubs
+            );
+        }
+        cache::hash(&input)
+    }
+
+    /// Writes the modules whose functions were inferred again
+    fn store(&self, cache: &Cache, inferred: &[Inferred]) -> Result<()> {
+        for (idx, module) in self.project.modules.iter().enumerate() {
+            let funcs = inferred
+                .iter()
+                .filter(|func| func.unit_idx == idx)
+                .map(|func| FuncEntry {
+                    name: func.name.clone(),
+                    key: func.key.clone(),
+                    return_typ: func.typ.clone(),
+                    diagnostics: func.diags.clone(),
+                })
+                .collect::<Vec<_>>();
+            let unchanged = self.cached[idx].as_ref().is_some_and(|entry| {
+                entry.funcs.len() == funcs.len()
+                    && entry
+                        .funcs
+                        .iter()
+                        .zip(&funcs)
+                        .all(|(old, new)| old.key == new.key)
+            });
+            if unchanged {
+                continue;
+            }
+            cache.store(&ModuleEntry {
+                version: cache::version(),
+                filename: module.filename.clone(),
+                hash: self.hashes[idx].clone(),
+                debloated: self.debloated[idx].0.clone(),
+                funcs,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Return types of the functions `unit` declares or imports, keyed by the name it calls them by,
+/// and the stub declarations it sees
+fn get_summaries(project: &Project, unit: &Unit, returns: &Returns, stubs: &Stubs) -> Summaries {
+    let mut summaries = Summaries {
+        stubs: stubs.bindings(unit.module),
+        ..Summaries::default()
+    };
+    for (local, import) in &unit.module.imports {
+        if let Some(typ) = project
+            .resolve_import(import)
+            .and_then(|name| returns.get(&(import.module.clone(), name.to_string())))
+        {
+            summaries.returns.insert(local.clone(), typ.clone());
+        }
+    }
+    for (_, name) in &unit.funcs {
+        if let Some(typ) = returns.get(&(unit.module.filename.clone(), name.to_string())) {
+            summaries.returns.insert(name.to_string(), typ.clone());
+        }
+    }
+    if unit.parsed() {
+        summaries.methods = callgraph::gather_methods(&summaries, &unit.program, unit.code);
+    }
+    // e.g., `ns.f()` of `import * as ns`, a method of the namespace named `ns.f` in `returns`
+    for (ns, import) in project.namespace_imports(unit.module) {
+        let member = format!("{}.{}", ns, import.name);
+        if let Some(typ) = project
+            .resolve_import(&import)
+            .and_then(|name| returns.get(&(import.module.clone(), name.to_string())))
+        {
+            summaries.returns.insert(member.clone(), typ.clone());
+        }
+        summaries
+            .methods
+            .insert((ns, import.name), (member, Props::new()));
+    }
+    summaries
+}
+
+/// Parameter types to infer `func`, named `name` in `unit`, with and the first callsite passing
+/// them, in module and callsite order. Importing modules call `func` by their local binding.
+/// Without callsites, the seeds come from `traces`.
+fn get_seeds<'a>(
+    reporter: &mut Reporter,
+    units: &[Unit<'a>],
+    summaries: &[Summaries],
+    unit: &Unit<'a>,
+    func: &Node<'a>,
+    name: &str,
+    traces: &Traces,
+) -> Vec<Seed> {
+    let mut seeds: Vec<Seed> = vec![];
+    for (caller, caller_summaries) in units.iter().zip(summaries) {
+        if !caller.parsed() {
+            continue;
+        }
+        let locals = if std::ptr::eq(caller, unit) {
+            vec![name.to_string()]
+        } else {
+            let exported = unit
+                .module
+                .exports
+                .iter()
+                .filter(|(_, local)| *local == name)
+                .map(|(exported, _)| exported)
+                .collect::<Vec<_>>();
+            let imported = caller
+                .module
+                .imports
+                .iter()
+                .filter(|(_, import)| {
+                    import.module == unit.module.filename && exported.contains(&&import.name)
+                })
+                .map(|(local, _)| local.clone());
+            // e.g., `ns.f`, which is a method of the namespace
+            let namespaced = caller
+                .module
+                .namespaces
+                .iter()
+                .filter(|(_, module)| **module == unit.module.filename)
+                .flat_map(|(ns, _)| exported.iter().map(move |name| format!("{}.{}", ns, name)));
+            imported.chain(namespaced).collect()
+        };
+        for local in locals {
+            for (callsite, param_typs, this) in
+                callgraph::gather_callsites(&local, caller_summaries, &caller.program, caller.code)
+            {
+                if !seeds
+                    .iter()
+                    .any(|(typs, this_, _)| *typs == param_typs && *this_ == this)
+                {
+                    let span = reporter.get_orig_span(&callsite, caller.code).ok();
+                    seeds.push((param_typs, this, span));
+                }
+            }
+        }
+    }
+    if seeds.is_empty() {
+        let n_params = get_params(func, unit.code).len();
+        seeds.extend(
+            traces
+                .seeds(&unit.module.filename, name, n_params)
+                .into_iter()
+                .map(|param_typs| (param_typs, Props::new(), None)),
+        );
+    }
+    if seeds.is_empty() {
+        let n_params = get_params(func, unit.code).len();
+        seeds.push((vec![JSTyp::Unknown; n_params], Props::new(), None));
+    }
+    seeds
+}
+
+fn get_params<'a>(func: &Node<'a>, code: &'a str) -> Vec<Node<'a>> {
+    let Some(params) = node::get_field(func, "parameters", code) else {
+        return vec![];
+    };
+    let mut cursor = params.info.walk();
+    params
+        .info
+        .named_children(&mut cursor)
+        .filter(|param| param.kind() != COMMENT)
+        .map(|param| Node {
+            info: param,
+            text: &code[param.byte_range()],
+        })
+        .collect()
+}
+
+/// Reports every parameter of `func` traced with a type none of the callsites of `seeds` passes
+fn report_trace_mismatches<'a>(
+    reporter: &mut Reporter,
+    unit: &Unit<'a>,
+    func: &Node<'a>,
+    name: &str,
+    seeds: &[Seed],
+    traces: &Traces,
+) -> Result<()> {
+    // seeded by the trace itself
+    if seeds.iter().all(|(_, _, callsite)| callsite.is_none()) {
+        return Ok(());
+    }
+    let Some(observed) = traces.params(&unit.module.filename, name) else {
+        return Ok(());
+    };
+    for (idx, param) in get_params(func, unit.code).iter().enumerate() {
+        let mut inferred = seeds
+            .iter()
+            .map(|(typs, _, _)| typs.get(idx).cloned().unwrap_or(JSTyp::Undefined))
+            .collect::<Vec<_>>();
+        inferred.sort();
+        inferred.dedup();
+        for name_observed in observed.get(idx).into_iter().flatten() {
+            if !trace::is_observable(name_observed, &inferred) {
+                reporter.report_trace_mismatch(param, unit.code, name, name_observed, &inferred)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A single seed whose parameters keep their type only if every seed agrees on it. `this` has
+/// the properties every seed has.
+fn join_seeds(seeds: Vec<Seed>) -> Vec<Seed> {
+    let n_params = seeds
+        .iter()
+        .map(|(typs, _, _)| typs.len())
+        .max()
+        .unwrap_or(0);
+    let param_typs = (0..n_params)
+        .map(|idx| {
+            join_typs(
+                seeds
+                    .iter()
+                    .map(|(typs, _, _)| typs.get(idx).cloned().unwrap_or(JSTyp::Undefined)),
+            )
+        })
+        .collect();
+    let this = match seeds.first() {
+        Some((_, first, _)) => first
+            .keys()
+            .filter(|prop| seeds.iter().all(|(_, this, _)| this.contains_key(*prop)))
+            .map(|prop| {
+                let typ = join_typs(seeds.iter().map(|(_, this, _)| this[prop].clone()));
+                (prop.clone(), typ)
+            })
+            .collect(),
+        None => Props::new(),
+    };
+    let callsite = seeds.into_iter().find_map(|(_, _, callsite)| callsite);
+    vec![(param_typs, this, callsite)]
+}
+
+/// The type all of `typs` agree on, otherwise `Unknown`
+fn join_typs(typs: impl IntoIterator<Item = JSTyp>) -> JSTyp {
+    let mut typs = typs.into_iter();
+    let first = typs.next().unwrap_or(JSTyp::Unknown);
+    if typs.all(|typ| typ == first) {
+        first
+    } else {
+        JSTyp::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::{
+        Severity, ARITH_COERCION, BIGINT_MIX, CMP_MISMATCH, STUB_ARG_MISMATCH, TRACE_MISMATCH,
+        UNDECLARED_GLOBAL, UNSUPPORTED_CONSTRUCT,
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_analyze_every_function() {
+        let diags = analyze_file("test/analysis/every_function.js", &Config::default()).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| (diag.rule, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        // `foo` once per distinct seed; `bar` is never called so `a` is Unknown
+        assert_eq!(
+            found,
+            vec![
+                (CMP_MISMATCH, "String == Number", 2),
+                (CMP_MISMATCH, "Null == Number", 2),
+                (ARITH_COERCION, "Bool + Bool", 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unparsed() {
+        // debloated code that does not parse is noted at the construct it came from, here the
+        // `do` of a do-while whose body was dropped
+        let filename = "test/analysis/unparsed.js";
+        let code = concat!(
+            "do // [Loc] test/analysis/unparsed.js:4:1, [Bytes] 34-36, [Non-branch], [Parent-ID] 0,\n",
+            "f ( \"1\" ) ; // [Loc] test/analysis/unparsed.js:5:3, [Bytes] 41-48, [Non-branch], [Parent-ID] 1,\n",
+        );
+        let tree = node::get_tree(code).unwrap();
+        let mut reporter = Reporter::default();
+        let origin = std::fs::read_to_string(filename).unwrap();
+        reporter.add_source(filename, &origin).unwrap();
+        reporter.report_unparsed(filename, &tree, code);
+        let found = reporter
+            .diagnostics()
+            .iter()
+            .map(|diag| (diag.rule, diag.span.start_line))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(UNSUPPORTED_CONSTRUCT, 4)]);
+    }
+
+    #[test]
+    fn test_generators() {
+        // generators are noted rather than inferred, and a top-level do-while is analyzed
+        let diags = analyze_file("test/analysis/generators.js", &Config::default()).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| (diag.rule, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    UNSUPPORTED_CONSTRUCT,
+                    "`inner` is not analyzed: generators are not supported",
+                    2
+                ),
+                (
+                    UNSUPPORTED_CONSTRUCT,
+                    "`gen` is not analyzed: generators are not supported",
+                    10
+                ),
+                (ARITH_COERCION, "Bool + Number", 5),
+                (ARITH_COERCION, "String + Number", 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let overrides = [
+            "precision=fast",
+            "rules.arith-coercion=error",
+            "globals.VERSION=string",
+        ];
+        let overrides = overrides.map(|o| o.to_string());
+        let config = Config::parse("", PathBuf::from("."), &overrides).unwrap();
+        let diags = analyze_file("test/analysis/config.js", &config).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| (diag.severity, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        // `a` is String at one callsite and Null at the other, so it is Unknown when joined;
+        // `b` is Number at both
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, "Bool + Number", 3),
+                (Severity::Error, "String - Number", 4),
+            ]
+        );
+
+        let config =
+            Config::parse("[rules]\narith-coercion = \"off\"", PathBuf::from("."), &[]).unwrap();
+        let diags = analyze_file("test/analysis/config.js", &config).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| (diag.severity, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Warning, "Number == String", 5),
+                (Severity::Warning, "Number == Null", 5),
+                (
+                    Severity::Warning,
+                    "`VERSION` is declared neither in the module nor in the environment",
+                    4
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_project() {
+        let diags = analyze_file("test/project/main.js", &Config::default()).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| {
+                (
+                    diag.rule,
+                    diag.message.as_str(),
+                    diag.span.file.as_str(),
+                    diag.span.start_line,
+                )
+            })
+            .collect::<Vec<_>>();
+        // return types flow from `math.js`, `lib/index.js` and `legacy.cjs` into `main`, and the
+        // argument of `half(true)` flows into `halve` of `math.js`
+        assert_eq!(
+            found,
+            vec![
+                (ARITH_COERCION, "String - Number", "test/project/main.js", 8),
+                (BIGINT_MIX, "BigInt + Number", "test/project/main.js", 9),
+                (ARITH_COERCION, "Bool / Number", "test/project/math.js", 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let diags = analyze_file("test/project/namespaces.js", &Config::default()).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| {
+                (
+                    diag.rule,
+                    diag.message.as_str(),
+                    diag.span.file.as_str(),
+                    diag.span.start_line,
+                )
+            })
+            .collect::<Vec<_>>();
+        // `math.halve(true)` of `import * as math` and `shapes.scale(undefined)` of
+        // `const shapes = require(...)` seed the functions they call, whose return types flow back
+        assert_eq!(
+            found,
+            vec![
+                (
+                    ARITH_COERCION,
+                    "String - Number",
+                    "test/project/namespaces.js",
+                    7
+                ),
+                (
+                    ARITH_COERCION,
+                    "Undefined * Number",
+                    "test/project/shapes.cjs",
+                    2
+                ),
+                (ARITH_COERCION, "Bool / Number", "test/project/math.js", 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_at() {
+        let file = "test/analysis/type_at.js";
+        let project = Project::load(&[file.to_string()], 1).unwrap();
+        let analysis = analyze_project_typs(&project, &Config::default()).unwrap();
+        let type_at = |line, column| analysis.type_at(file, line, column);
+        assert_eq!(type_at(2, 7), Some(JSTyp::String));
+        assert_eq!(type_at(3, 10), Some(JSTyp::Number));
+        assert_eq!(type_at(5, 10), Some(JSTyp::Number));
+        // `w` is a Number at one callsite and a String at the other
+        assert_eq!(type_at(1, 15), Some(JSTyp::Unknown));
+        let byte = project.modules[0].origin.find("w * h").unwrap();
+        let (span, typs) = analysis.typs_at(file, byte).unwrap();
+        assert_eq!((span.start_line, span.start_column), (3, 14));
+        assert_eq!(typs, vec![JSTyp::Number, JSTyp::String]);
+        // arguments of the unsupported call are typed by the environment before the statement
+        assert_eq!(type_at(4, 15), Some(JSTyp::Number));
+        assert_eq!(type_at(4, 21), Some(JSTyp::String));
+        // `console`, keywords, numbers, top-level code and positions out of the file
+        assert_eq!(type_at(4, 3), None);
+        assert_eq!(type_at(2, 3), None);
+        assert_eq!(type_at(8, 6), None);
+        assert_eq!(type_at(8, 1), None);
+        assert_eq!(type_at(20, 1), None);
+        assert_eq!(type_at(0, 1), None);
+    }
+
+    #[test]
+    fn test_trace() {
+        let overrides = ["trace=test/analysis/trace.jsonl".to_string()];
+        let config = Config::parse("", PathBuf::from("."), &overrides).unwrap();
+        let diags = analyze_file("test/analysis/trace.js", &config).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| (diag.rule, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        // `scale` is never called, so it is inferred with its traced types; `label` is called with
+        // a String but traced with a Number too
+        assert_eq!(
+            found,
+            vec![
+                (BIGINT_MIX, "BigInt * Number", 2),
+                (
+                    TRACE_MISMATCH,
+                    "`name` of `label` is a `number` at runtime but inferred as String",
+                    5
+                ),
+            ]
+        );
+
+        let overrides = ["trace=test/analysis/missing.jsonl".to_string()];
+        let config = Config::parse("", PathBuf::from("."), &overrides).unwrap();
+        assert!(analyze_file("test/analysis/trace.js", &config).is_err());
+    }
+
+    #[test]
+    fn test_stubs() {
+        let file = "test/stubs/main.js";
+        let overrides = ["stubs=[\"test/stubs/stubs\"]".to_string()];
+        let config = Config::parse("", PathBuf::from("."), &overrides).unwrap();
+        let project = Project::load(&[file.to_string()], 1).unwrap();
+        let analysis = analyze_project_typs(&project, &config).unwrap();
+        let found = analysis
+            .diagnostics
+            .iter()
+            .map(|diag| (diag.rule, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    STUB_ARG_MISMATCH,
+                    "`padStart` expects String, found Number",
+                    6
+                ),
+                (
+                    STUB_ARG_MISMATCH,
+                    "`sdk.track` expects String, found Number",
+                    8
+                ),
+                (
+                    STUB_ARG_MISMATCH,
+                    "`onReady` expects a function, found Number",
+                    9
+                ),
+            ]
+        );
+        // typed by the declared return types
+        assert_eq!(analysis.type_at(file, 5, 7), Some(JSTyp::Array));
+        assert_eq!(analysis.type_at(file, 7, 7), Some(JSTyp::Number));
+        assert_eq!(analysis.type_at(file, 10, 10), Some(JSTyp::String));
+
+        // without stubs, the calls are unsupported and the globals undeclared
+        let diags = analyze_file(file, &Config::default()).unwrap();
+        assert!(diags
+            .iter()
+            .all(|diag| diag.rule == UNSUPPORTED_CONSTRUCT || diag.rule == UNDECLARED_GLOBAL));
+        let undeclared = diags
+            .iter()
+            .filter(|diag| diag.rule == UNDECLARED_GLOBAL)
+            .map(|diag| diag.span.start_line)
+            .collect::<Vec<_>>();
+        assert_eq!(undeclared, vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_methods() {
+        let file = "test/analysis/methods.js";
+        let project = Project::load(&[file.to_string()], 1).unwrap();
+        let analysis = analyze_project_typs(&project, &Config::default()).unwrap();
+        let found = analysis
+            .diagnostics
+            .iter()
+            .map(|diag| (diag.rule, diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        // `this` of `area` is `rect` at the first callsite and `other` at the second
+        assert_eq!(found, vec![(ARITH_COERCION, "Number * String", 2)]);
+        assert_eq!(analysis.type_at(file, 7, 7), Some(JSTyp::Number));
+        assert_eq!(analysis.type_at(file, 9, 7), Some(JSTyp::Number));
+
+        // joined, `this.h` is Unknown
+        let config = Config::parse("", PathBuf::from("."), &["precision=fast".to_string()]);
+        let diags = analyze_project(&project, &config.unwrap()).unwrap();
+        assert!(diags.is_empty());
+    }
+
+    #[test]
+    fn test_env() {
+        let file = "test/env/main.js";
+        let undeclared = |env: &str| {
+            let overrides = [format!("env={}", env)];
+            let config = Config::parse("", PathBuf::from("."), &overrides).unwrap();
+            let analysis =
+                analyze_project_typs(&Project::load(&[file.to_string()], 1).unwrap(), &config)
+                    .unwrap();
+            let names = analysis
+                .diagnostics
+                .iter()
+                .filter(|diag| diag.rule == UNDECLARED_GLOBAL)
+                .map(|diag| diag.message.split('`').nth(1).unwrap().to_string())
+                .collect::<Vec<_>>();
+            (analysis, names)
+        };
+        let (analysis, names) = undeclared(r#"["browser"]"#);
+        assert_eq!(names, vec!["process"]);
+        assert_eq!(analysis.type_at(file, 2, 7), Some(JSTyp::String));
+        assert!(analysis
+            .diagnostics
+            .iter()
+            .any(|diag| diag.rule == STUB_ARG_MISMATCH && diag.span.start_line == 3));
+        assert_eq!(
+            undeclared(r#"["node"]"#).1,
+            vec!["document", "localStorage"]
+        );
+        assert!(undeclared(r#"["browser", "node"]"#).1.is_empty());
+        assert_eq!(
+            undeclared(r#"["none"]"#).1,
+            vec!["document", "localStorage", "process"]
+        );
+    }
+
+    #[test]
+    fn test_parallel() {
+        let files = [
+            "test/project/main.js",
+            "example/example.js",
+            "test/analysis/every_function.js",
+        ];
+        let project = Project::load(&files.map(|file| file.to_string()), 1).unwrap();
+        let analyze = |jobs: &str| {
+            let overrides = [format!("jobs={}", jobs)];
+            let config = Config::parse("", PathBuf::from("."), &overrides).unwrap();
+            analyze_project(&project, &config).unwrap()
+        };
+        let serial = analyze("1");
+        assert!(!serial.is_empty());
+        for _ in 0..4 {
+            assert_eq!(analyze("4"), serial);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::diagnostic::Diagnostic;
+use crate::error::{Result, TypeinferError};
+use crate::jssyntax::FUNC_DECL;
+use crate::node;
+use crate::util;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tree_sitter::Tree;
+use tree_sitter_traversal::{traverse, Order};
+
+const BASELINE_VERSION: u32 = 1;
+
+/// A known diagnostic. The fingerprint does not depend on lines or columns so that the entry
+/// still matches after unrelated code moves the diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Entry {
+    pub rule: String,
+    pub file: String,
+    pub fingerprint: String,
+    /// for readers of the baseline file only
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub version: u32,
+    pub entries: Vec<Entry>,
+}
+
+/// Original sources the fingerprints are computed from
+#[derive(Default)]
+struct Sources(HashMap<String, (String, Tree)>);
+impl Sources {
+    fn get(&mut self, filename: &str) -> Result<&(String, Tree)> {
+        if !self.0.contains_key(filename) {
+            let code = util::read_file(filename)?;
+            let tree = node::get_tree(&code)?;
+            self.0.insert(filename.to_string(), (code, tree));
+        }
+        Ok(&self.0[filename])
+    }
+}
+
+/// Hash of the whitespace-normalized code of the diagnostic and the name of its enclosing function
+pub(crate) fn fingerprint(diag: &Diagnostic, code: &str, tree: &Tree) -> String {
+    let snippet = code
+        .get(diag.span.start_byte..diag.span.end_byte)
+        .unwrap_or("")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let func = traverse(tree.walk(), Order::Pre)
+        .filter(|node| {
+            node.kind() == FUNC_DECL
+                && node.start_byte() <= diag.span.start_byte
+                && diag.span.end_byte <= node.end_byte()
+        })
+        .last()
+        .and_then(|func| func.child_by_field_name("name"))
+        .map_or("", |name| &code[name.byte_range()]);
+    let key = format!("{}\n{}\n{}", diag.rule, func, snippet);
+    format!("{:016x}", util::fnv1a(key.as_bytes()))
+}
+
+impl Baseline {
+    pub fn from_diagnostics(diags: &[Diagnostic]) -> Result<Self> {
+        let mut sources = Sources::default();
+        let mut entries = vec![];
+        for diag in diags {
+            let (code, tree) = sources.get(&diag.span.file)?;
+            entries.push(Entry {
+                rule: diag.rule.to_string(),
+                file: diag.span.file.clone(),
+                fingerprint: fingerprint(diag, code, tree),
+                message: diag.message.clone(),
+            });
+        }
+        Ok(Self {
+            version: BASELINE_VERSION,
+            entries,
+        })
+    }
+
+    pub fn load(filename: &str) -> Result<Self> {
+        let content = util::read_file(filename)?;
+        let baseline: Self = serde_json::from_str(&content)
+            .map_err(|e| TypeinferError::Baseline(format!("{}: {}", filename, e)))?;
+        if baseline.version != BASELINE_VERSION {
+            return Err(TypeinferError::Baseline(format!(
+                "{}: unsupported version {}",
+                filename, baseline.version
+            )));
+        }
+        Ok(baseline)
+    }
+
+    pub fn write(&self, filename: &str) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| TypeinferError::Baseline(e.to_string()))?;
+        util::write_file(filename, &content)
+    }
+
+    /// Diagnostics not in the baseline. An entry recorded `n` times hides at most `n` diagnostics
+    /// with the same key.
+    pub fn filter_new(&self, diags: Vec<Diagnostic>) -> Result<Vec<Diagnostic>> {
+        let mut known = HashMap::new();
+        for entry in &self.entries {
+            *known
+                .entry((
+                    entry.rule.clone(),
+                    entry.file.clone(),
+                    entry.fingerprint.clone(),
+                ))
+                .or_insert(0) += 1;
+        }
+        let mut sources = Sources::default();
+        let mut new = vec![];
+        for diag in diags {
+            let (code, tree) = sources.get(&diag.span.file)?;
+            let key = (
+                diag.rule.to_string(),
+                diag.span.file.clone(),
+                fingerprint(&diag, code, tree),
+            );
+            match known.get_mut(&key) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => new.push(diag),
+            }
+        }
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::config::Config;
+
+    #[test]
+    fn test_baseline() {
+        let dir = std::env::temp_dir().join(format!("typeinfer-baseline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("legacy.js").to_string_lossy().to_string();
+        let baseline_filename = dir.join("baseline.json").to_string_lossy().to_string();
+        let config = Config::default();
+
+        std::fs::copy("test/baseline/legacy.js", &filename).unwrap();
+        let diags = analysis::analyze_file(&filename, &config).unwrap();
+        assert_eq!(diags.len(), 3);
+        Baseline::from_diagnostics(&diags)
+            .unwrap()
+            .write(&baseline_filename)
+            .unwrap();
+
+        // the known diagnostics moved down and got reindented; one is new
+        std::fs::copy("test/baseline/legacy_edited.js", &filename).unwrap();
+        let diags = analysis::analyze_file(&filename, &config).unwrap();
+        assert_eq!(diags.len(), 4);
+        let baseline = Baseline::load(&baseline_filename).unwrap();
+        let new = baseline.filter_new(diags).unwrap();
+        let new = new
+            .iter()
+            .map(|diag| (diag.message.as_str(), diag.span.start_line))
+            .collect::<Vec<_>>();
+        assert_eq!(new, vec![("Null - Number", 8)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
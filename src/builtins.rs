@@ -0,0 +1,416 @@
+use crate::jssyntax::JSTyp;
+
+/// What a builtin does with an argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    /// any value, e.g., of `String(x)`
+    Any,
+    /// converted to a number: a BigInt or a Symbol throws and other types are coerced
+    Number,
+    /// converted to a number or a BigInt, e.g., by `Number(x)`: a Symbol throws
+    Numeric,
+    /// converted to a BigInt by `BigInt(x)`: `null`, `undefined` and a Symbol throw
+    BigInt,
+    /// converted to a string: a Symbol throws and other types are coerced
+    String,
+    /// converted to an object: `null` and `undefined` throw
+    Object,
+    /// called back: anything but a function throws
+    Callback,
+}
+
+/// How an argument of type `typ` is passed as `param`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// implicitly converted to the expected type
+    Coerced,
+    /// the builtin throws a `TypeError`
+    Throws,
+}
+
+impl Param {
+    /// `None` if a value of `typ` is what the builtin expects, or could be
+    pub fn check(&self, typ: &JSTyp) -> Option<Violation> {
+        use JSTyp::*;
+        match (self, typ) {
+            (_, Unknown | Never) | (Self::Any, _) => None,
+            (Self::Number, BigInt | Symbol) => Some(Violation::Throws),
+            (Self::Number, Number) => None,
+            (Self::Number, _) => Some(Violation::Coerced),
+            (Self::Numeric, Symbol) => Some(Violation::Throws),
+            (Self::Numeric, _) => None,
+            (Self::BigInt, Null | Undefined | Symbol) => Some(Violation::Throws),
+            (Self::BigInt, _) => None,
+            (Self::String, Symbol) => Some(Violation::Throws),
+            (Self::String, String) => None,
+            (Self::String, _) => Some(Violation::Coerced),
+            (Self::Object, Null | Undefined) => Some(Violation::Throws),
+            (Self::Object, _) => None,
+            // functions are not typed, so a typed value is not one
+            (Self::Callback, _) => Some(Violation::Throws),
+        }
+    }
+
+    /// The expected type, as in messages
+    pub fn expected(&self) -> &'static str {
+        match self {
+            Self::Any => "any value",
+            Self::Number => "a Number",
+            Self::Numeric => "a Number or a BigInt",
+            Self::BigInt => "a Number, a String or a BigInt",
+            Self::String => "a String",
+            Self::Object => "an Object",
+            Self::Callback => "a function",
+        }
+    }
+}
+
+/// Signature of a builtin function or method. Parameters without an argument are not checked,
+/// since most builtins have optional parameters.
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [Param],
+    /// every argument after `params`
+    pub rest: Option<Param>,
+    pub ret: JSTyp,
+}
+
+const fn func(name: &'static str, params: &'static [Param], ret: JSTyp) -> Builtin {
+    Builtin {
+        name,
+        params,
+        rest: None,
+        ret,
+    }
+}
+
+const fn variadic(name: &'static str, rest: Param, ret: JSTyp) -> Builtin {
+    Builtin {
+        name,
+        params: &[],
+        rest: Some(rest),
+        ret,
+    }
+}
+
+use Param::*;
+
+/// Global functions
+const GLOBALS: &[Builtin] = &[
+    func("parseInt", &[String, Number], JSTyp::Number),
+    func("parseFloat", &[String], JSTyp::Number),
+    func("isNaN", &[Number], JSTyp::Bool),
+    func("isFinite", &[Number], JSTyp::Bool),
+    func("String", &[Any], JSTyp::String),
+    func("Number", &[Numeric], JSTyp::Number),
+    func("Boolean", &[Any], JSTyp::Bool),
+    func("BigInt", &[BigInt], JSTyp::BigInt),
+    func("Symbol", &[String], JSTyp::Symbol),
+    func("encodeURIComponent", &[String], JSTyp::String),
+    func("decodeURIComponent", &[String], JSTyp::String),
+    func("encodeURI", &[String], JSTyp::String),
+    func("decodeURI", &[String], JSTyp::String),
+];
+
+const MATH: &[Builtin] = &[
+    variadic("max", Number, JSTyp::Number),
+    variadic("min", Number, JSTyp::Number),
+    variadic("hypot", Number, JSTyp::Number),
+    func("abs", &[Number], JSTyp::Number),
+    func("floor", &[Number], JSTyp::Number),
+    func("ceil", &[Number], JSTyp::Number),
+    func("round", &[Number], JSTyp::Number),
+    func("trunc", &[Number], JSTyp::Number),
+    func("sign", &[Number], JSTyp::Number),
+    func("sqrt", &[Number], JSTyp::Number),
+    func("cbrt", &[Number], JSTyp::Number),
+    func("exp", &[Number], JSTyp::Number),
+    func("log", &[Number], JSTyp::Number),
+    func("log2", &[Number], JSTyp::Number),
+    func("log10", &[Number], JSTyp::Number),
+    func("sin", &[Number], JSTyp::Number),
+    func("cos", &[Number], JSTyp::Number),
+    func("tan", &[Number], JSTyp::Number),
+    func("asin", &[Number], JSTyp::Number),
+    func("acos", &[Number], JSTyp::Number),
+    func("atan", &[Number], JSTyp::Number),
+    func("atan2", &[Number, Number], JSTyp::Number),
+    func("pow", &[Number, Number], JSTyp::Number),
+    func("random", &[], JSTyp::Number),
+];
+
+const MATH_PROPS: &[(&str, JSTyp)] = &[
+    ("PI", JSTyp::Number),
+    ("E", JSTyp::Number),
+    ("LN2", JSTyp::Number),
+    ("LN10", JSTyp::Number),
+    ("LOG2E", JSTyp::Number),
+    ("LOG10E", JSTyp::Number),
+    ("SQRT2", JSTyp::Number),
+    ("SQRT1_2", JSTyp::Number),
+];
+
+const JSON: &[Builtin] = &[
+    func("parse", &[String, Any], JSTyp::Unknown),
+    // `undefined` for `undefined`, functions and symbols, which are not what is serialized
+    func("stringify", &[Any], JSTyp::String),
+];
+
+const OBJECT: &[Builtin] = &[
+    func("keys", &[Object], JSTyp::Array),
+    func("values", &[Object], JSTyp::Array),
+    func("entries", &[Object], JSTyp::Array),
+    Builtin {
+        name: "assign",
+        params: &[Object],
+        rest: Some(Any),
+        ret: JSTyp::Object,
+    },
+    func("fromEntries", &[Object], JSTyp::Object),
+    func("is", &[Any, Any], JSTyp::Bool),
+];
+
+const NUMBER: &[Builtin] = &[
+    func("isInteger", &[Any], JSTyp::Bool),
+    func("isSafeInteger", &[Any], JSTyp::Bool),
+    func("isFinite", &[Any], JSTyp::Bool),
+    func("isNaN", &[Any], JSTyp::Bool),
+    func("parseInt", &[String, Number], JSTyp::Number),
+    func("parseFloat", &[String], JSTyp::Number),
+];
+
+const NUMBER_PROPS: &[(&str, JSTyp)] = &[
+    ("MAX_SAFE_INTEGER", JSTyp::Number),
+    ("MIN_SAFE_INTEGER", JSTyp::Number),
+    ("MAX_VALUE", JSTyp::Number),
+    ("MIN_VALUE", JSTyp::Number),
+    ("EPSILON", JSTyp::Number),
+    ("POSITIVE_INFINITY", JSTyp::Number),
+    ("NEGATIVE_INFINITY", JSTyp::Number),
+    ("NaN", JSTyp::Number),
+];
+
+const STRING: &[Builtin] = &[variadic("fromCharCode", Number, JSTyp::String)];
+
+const ARRAY: &[Builtin] = &[
+    func("isArray", &[Any], JSTyp::Bool),
+    func("from", &[Object, Callback], JSTyp::Array),
+    variadic("of", Any, JSTyp::Array),
+];
+
+/// Methods of `String.prototype`
+const STRING_PROTO: &[Builtin] = &[
+    func("charAt", &[Number], JSTyp::String),
+    func("charCodeAt", &[Number], JSTyp::Number),
+    func("indexOf", &[String, Number], JSTyp::Number),
+    func("lastIndexOf", &[String, Number], JSTyp::Number),
+    func("includes", &[String, Number], JSTyp::Bool),
+    func("startsWith", &[String, Number], JSTyp::Bool),
+    func("endsWith", &[String, Number], JSTyp::Bool),
+    func("slice", &[Number, Number], JSTyp::String),
+    func("substring", &[Number, Number], JSTyp::String),
+    func("toUpperCase", &[], JSTyp::String),
+    func("toLowerCase", &[], JSTyp::String),
+    func("trim", &[], JSTyp::String),
+    func("trimStart", &[], JSTyp::String),
+    func("trimEnd", &[], JSTyp::String),
+    func("padStart", &[Number, String], JSTyp::String),
+    func("padEnd", &[Number, String], JSTyp::String),
+    func("repeat", &[Number], JSTyp::String),
+    func("split", &[Any, Number], JSTyp::Array),
+    variadic("concat", String, JSTyp::String),
+    func("replace", &[Any, Any], JSTyp::String),
+    func("replaceAll", &[Any, Any], JSTyp::String),
+    func("localeCompare", &[String], JSTyp::Number),
+    func("normalize", &[String], JSTyp::String),
+    func("toString", &[], JSTyp::String),
+];
+
+/// Methods of `Array.prototype`
+const ARRAY_PROTO: &[Builtin] = &[
+    func("join", &[String], JSTyp::String),
+    func("map", &[Callback], JSTyp::Array),
+    func("filter", &[Callback], JSTyp::Array),
+    func("flatMap", &[Callback], JSTyp::Array),
+    func("forEach", &[Callback], JSTyp::Undefined),
+    func("some", &[Callback], JSTyp::Bool),
+    func("every", &[Callback], JSTyp::Bool),
+    func("findIndex", &[Callback], JSTyp::Number),
+    func("findLastIndex", &[Callback], JSTyp::Number),
+    func("reduce", &[Callback], JSTyp::Unknown),
+    func("includes", &[Any, Number], JSTyp::Bool),
+    func("indexOf", &[Any, Number], JSTyp::Number),
+    func("lastIndexOf", &[Any, Number], JSTyp::Number),
+    variadic("push", Any, JSTyp::Number),
+    variadic("unshift", Any, JSTyp::Number),
+    func("slice", &[Number, Number], JSTyp::Array),
+    variadic("concat", Any, JSTyp::Array),
+    func("reverse", &[], JSTyp::Array),
+    func("flat", &[Number], JSTyp::Array),
+    func("toString", &[], JSTyp::String),
+];
+
+/// Methods of `Number.prototype`
+const NUMBER_PROTO: &[Builtin] = &[
+    func("toFixed", &[Number], JSTyp::String),
+    func("toPrecision", &[Number], JSTyp::String),
+    func("toString", &[Number], JSTyp::String),
+];
+
+/// Globals of ECMAScript that are not modeled, which every environment has
+const UNMODELED_GLOBALS: &[&str] = &[
+    "globalThis",
+    "undefined",
+    "NaN",
+    "Infinity",
+    "eval",
+    "escape",
+    "unescape",
+    "Function",
+    "Date",
+    "RegExp",
+    "Map",
+    "Set",
+    "WeakMap",
+    "WeakSet",
+    "WeakRef",
+    "FinalizationRegistry",
+    "Promise",
+    "Proxy",
+    "Reflect",
+    "Intl",
+    "Atomics",
+    "ArrayBuffer",
+    "SharedArrayBuffer",
+    "DataView",
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "Float32Array",
+    "Float64Array",
+    "BigInt64Array",
+    "BigUint64Array",
+    "Error",
+    "AggregateError",
+    "EvalError",
+    "RangeError",
+    "ReferenceError",
+    "SyntaxError",
+    "TypeError",
+    "URIError",
+];
+
+/// Whether `name` is a global of ECMAScript, modeled or not
+pub fn is_global(name: &str) -> bool {
+    global(name).is_some() || is_namespace(name) || UNMODELED_GLOBALS.contains(&name)
+}
+
+/// A global function, e.g., `parseInt`
+pub fn global(name: &str) -> Option<&'static Builtin> {
+    GLOBALS.iter().find(|builtin| builtin.name == name)
+}
+
+/// Whether `name` is a global object whose members are modeled, e.g., `Math`
+pub fn is_namespace(name: &str) -> bool {
+    matches!(
+        name,
+        "Math" | "JSON" | "Object" | "Number" | "String" | "Array"
+    )
+}
+
+/// A function of a global object, e.g., `max` of `Math`
+pub fn static_method(namespace: &str, name: &str) -> Option<&'static Builtin> {
+    let table = match namespace {
+        "Math" => MATH,
+        "JSON" => JSON,
+        "Object" => OBJECT,
+        "Number" => NUMBER,
+        "String" => STRING,
+        "Array" => ARRAY,
+        _ => return None,
+    };
+    table.iter().find(|builtin| builtin.name == name)
+}
+
+/// A constant of a global object, e.g., `PI` of `Math`
+pub fn static_property(namespace: &str, name: &str) -> Option<JSTyp> {
+    let table = match namespace {
+        "Math" => MATH_PROPS,
+        "Number" => NUMBER_PROPS,
+        _ => return None,
+    };
+    table
+        .iter()
+        .find(|(prop, _)| *prop == name)
+        .map(|(_, typ)| typ.clone())
+}
+
+/// A method of the values of `typ`, e.g., `toUpperCase` of a String
+pub fn method(typ: &JSTyp, name: &str) -> Option<&'static Builtin> {
+    let table = match typ {
+        JSTyp::String => STRING_PROTO,
+        JSTyp::Array => ARRAY_PROTO,
+        JSTyp::Number => NUMBER_PROTO,
+        _ => return None,
+    };
+    table.iter().find(|builtin| builtin.name == name)
+}
+
+/// Whether `name` is a method of the values of some type, e.g., `slice`
+pub fn is_method(name: &str) -> bool {
+    [STRING_PROTO, ARRAY_PROTO, NUMBER_PROTO]
+        .iter()
+        .any(|table| table.iter().any(|builtin| builtin.name == name))
+}
+
+/// A property of the values of `typ`, e.g., `length` of a String
+pub fn property(typ: &JSTyp, name: &str) -> Option<JSTyp> {
+    match (typ, name) {
+        (JSTyp::String | JSTyp::Array, "length") => Some(JSTyp::Number),
+        _ => None,
+    }
+}
+
+impl Builtin {
+    /// What the argument at `idx` is passed as; `None` past the parameters
+    pub fn param(&self, idx: usize) -> Option<Param> {
+        self.params.get(idx).copied().or(self.rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(global("parseInt").unwrap().ret, JSTyp::Number);
+        assert!(global("eval").is_none());
+        let max = static_method("Math", "max").unwrap();
+        assert_eq!(max.param(5), Some(Number));
+        assert_eq!(static_property("Math", "PI"), Some(JSTyp::Number));
+        assert_eq!(method(&JSTyp::Array, "map").unwrap().ret, JSTyp::Array);
+        assert_eq!(property(&JSTyp::String, "length"), Some(JSTyp::Number));
+        assert!(method(&JSTyp::Object, "map").is_none());
+        assert_eq!(
+            global("parseFloat").unwrap().param(1),
+            None,
+            "extra arguments are not checked"
+        );
+
+        assert!(is_global("parseInt") && is_global("Math") && is_global("Promise"));
+        assert!(!is_global("document"));
+
+        assert_eq!(Number.check(&JSTyp::String), Some(Violation::Coerced));
+        assert_eq!(Number.check(&JSTyp::BigInt), Some(Violation::Throws));
+        assert_eq!(Numeric.check(&JSTyp::BigInt), None);
+        assert_eq!(Object.check(&JSTyp::Null), Some(Violation::Throws));
+        assert_eq!(Callback.check(&JSTyp::Unknown), None);
+        assert_eq!(String.check(&JSTyp::Symbol), Some(Violation::Throws));
+    }
+}
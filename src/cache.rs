@@ -0,0 +1,143 @@
+use crate::diagnostic::Diagnostic;
+use crate::error::Result;
+use crate::jssyntax::JSTyp;
+use crate::util;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the analysis changes between two releases so that results of older builds
+/// are not reused
+const CACHE_VERSION: u32 = 2;
+
+/// Version of the entries written by this build, e.g., `0.1.0.2`. A new release invalidates the
+/// entries of the previous ones even if `CACHE_VERSION` is not bumped.
+pub fn version() -> String {
+    format!("{}.{}", env!("CARGO_PKG_VERSION"), CACHE_VERSION)
+}
+
+/// Inference of a function, valid as long as the inputs of the function hash to `key`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuncEntry {
+    pub name: String,
+    pub key: String,
+    pub return_typ: JSTyp,
+    /// before suppressions and configured severities are applied
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// What is cached for a module whose original source hashes to `hash`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleEntry {
+    pub version: String,
+    pub filename: String,
+    pub hash: String,
+    pub debloated: String,
+    /// in the order of the function declarations
+    pub funcs: Vec<FuncEntry>,
+}
+
+/// One JSON file per module in `dir`
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self, filename: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", hash(filename)))
+    }
+
+    /// The entry of `filename` if its source still hashes to `hash`. Unreadable entries, e.g.,
+    /// of another version, are ignored and overwritten later.
+    pub fn load(&self, filename: &str, hash: &str) -> Option<ModuleEntry> {
+        let content = util::read_file(&self.path(filename).to_string_lossy()).ok()?;
+        let entry: ModuleEntry = serde_json::from_str(&content).ok()?;
+        let valid = entry.version == version() && entry.filename == filename && entry.hash == hash;
+        valid.then_some(entry)
+    }
+
+    pub fn store(&self, entry: &ModuleEntry) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string(entry).unwrap_or_default();
+        util::write_file(&self.path(&entry.filename).to_string_lossy(), &content)
+    }
+}
+
+/// Hex of the FNV-1a hash of `content`
+pub fn hash(content: &str) -> String {
+    format!("{:016x}", util::fnv1a(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::config::Config;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("typeinfer-cache-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        for file in ["main.js", "math.js", "legacy.cjs", "lib/index.js"] {
+            std::fs::copy(format!("test/project/{}", file), dir.join(file)).unwrap();
+        }
+        let main = dir.join("main.js").to_string_lossy().to_string();
+        let math = dir.join("math.js").to_string_lossy().to_string();
+        let config = Config::parse("cache = \"cache\"", dir.clone(), &[]).unwrap();
+        let analyze = || {
+            analysis::analyze_file(&main, &config)
+                .unwrap()
+                .into_iter()
+                .map(|diag| (diag.message, diag.span.start_line))
+                .collect::<Vec<_>>()
+        };
+
+        let uncached = analysis::analyze_file(&main, &Config::default()).unwrap();
+        let first = analyze();
+        assert_eq!(first.len(), uncached.len());
+        assert_eq!(analyze(), first);
+
+        // mark the cached diagnostics of `main.js` to tell reused results from inferred ones
+        let cache = Cache::new(&dir.join("cache"));
+        let origin = util::read_file(&main).unwrap();
+        let mut entry = cache.load(&main, &hash(&origin)).unwrap();
+        for func in &mut entry.funcs {
+            for diag in &mut func.diagnostics {
+                diag.message = format!("cached {}", diag.message);
+            }
+        }
+        cache.store(&entry).unwrap();
+
+        // `halve` still returns a Number, so `main` is not inferred again
+        let edited = util::read_file(&math).unwrap().replace("x / 2", "x / 4");
+        util::write_file(&math, &edited).unwrap();
+        assert_eq!(
+            analyze(),
+            vec![
+                ("cached String - Number".to_string(), 8),
+                ("cached BigInt + Number".to_string(), 9),
+                ("Bool / Number".to_string(), 6),
+            ]
+        );
+
+        // `double` returns a String now, so `main` is inferred again
+        let edited = edited.replace("x * 2", "\"\" + x");
+        util::write_file(&math, &edited).unwrap();
+        let found = analyze();
+        assert!(found.iter().all(|(message, _)| !message.starts_with("cached")));
+        assert!(found.contains(&("String - String".to_string(), 8)));
+
+        // entries written by another build are not reused
+        let mut entry = cache.load(&main, &hash(&origin)).unwrap();
+        entry.version = "0.0.0.1".to_string();
+        cache.store(&entry).unwrap();
+        assert!(cache.load(&main, &hash(&origin)).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,28 +1,37 @@
-use crate::infer;
+use crate::debloat;
+use crate::infer::{self, Props, Summaries};
 use crate::jssyntax::{
-    JSTyp, ARGS, CALL_EXPR, FALSE, IDENT, NULL, NUMBER, OBJECT, PROGRAM, STRING, TRUE, UNDEFINED,
+    JSTyp, ARGS, ARRAY, ASSIGNMENT_STMT, CALL_EXPR, COMMENT, FALSE, IDENT, MEMBER_EXPR, NULL,
+    NUMBER, OBJECT, PAIR, PROGRAM, PROPERTY_IDENT, SHORTHAND_PROPERTY_IDENT, STRING, TRUE,
+    UNDEFINED, VAR_DECL,
 };
 use crate::node::{self, Node};
+use std::collections::HashMap;
 
-/// returns a pair of (node, params) for every target callsites
+/// Functions called as methods, keyed by the original name of the receiver and the property
+pub type Methods = HashMap<(String, String), (String, Props)>;
+
+/// returns (node, params, this) for every target callsites, where `this` is the properties of the
+/// receiver of a method call. Arguments that are calls have the return type of their callee in
+/// `summaries`.
 pub fn gather_callsites<'a>(
     target_func_ident: &str,
+    summaries: &Summaries,
     node: &Node<'a>,
     code: &'a str,
-) -> Vec<(Node<'a>, Vec<JSTyp>)> {
-    assert_eq!(node.kind(), PROGRAM);
+) -> Vec<(Node<'a>, Vec<JSTyp>, Props)> {
+    // debloated code that does not parse has no callsites to rely on
+    if node.kind() != PROGRAM {
+        return vec![];
+    }
     let mut callsites = vec![];
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            CALL_EXPR => {
-                let (call_expr_node, param_typs) = run_call_expr(target_func_ident, child, code);
-                if let Some(call_expr_node) = call_expr_node {
-                    callsites.push((call_expr_node, param_typs));
-                }
-                return Some(child.info.range());
+    node::run_subtree(node, code, |child, _| {
+        if child.kind() == CALL_EXPR {
+            if let Some(callsite) = run_call_expr(target_func_ident, summaries, child, code) {
+                callsites.push(callsite);
             }
-            _ => {}
         }
+        // calls may be nested in the arguments of another call
         None
     });
     callsites
@@ -30,43 +39,164 @@ pub fn gather_callsites<'a>(
 
 fn run_call_expr<'a>(
     target_func_ident: &str,
+    summaries: &Summaries,
     node: &Node<'a>,
     code: &'a str,
-) -> (Option<Node<'a>>, Vec<JSTyp>) {
+) -> Option<(Node<'a>, Vec<JSTyp>, Props)> {
     assert_eq!(node.kind(), CALL_EXPR);
-    let mut func_node = None;
-    let mut args = vec![];
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            IDENT if target_func_ident == child.text => func_node = Some(child.clone()),
-            ARGS => args = run_arguments(child, code),
-            // TODO: Consider `MEMBER_EXPR` for method callsites
-            _ => {}
-        }
-        Some(child.info.range())
-    });
-    (func_node, args)
+    let func = node::get_field(node, "function", code)?;
+    let this = match func.kind() {
+        IDENT if func.text == target_func_ident => Props::new(),
+        MEMBER_EXPR => match method(summaries, &func, code)? {
+            (name, this) if name == target_func_ident => this.clone(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let args = node::get_field(node, "arguments", code)
+        .filter(|args| args.kind() == ARGS)
+        .map(|args| run_arguments(summaries, &args, code))
+        .unwrap_or_default();
+    Some((func, args, this))
 }
 
-fn run_arguments<'a>(node: &Node<'a>, code: &'a str) -> Vec<JSTyp> {
-    assert_eq!(node.kind(), ARGS);
-    let mut typs = vec![];
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            TRUE | FALSE => typs.push(JSTyp::Bool),
-            NULL => typs.push(JSTyp::Null),
-            UNDEFINED => typs.push(JSTyp::Undefined),
-            NUMBER => typs.push(infer::number2typ(child)),
-            STRING => typs.push(JSTyp::String),
-            CALL_EXPR if infer::is_symbol_call(child, code) => {
-                typs.push(JSTyp::Symbol);
-            }
-            OBJECT => {
-                typs.push(JSTyp::Object);
+/// The function a member expression of a method call refers to, e.g., `area` of `rect.area` with
+/// `let rect = { w: 1, area }`, and the properties of its receiver
+pub fn method<'s, 'a>(
+    summaries: &'s Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Option<(&'s str, &'s Props)> {
+    assert_eq!(node.kind(), MEMBER_EXPR);
+    let object = node::get_field(node, "object", code)?;
+    let property = node::get_field(node, "property", code)?;
+    if object.kind() != IDENT {
+        return None;
+    }
+    let key = (
+        debloat::orig_ident(object.text).to_string(),
+        property.text.to_string(),
+    );
+    let (name, this) = summaries.methods.get(&key)?;
+    Some((name, this))
+}
+
+/// Methods of the object literals assigned to variables of the module, whose values are functions
+/// of `summaries`. A method whose receiver name is bound to different functions is left out.
+pub fn gather_methods<'a>(summaries: &Summaries, node: &Node<'a>, code: &'a str) -> Methods {
+    if node.kind() != PROGRAM {
+        return Methods::new();
+    }
+    let mut methods: HashMap<(String, String), Option<(String, Props)>> = HashMap::new();
+    node::run_subtree(node, code, |child, _| {
+        let (receiver, value) = match child.kind() {
+            VAR_DECL => (
+                node::get_field(child, "name", code),
+                node::get_field(child, "value", code),
+            ),
+            ASSIGNMENT_STMT => (
+                node::get_field(child, "left", code),
+                node::get_field(child, "right", code),
+            ),
+            _ => return None,
+        };
+        let (Some(receiver), Some(value)) = (receiver, value) else {
+            return None;
+        };
+        if receiver.kind() != IDENT || value.kind() != OBJECT {
+            return None;
+        }
+        let mut funcs = vec![];
+        let mut this = Props::new();
+        let mut cursor = value.info.walk();
+        for member in value.info.named_children(&mut cursor) {
+            let member = Node {
+                info: member,
+                text: &code[member.byte_range()],
+            };
+            let (key, value) = match member.kind() {
+                PAIR => match (
+                    node::get_field(&member, "key", code),
+                    node::get_field(&member, "value", code),
+                ) {
+                    (Some(key), Some(value)) if key.kind() == PROPERTY_IDENT => (key.text, value),
+                    _ => continue,
+                },
+                SHORTHAND_PROPERTY_IDENT => (member.text, member.clone()),
+                _ => continue,
+            };
+            match func_name(summaries, &value) {
+                Some(func) => funcs.push((key, func)),
+                None => {
+                    this.insert(key.to_string(), arg_typ(summaries, &value, code));
+                }
             }
-            _ => {}
         }
-        Some(child.info.range())
+        let receiver = debloat::orig_ident(receiver.text);
+        for (key, func) in funcs {
+            let method = Some((func.to_string(), this.clone()));
+            methods
+                .entry((receiver.to_string(), key.to_string()))
+                .and_modify(|other| {
+                    if *other != method {
+                        *other = None;
+                    }
+                })
+                .or_insert(method);
+        }
+        None
     });
-    typs
+    methods
+        .into_iter()
+        .filter_map(|(key, method)| Some((key, method?)))
+        .collect()
+}
+
+/// Name of the function of `summaries` that the identifier `node` refers to
+fn func_name<'s>(summaries: &'s Summaries, node: &Node) -> Option<&'s str> {
+    if !matches!(node.kind(), IDENT | SHORTHAND_PROPERTY_IDENT) {
+        return None;
+    }
+    // a value may be debloated, e.g., `area_1_2`
+    [node.text, debloat::orig_ident(node.text)]
+        .into_iter()
+        .find_map(|name| summaries.returns.get_key_value(name))
+        .map(|(name, _)| name.as_str())
+}
+
+fn run_arguments<'a>(summaries: &Summaries, node: &Node<'a>, code: &'a str) -> Vec<JSTyp> {
+    assert_eq!(node.kind(), ARGS);
+    let mut cursor = node.info.walk();
+    node.info
+        .named_children(&mut cursor)
+        // keep the position of the following arguments
+        .filter(|child| child.kind() != COMMENT)
+        .map(|child| {
+            let child = Node {
+                info: child,
+                text: &code[child.byte_range()],
+            };
+            arg_typ(summaries, &child, code)
+        })
+        .collect()
+}
+
+/// Type of an expression whose variables are not known, e.g., an argument of a callsite
+fn arg_typ<'a>(summaries: &Summaries, node: &Node<'a>, code: &'a str) -> JSTyp {
+    match node.kind() {
+        TRUE | FALSE => JSTyp::Bool,
+        NULL => JSTyp::Null,
+        UNDEFINED => JSTyp::Undefined,
+        NUMBER => infer::number2typ(node),
+        STRING => JSTyp::String,
+        CALL_EXPR if infer::is_symbol_call(node, code) => JSTyp::Symbol,
+        CALL_EXPR => infer::callee_summary(summaries, node, code)
+            .or_else(|| infer::stub_call_typ(summaries, node, code))
+            .or_else(|| infer::builtin_call_typ(node, code))
+            .unwrap_or(JSTyp::Unknown),
+        MEMBER_EXPR => infer::builtin_property_typ(node, code).unwrap_or(JSTyp::Unknown),
+        OBJECT => JSTyp::Object,
+        ARRAY => JSTyp::Array,
+        _ => JSTyp::Unknown,
+    }
 }
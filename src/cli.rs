@@ -0,0 +1,234 @@
+use crate::error::{Result, TypeinferError};
+use crate::output::Format;
+
+pub const USAGE: &str =
+    "usage: typeinfer [--format text|json|sarif] [--set KEY=VALUE]... [--baseline FILE]
+                 [--write-baseline FILE] [--trace FILE] [--dump] [FILE...]
+       typeinfer watch [--format text|json] [--set KEY=VALUE]... [FILE...]
+       typeinfer lsp [--set KEY=VALUE]...
+       typeinfer type-at [--format text|json] [--set KEY=VALUE]... FILE:LINE:COLUMN
+       typeinfer annotate [--set KEY=VALUE]... [FILE...]
+       typeinfer dts [--set KEY=VALUE]... [FILE...]
+       typeinfer instrument --out-dir DIR [--set KEY=VALUE]... [FILE...]
+
+  watch              analyze again whenever a file is modified and print only the diagnostics
+                     that appeared (+) or disappeared (-) since the previous analysis
+  lsp                serve the Language Server Protocol over stdin and stdout: diagnostics of the
+                     open documents and inferred types on hover
+  type-at            print the inferred type of the variable at FILE:LINE:COLUMN (1-based)
+  annotate           insert JSDoc blocks with the inferred types above the functions and
+                     variable declarations of the files, rewriting them in place
+  dts                write a TypeScript declaration file next to every file, e.g., lib.d.ts for
+                     lib.js, with the inferred types of its exports
+  instrument         write the files and the modules they import to DIR with runtime assertions
+                     that every parameter and assigned variable has one of its inferred types
+
+  --format <FORMAT>  text (default), json (one diagnostic per line) or sarif (SARIF 2.1.0)
+  --set <KEY=VALUE>  override a key of typeinfer.toml, e.g., `rules.cmp-mismatch=off`
+  --baseline <FILE>  report only the diagnostics not recorded in the baseline FILE
+  --write-baseline <FILE>
+                     record the current diagnostics in FILE instead of reporting them
+  --out-dir <DIR>    directory of the instrumented files
+  --trace <FILE>     runtime types of parameters, one JSON object per line with `function`,
+                     `file`, `param` (0-based) and `typeof`, to infer the functions without
+                     callsites with and to check the inferred parameter types against
+  --dump             write node-dump.txt, debloated.js and debloated-dump.txt for debugging
+  -h, --help         print this message
+
+Without FILE, the files matching `include` of typeinfer.toml are analyzed, or example/example.js
+if there is none.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Analyze once and report every diagnostic
+    Check,
+    /// Poll the files and report what changed
+    Watch,
+    /// Serve an editor over stdio
+    Lsp,
+    /// Print the type of the variable at a position
+    TypeAt,
+    /// Rewrite the files with JSDoc annotations
+    Annotate,
+    /// Write TypeScript declarations of the exports
+    Dts,
+    /// Write the files with runtime type assertions
+    Instrument,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub format: Format,
+    pub dump: bool,
+    pub help: bool,
+    /// `key=value` overrides of the configuration
+    pub overrides: Vec<String>,
+    pub baseline: Option<String>,
+    pub write_baseline: Option<String>,
+    pub out_dir: Option<String>,
+    pub trace: Option<String>,
+    pub files: Vec<String>,
+}
+
+/// Parses the command line arguments without the program name
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options> {
+    let mut opts = Options {
+        command: Command::Check,
+        format: Format::Text,
+        dump: false,
+        help: false,
+        overrides: vec![],
+        baseline: None,
+        write_baseline: None,
+        out_dir: None,
+        trace: None,
+        files: vec![],
+    };
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek().map(|arg| arg.as_str()) {
+        Some("watch") => Some(Command::Watch),
+        Some("lsp") => Some(Command::Lsp),
+        Some("type-at") => Some(Command::TypeAt),
+        Some("annotate") => Some(Command::Annotate),
+        Some("dts") => Some(Command::Dts),
+        Some("instrument") => Some(Command::Instrument),
+        _ => None,
+    };
+    if let Some(command) = command {
+        args.next();
+        opts.command = command;
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let format = args
+                    .next()
+                    .ok_or_else(|| TypeinferError::Usage("`--format` needs a value".to_string()))?;
+                opts.format = format.parse()?;
+            }
+            _ if arg.starts_with("--format=") => {
+                opts.format = arg["--format=".len()..].parse()?;
+            }
+            "--set" => {
+                let key_value = args
+                    .next()
+                    .ok_or_else(|| TypeinferError::Usage("`--set` needs a value".to_string()))?;
+                opts.overrides.push(key_value);
+            }
+            "--baseline" | "--write-baseline" => {
+                let filename = args
+                    .next()
+                    .ok_or_else(|| TypeinferError::Usage(format!("`{}` needs a file", arg)))?;
+                if arg == "--baseline" {
+                    opts.baseline = Some(filename);
+                } else {
+                    opts.write_baseline = Some(filename);
+                }
+            }
+            "--out-dir" => {
+                let dir = args.next().ok_or_else(|| {
+                    TypeinferError::Usage("`--out-dir` needs a value".to_string())
+                })?;
+                opts.out_dir = Some(dir);
+            }
+            "--trace" => {
+                let filename = args
+                    .next()
+                    .ok_or_else(|| TypeinferError::Usage("`--trace` needs a file".to_string()))?;
+                opts.trace = Some(filename);
+            }
+            "--dump" => opts.dump = true,
+            "-h" | "--help" => opts.help = true,
+            _ if arg.starts_with('-') => {
+                return Err(TypeinferError::Usage(format!("unknown option `{}`", arg)));
+            }
+            _ => opts.files.push(arg),
+        }
+    }
+    Ok(opts)
+}
+
+/// Splits `FILE:LINE:COLUMN`, the location format of the diagnostics
+pub fn parse_location(location: &str) -> Result<(&str, usize, usize)> {
+    let invalid =
+        || TypeinferError::Usage(format!("expected FILE:LINE:COLUMN, found `{}`", location));
+    let mut parts = location.rsplitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(column), Some(line), Some(file)) if !file.is_empty() => Ok((
+            file,
+            line.parse().map_err(|_| invalid())?,
+            column.parse().map_err(|_| invalid())?,
+        )),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let opts = parse(&[]).unwrap();
+        assert_eq!(opts.command, Command::Check);
+        assert_eq!(opts.format, Format::Text);
+        assert!(opts.files.is_empty());
+
+        let opts = parse(&["--format", "sarif", "a.js", "b.js"]).unwrap();
+        assert_eq!(opts.format, Format::Sarif);
+        assert_eq!(opts.files, vec!["a.js", "b.js"]);
+
+        let opts = parse(&["--format=json", "--dump"]).unwrap();
+        assert_eq!(opts.format, Format::Json);
+        assert!(opts.dump);
+
+        let opts = parse(&["--set", "precision=fast", "--set", "rules.cmp-mismatch=off"]).unwrap();
+        assert_eq!(
+            opts.overrides,
+            vec!["precision=fast", "rules.cmp-mismatch=off"]
+        );
+        assert!(parse(&["--set"]).is_err());
+
+        let opts = parse(&["--write-baseline", "baseline.json"]).unwrap();
+        assert_eq!(opts.write_baseline.as_deref(), Some("baseline.json"));
+        let opts = parse(&["--baseline", "baseline.json", "a.js"]).unwrap();
+        assert_eq!(opts.baseline.as_deref(), Some("baseline.json"));
+        assert_eq!(opts.files, vec!["a.js"]);
+        assert!(parse(&["--baseline"]).is_err());
+
+        let opts = parse(&["watch", "--format", "json", "a.js"]).unwrap();
+        assert_eq!(opts.command, Command::Watch);
+        assert_eq!(opts.files, vec!["a.js"]);
+        assert_eq!(parse(&["lsp"]).unwrap().command, Command::Lsp);
+        let opts = parse(&["type-at", "a.js:10:5"]).unwrap();
+        assert_eq!(opts.command, Command::TypeAt);
+        assert_eq!(parse_location(&opts.files[0]).unwrap(), ("a.js", 10, 5));
+        assert_eq!(parse_location("c:/a.js:1:2").unwrap(), ("c:/a.js", 1, 2));
+        assert!(parse_location("a.js:10").is_err());
+        assert!(parse_location(":1:2").is_err());
+        assert_eq!(
+            parse(&["annotate", "a.js"]).unwrap().command,
+            Command::Annotate
+        );
+        assert_eq!(parse(&["dts", "a.js"]).unwrap().command, Command::Dts);
+        let opts = parse(&["instrument", "--out-dir", "out", "a.js"]).unwrap();
+        assert_eq!(opts.command, Command::Instrument);
+        assert_eq!(opts.out_dir.as_deref(), Some("out"));
+        assert!(parse(&["instrument", "--out-dir"]).is_err());
+        let opts = parse(&["--trace", "trace.jsonl", "a.js"]).unwrap();
+        assert_eq!(opts.trace.as_deref(), Some("trace.jsonl"));
+        // a file named like the command is analyzed once
+        let opts = parse(&["a.js", "watch"]).unwrap();
+        assert_eq!(opts.command, Command::Check);
+        assert_eq!(opts.files, vec!["a.js", "watch"]);
+
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--format", "xml"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+}
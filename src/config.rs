@@ -0,0 +1,313 @@
+use crate::diagnostic::{Diagnostic, Severity, RULES};
+use crate::env::Profile;
+use crate::error::{Result, TypeinferError};
+use crate::jssyntax::JSTyp;
+use crate::parallel;
+use crate::util;
+use glob::Pattern;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILENAME: &str = "typeinfer.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Infer a function once per distinct argument types of its callsites
+    #[default]
+    Precise,
+    /// Infer a function once with the argument types joined over all of its callsites
+    Fast,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    Off,
+    Note,
+    Warning,
+    Error,
+}
+
+/// `typeinfer.toml`
+/// ```toml
+/// include = ["src/**/*.js"]
+/// exclude = ["src/vendor/**"]
+/// precision = "fast"
+/// jobs = 4
+/// cache = ".typeinfer-cache"
+/// trace = "typeinfer-trace.jsonl"
+/// stubs = ["stubs"]
+/// env = ["browser"]
+///
+/// [globals]
+/// VERSION = "string"
+///
+/// [rules]
+/// cmp-mismatch = "off"
+/// arith-coercion = "error"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Globs of the files to analyze when none are given on the command line
+    pub include: Vec<String>,
+    /// Globs of the files never analyzed
+    pub exclude: Vec<String>,
+    /// Globals of the environment and their `typeof` names
+    pub globals: BTreeMap<String, String>,
+    pub precision: Precision,
+    /// Threads analyzing in parallel; one per core if 0
+    pub jobs: usize,
+    /// Directory caching the analysis between runs, relative to the configuration file; no
+    /// cache if empty
+    pub cache: String,
+    /// Runtime types of parameters, relative to the configuration file; see `trace::Traces`
+    pub trace: String,
+    /// Directories of the stub files declaring packages and globals, relative to the
+    /// configuration file; see `stubs::Stubs`
+    pub stubs: Vec<String>,
+    /// Environments declaring the globals; every environment but `none` if not set
+    pub env: Option<Vec<Profile>>,
+    /// `off` or a severity replacing the default severity of a rule
+    pub rules: BTreeMap<String, RuleLevel>,
+    /// Directory of the configuration file. Globs are relative to it.
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+impl Config {
+    /// Loads `typeinfer.toml` of `dir` or of its closest ancestor having one, then applies the
+    /// `key=value` overrides. Without a configuration file, the defaults are used.
+    pub fn discover(dir: &Path, overrides: &[String]) -> Result<Self> {
+        let path = dir
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILENAME))
+            .find(|path| path.is_file());
+        match path {
+            Some(path) => {
+                let content = util::read_file(&path.to_string_lossy())?;
+                let root = path.parent().unwrap_or(dir).to_path_buf();
+                Self::parse(&content, root, overrides)
+                    .map_err(|e| TypeinferError::Config(format!("{}: {}", path.display(), e)))
+            }
+            None => Self::parse("", dir.to_path_buf(), overrides),
+        }
+    }
+
+    pub fn parse(content: &str, root: PathBuf, overrides: &[String]) -> Result<Self> {
+        let mut table = content
+            .parse::<toml::Table>()
+            .map_err(|e| TypeinferError::Config(e.to_string()))?;
+        for key_value in overrides {
+            set(&mut table, key_value)?;
+        }
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| TypeinferError::Config(e.to_string()))?;
+        config.root = root;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for rule in self.rules.keys() {
+            if !RULES.iter().any(|(id, _, _)| id == rule) {
+                return Err(TypeinferError::Config(format!("unknown rule `{}`", rule)));
+            }
+        }
+        for typ in self.globals.values() {
+            typ.parse::<JSTyp>()?;
+        }
+        for glob in self.include.iter().chain(self.exclude.iter()) {
+            Pattern::new(glob)
+                .map_err(|e| TypeinferError::Config(format!("invalid glob `{}`: {}", glob, e)))?;
+        }
+        Ok(())
+    }
+
+    pub fn global_typs(&self) -> BTreeMap<String, JSTyp> {
+        self.globals
+            .iter()
+            .map(|(name, typ)| (name.clone(), typ.parse().unwrap_or(JSTyp::Unknown)))
+            .collect()
+    }
+
+    pub fn thread_count(&self) -> usize {
+        match self.jobs {
+            0 => parallel::default_jobs(),
+            jobs => jobs,
+        }
+    }
+
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        (!self.cache.is_empty()).then(|| self.root.join(&self.cache))
+    }
+
+    pub fn trace_file(&self) -> Option<PathBuf> {
+        (!self.trace.is_empty()).then(|| self.root.join(&self.trace))
+    }
+
+    pub fn stub_dirs(&self) -> Vec<PathBuf> {
+        self.stubs.iter().map(|dir| self.root.join(dir)).collect()
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        self.env.as_deref().unwrap_or(Profile::ALL)
+    }
+
+    /// `filename` is relative to the working directory
+    pub fn is_excluded(&self, filename: &str) -> bool {
+        let path = self.relative_to_root(Path::new(filename));
+        self.exclude
+            .iter()
+            .filter_map(|glob| Pattern::new(glob).ok())
+            .any(|pattern| pattern.matches_path(&path))
+    }
+
+    /// Files matching `include` but not `exclude`, relative to the working directory if possible
+    pub fn included_files(&self) -> Result<Vec<String>> {
+        let cwd = std::env::current_dir()?;
+        let mut files = vec![];
+        for glob in &self.include {
+            let pattern = self.root.join(glob);
+            let paths = glob::glob(&pattern.to_string_lossy())
+                .map_err(|e| TypeinferError::Config(format!("invalid glob `{}`: {}", glob, e)))?;
+            for path in paths.flatten().filter(|path| path.is_file()) {
+                let path = path.strip_prefix(&cwd).unwrap_or(&path);
+                let filename = path.to_string_lossy().to_string();
+                if !self.is_excluded(&filename) {
+                    files.push(filename);
+                }
+            }
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    fn relative_to_root(&self, path: &Path) -> PathBuf {
+        let path = match std::env::current_dir() {
+            Ok(cwd) if path.is_relative() => cwd.join(path),
+            _ => path.to_path_buf(),
+        };
+        path.strip_prefix(&self.root)
+            .map(|path| path.to_path_buf())
+            .unwrap_or(path)
+    }
+
+    /// Drops the diagnostics of disabled rules and applies the configured severities
+    pub fn apply(&self, diags: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diags
+            .into_iter()
+            .filter_map(|mut diag| {
+                diag.severity = match self.rules.get(diag.rule) {
+                    None => diag.severity,
+                    Some(RuleLevel::Off) => return None,
+                    Some(RuleLevel::Note) => Severity::Note,
+                    Some(RuleLevel::Warning) => Severity::Warning,
+                    Some(RuleLevel::Error) => Severity::Error,
+                };
+                Some(diag)
+            })
+            .collect()
+    }
+}
+
+/// Applies `key=value`, where `key` is a dotted path such as `rules.cmp-mismatch` and `value` is a
+/// TOML value. A value that is not valid TOML is taken as a string, e.g., `precision=fast`.
+fn set(table: &mut toml::Table, key_value: &str) -> Result<()> {
+    let (key, value) = key_value.split_once("=").ok_or_else(|| {
+        TypeinferError::Config(format!("expected `key=value`, found `{}`", key_value))
+    })?;
+    let value = match format!("value = {}", value).parse::<toml::Table>() {
+        Ok(mut parsed) => parsed.remove("value").unwrap(),
+        Err(_) => toml::Value::String(value.to_string()),
+    };
+    let mut keys = key.trim().split(".").collect::<Vec<_>>();
+    let last = keys.pop().unwrap();
+    let mut table = table;
+    for key in keys {
+        let entry = table
+            .entry(key.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        table = match entry {
+            toml::Value::Table(table) => table,
+            _ => {
+                return Err(TypeinferError::Config(format!(
+                    "`{}` of `{}` is not a table",
+                    key, key_value
+                )))
+            }
+        };
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::{ARITH_COERCION, CMP_MISMATCH};
+
+    #[test]
+    fn test_parse_and_override() {
+        let content = r#"
+            exclude = ["vendor/**"]
+            precision = "fast"
+
+            [globals]
+            VERSION = "string"
+
+            [rules]
+            cmp-mismatch = "off"
+            arith-coercion = "note"
+        "#;
+        let overrides = vec![
+            "rules.arith-coercion=error".to_string(),
+            "globals.DEBUG=boolean".to_string(),
+        ];
+        let config = Config::parse(content, PathBuf::from("/project"), &overrides).unwrap();
+        assert_eq!(config.precision, Precision::Fast);
+        assert_eq!(config.rules[CMP_MISMATCH], RuleLevel::Off);
+        assert_eq!(config.rules[ARITH_COERCION], RuleLevel::Error);
+        assert_eq!(
+            config.global_typs().into_iter().collect::<Vec<_>>(),
+            vec![
+                ("DEBUG".to_string(), JSTyp::Bool),
+                ("VERSION".to_string(), JSTyp::String)
+            ]
+        );
+        assert!(config.is_excluded("/project/vendor/lib.js"));
+        assert!(!config.is_excluded("/project/src/lib.js"));
+        assert_eq!(config.profiles(), Profile::ALL);
+        let config = Config::parse(
+            "",
+            PathBuf::from("/project"),
+            &["env=[\"node\"]".to_string()],
+        );
+        assert_eq!(config.unwrap().profiles(), &[Profile::Node]);
+
+        let root = PathBuf::from("/project");
+        assert!(Config::parse("[rules]\nno-such-rule = \"off\"", root.clone(), &[]).is_err());
+        assert!(Config::parse("[globals]\nx = \"integer\"", root.clone(), &[]).is_err());
+        assert!(Config::parse("precison = \"fast\"", root.clone(), &[]).is_err());
+        assert!(Config::parse("", root, &["precision".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_discover() {
+        let cwd = std::env::current_dir().unwrap();
+        let config = Config::discover(&cwd.join("test/config/nested"), &[]).unwrap();
+        assert_eq!(config.root, cwd.join("test/config"));
+        assert_eq!(
+            config.included_files().unwrap(),
+            vec!["test/config/nested/included.js"]
+        );
+
+        let config = Config::discover(&cwd.join("test/infer"), &[]).unwrap();
+        assert_eq!(config.precision, Precision::Precise);
+        assert!(config.rules.is_empty());
+    }
+}
@@ -1,10 +1,14 @@
+use crate::error::Result;
 use crate::jssyntax::{
-    ARGS, ASSIGNMENT_STMT, BINARY_EXPR, BREAK, BREAK_STMT, CALL_EXPR, CASE, CLOSE_BRACKET,
-    CLOSE_PARENTHESIS, COLON, CONTINUE, CONTINUE_STMT, DO, DOUBLE_QUOTE, DO_STMT, ELSE,
-    ELSE_CLAUSE, EMPTY_STMT, EXPR_STMT, FOR, FORMAL_PARAMS, FOR_IN_STMT, FOR_STMT, FUNC_DECL,
-    IDENT, IF, IF_STATEMENT, LEXICAL_DECL, OBJECT, OPEN_BRACKET, OPEN_PARENTHESIS, PAIR,
-    PARENTHESIZED_EXPR, PROGRAM, RETURN_STMT, SEMICOLON, STMT_BLK, STRING, STRING_FRAGMENT, SWITCH,
-    SWITCH_BODY, SWITCH_CASE, SWITCH_STMT, VAR_DECL, WHILE, WHILE_STMT,
+    ARGS, ARRAY_PATTERN, ASSIGNMENT_PATTERN, ASSIGNMENT_STMT, AUGMENTED_ASSIGNMENT_STMT,
+    BINARY_EXPR, BREAK, BREAK_STMT, CALL_EXPR, CASE, CLOSE_BRACKET, CLOSE_PARENTHESIS, COLON,
+    COMMENT, CONTINUE, CONTINUE_STMT, DEFAULT, DO, DO_STMT, ELSE, ELSE_CLAUSE, EMPTY_STMT, EXPORT,
+    EXPORT_CLAUSE, EXPORT_STMT, EXPR_STMT, FORMAL_PARAMS, FOR_IN_STMT, FOR_STMT, FUNC_DECL,
+    GENERATOR_FUNC_DECL, IDENT, IF, IF_STATEMENT, IMPORT_STMT, LEXICAL_DECL, OBJECT,
+    OBJECT_ASSIGNMENT_PATTERN, OBJECT_PATTERN, OPEN_BRACKET, OPEN_PARENTHESIS, PAIR, PAIR_PATTERN,
+    PARENTHESIZED_EXPR, PROGRAM, REGEX, RETURN_STMT, SEMICOLON, SHORTHAND_PROPERTY_IDENT_PATTERN,
+    STMT_BLK, STRING, SWITCH, SWITCH_BODY, SWITCH_CASE, SWITCH_STMT, TEMPLATE_STRING, VAR_DECL,
+    WHILE, WHILE_STMT,
 };
 use crate::node::{self, Node};
 use crate::util;
@@ -15,7 +19,7 @@ use tree_sitter_traversal::Order;
 struct ScopeEnv {
     lvl: usize,
     lvl_visited: Vec<usize>,
-    lvl_occupied: HashSet<(usize, usize)>, // <scope level, # of scope level visited>
+    lvl_occupied: HashSet<(String, usize, usize)>, // <variable, scope level, # of scope level visited>
 }
 impl Default for ScopeEnv {
     fn default() -> Self {
@@ -35,10 +39,26 @@ impl ScopeEnv {
 pub const LOC_ANNOT: &str = "// [Loc]";
 pub const NON_BRANCH_ANNOT: &str = "[Non-branch]";
 pub const PARENT_NODE_ID_ANNOT: &str = "[Parent-ID]";
+pub const BYTES_ANNOT: &str = "[Bytes]";
 
-fn mapping_source<'a>(node: &Node<'a>, filename: &str) -> String {
-    let range = node.info.range().start_point;
-    format!("{} {}:{}:{}", LOC_ANNOT, filename, range.row, range.column)
+/// `// [Loc] <file>:<line>:<column>, [Bytes] <start>-<end>` where the position is the 1-based
+/// start of the original statement and the bytes cover all of its original tokens
+fn mapping_source(range: &Range, filename: &str) -> String {
+    format!(
+        "{} {}:{}:{}, {} {}-{}",
+        LOC_ANNOT,
+        filename,
+        range.start_point.row + 1,
+        range.start_point.column + 1,
+        BYTES_ANNOT,
+        range.start_byte,
+        range.end_byte
+    )
+}
+
+/// The identifier of the original source, i.e., `a` for the debloated `a_1_2`
+pub fn orig_ident(ident: &str) -> &str {
+    ident.rsplitn(3, "_").nth(2).unwrap_or(ident)
 }
 
 fn get_scoped_ident<'a>(
@@ -46,27 +66,37 @@ fn get_scoped_ident<'a>(
     ident_node: &Node<'a>,
     scope_env: &mut ScopeEnv,
 ) -> String {
-    assert_eq!(ident_node.kind(), IDENT);
+    assert!(matches!(
+        ident_node.kind(),
+        IDENT | SHORTHAND_PROPERTY_IDENT_PATTERN
+    ));
     let mut n_visited = scope_env.count_lvl_visited(scope_env.lvl);
     let mut lvl = scope_env.lvl as i64;
-    let parent_kind = ident_node.info.parent().unwrap().kind();
+    let decl_kind = declaration(ident_node.info).map(|decl| decl.kind());
 
-    if parent_kind == VAR_DECL || parent_kind == FORMAL_PARAMS {
-        scope_env.lvl_occupied.insert((lvl as usize, n_visited));
+    if let Some(FORMAL_PARAMS | FOR_IN_STMT) = decl_kind {
+        // parameters belong to the scope of the function body that is visited next
+        let lvl = scope_env.lvl + 1;
+        let n_visited = scope_env.count_lvl_visited(lvl) + 1;
+        scope_env
+            .lvl_occupied
+            .insert((ident_node.text.to_string(), lvl, n_visited));
         vars.insert(ident_node.text.to_string());
-        if parent_kind == FORMAL_PARAMS {
-            format!("{}_{}_{}", ident_node.text, 1, 1)
-        } else {
-            format!("{}_{}_{}", ident_node.text, lvl, n_visited)
-        }
+        format!("{}_{}_{}", ident_node.text, lvl, n_visited)
+    } else if decl_kind == Some(VAR_DECL) {
+        scope_env
+            .lvl_occupied
+            .insert((ident_node.text.to_string(), lvl as usize, n_visited));
+        vars.insert(ident_node.text.to_string());
+        format!("{}_{}_{}", ident_node.text, lvl, n_visited)
     } else if vars.get(ident_node.text).is_some() {
         let mut ident = None;
         while lvl >= 0 {
-            if scope_env
-                .lvl_occupied
-                .get(&(lvl as usize, n_visited))
-                .is_some()
-            {
+            if scope_env.lvl_occupied.contains(&(
+                ident_node.text.to_string(),
+                lvl as usize,
+                n_visited,
+            )) {
                 ident = Some(format!("{}_{}_{}", ident_node.text, lvl, n_visited));
                 break;
             }
@@ -79,16 +109,43 @@ fn get_scoped_ident<'a>(
             ident.unwrap()
         }
     } else {
-        scope_env.lvl_occupied.insert((lvl as usize, n_visited));
+        scope_env
+            .lvl_occupied
+            .insert((ident_node.text.to_string(), lvl as usize, n_visited));
         vars.insert(ident_node.text.to_string());
         format!("{}_{}_{}", ident_node.text, scope_env.lvl, n_visited)
     }
 }
 
+/// The declaration, parameter list or loop header that declares the identifier `node`, possibly
+/// through destructuring patterns, e.g., `c` of `let { a, b: [c] } = o`; `None` if `node` reads a
+/// variable
+fn declaration(node: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    let mut node = node;
+    loop {
+        let parent = node.parent()?;
+        let field = |name| parent.child_by_field_name(name) == Some(node);
+        match parent.kind() {
+            FORMAL_PARAMS => return Some(parent),
+            VAR_DECL if field("name") => return Some(parent),
+            // without `let`, `const` or `var`, the loop assigns an existing variable
+            FOR_IN_STMT if field("left") && parent.child_by_field_name("kind").is_some() => {
+                return Some(parent)
+            }
+            OBJECT_PATTERN | ARRAY_PATTERN => {}
+            PAIR_PATTERN if field("value") => {}
+            ASSIGNMENT_PATTERN | OBJECT_ASSIGNMENT_PATTERN if field("left") => {}
+            _ => return None,
+        }
+        node = parent;
+    }
+}
+
 fn aggregate<'a>(
     debloated: &mut Vec<String>,
     child: &Node<'a>,
     text: &str,
+    text_range: &Range,
     first_stmt_blk: bool,
     filename: &str,
 ) {
@@ -98,7 +155,7 @@ fn aggregate<'a>(
         text.to_string()
     };
 
-    text = format!("{} {}", text, mapping_source(child, filename));
+    text = format!("{} {}", text, mapping_source(text_range, filename));
     if !node::is_in_ctrl_flow(child) {
         text = format!("{}, {}", text, NON_BRANCH_ANNOT);
     }
@@ -111,6 +168,14 @@ fn aggregate<'a>(
     debloated.push(text);
 }
 
+/// Whether `node` is the body of a function declaration
+fn is_func_body(node: tree_sitter::Node) -> bool {
+    node.kind() == STMT_BLK
+        && node
+            .parent()
+            .is_some_and(|func| matches!(func.kind(), FUNC_DECL | GENERATOR_FUNC_DECL))
+}
+
 fn append_text(text: &str, to_append: &str) -> String {
     if text.len() == 0 {
         to_append.to_string()
@@ -122,6 +187,9 @@ fn append_text(text: &str, to_append: &str) -> String {
 pub fn debloat_control_flow<'a>(nodes: &Vec<Node<'a>>, code: &'a str, filename: &str) -> String {
     assert!(nodes[0].kind() == PROGRAM);
     let mut debloated = vec![];
+    if nodes.len() < 2 {
+        return "".to_string();
+    }
 
     let mut first_stmt_blk = true;
     let mut node = &nodes[1];
@@ -129,41 +197,119 @@ pub fn debloat_control_flow<'a>(nodes: &Vec<Node<'a>>, code: &'a str, filename:
     let mut vars = HashSet::new();
     let mut last_row = 0;
     loop {
+        // modules are resolved from the original source
+        if node.kind() == IMPORT_STMT {
+            match node::get_next_node(nodes, node) {
+                Some(next_node) => {
+                    node = next_node;
+                    continue;
+                }
+                None => break,
+            }
+        }
+        // `export function f() {}` is debloated as `function f() {}`
+        let is_func = node.kind() == FUNC_DECL
+            || node::get_field(node, "declaration", code)
+                .is_some_and(|decl| decl.kind() == FUNC_DECL);
+        if is_func {
+            first_stmt_blk = true;
+        }
         let mut text = "".to_string();
+        // original source range of the tokens in `text`
+        let mut text_range = node.info.range();
         node::run_subtree(node, code, |child, last| {
             let parent = child.info.parent().unwrap();
             let Range { start_point, .. } = child.info.range();
             if last_row < start_point.row {
                 last_row = start_point.row;
                 if text.len() > 0 {
-                    aggregate(&mut debloated, &child, &text, first_stmt_blk, filename);
+                    aggregate(
+                        &mut debloated,
+                        &child,
+                        &text,
+                        &text_range,
+                        first_stmt_blk,
+                        filename,
+                    );
                     text = "".to_string();
                 }
             } else {
                 if last {
                     if text.len() > 0 {
-                        text = append_text(&text, child.text);
-                        aggregate(&mut debloated, &child, &text, first_stmt_blk, filename);
+                        // the braces of a block are not kept, and that of a function body is put
+                        // on its own line below
+                        if !(child.kind() == CLOSE_BRACKET && parent.kind() == STMT_BLK) {
+                            text = append_text(&text, child.text);
+                        }
+                        text_range.end_byte = child.info.end_byte();
+                        text_range.end_point = child.info.end_position();
+                        aggregate(
+                            &mut debloated,
+                            &child,
+                            &text,
+                            &text_range,
+                            first_stmt_blk,
+                            filename,
+                        );
                     }
                     text = "".to_string();
                 }
             }
+            let text_len = text.len();
+            let mut skip = None;
             match child.kind() {
-                PROGRAM | FUNC_DECL | FORMAL_PARAMS | STMT_BLK | ASSIGNMENT_STMT | CALL_EXPR
-                | LEXICAL_DECL | VAR_DECL | PARENTHESIZED_EXPR | EXPR_STMT | BINARY_EXPR
-                | IF_STATEMENT | IF | ELSE | ELSE_CLAUSE | SWITCH_CASE | SWITCH_BODY
-                | SWITCH_STMT | CASE | SWITCH | FOR_STMT | FOR | BREAK_STMT | BREAK
-                | CONTINUE_STMT | CONTINUE | EMPTY_STMT | WHILE_STMT | WHILE | ARGS
-                | RETURN_STMT | STRING | OBJECT | PAIR => {
+                PROGRAM
+                | FUNC_DECL
+                | FORMAL_PARAMS
+                | STMT_BLK
+                | ASSIGNMENT_STMT
+                | AUGMENTED_ASSIGNMENT_STMT
+                | CALL_EXPR
+                | LEXICAL_DECL
+                | VAR_DECL
+                | PARENTHESIZED_EXPR
+                | EXPR_STMT
+                | BINARY_EXPR
+                | IF_STATEMENT
+                | IF
+                | ELSE
+                | ELSE_CLAUSE
+                | SWITCH_CASE
+                | SWITCH_BODY
+                | SWITCH_STMT
+                | CASE
+                | SWITCH
+                | FOR_STMT
+                | BREAK_STMT
+                | BREAK
+                | CONTINUE_STMT
+                | CONTINUE
+                | EMPTY_STMT
+                | WHILE_STMT
+                | WHILE
+                | DO_STMT
+                | DO
+                | ARGS
+                | RETURN_STMT
+                | EXPORT_STMT
+                | OBJECT
+                | PAIR => {
                     return None;
                 }
+                // a destructuring pattern, e.g., of a parameter, opens no scope
+                OPEN_BRACKET if parent.kind() == OBJECT_PATTERN => {
+                    text = append_text(&text, OPEN_BRACKET);
+                }
+                CLOSE_BRACKET if parent.kind() == OBJECT_PATTERN => {
+                    text = append_text(&text, CLOSE_BRACKET);
+                }
                 OPEN_BRACKET => {
-                    if first_stmt_blk {
-                        if node.kind() == FUNC_DECL {
-                            text = append_text(&text, OPEN_BRACKET);
-                        }
-                        first_stmt_blk = false;
+                    // the body of every function declaration keeps its braces, e.g., of a nested
+                    // generator, so that the debloated code still parses
+                    if is_func_body(parent) {
+                        text = append_text(&text, OPEN_BRACKET);
                     }
+                    first_stmt_blk = false;
                     scope_env.lvl += 1;
                     scope_env.lvl_visited.push(scope_env.lvl);
                     if parent.kind() == OBJECT {
@@ -174,28 +320,61 @@ pub fn debloat_control_flow<'a>(nodes: &Vec<Node<'a>>, code: &'a str, filename:
                     if parent.kind() == OBJECT {
                         text = append_text(&text, CLOSE_BRACKET);
                     }
+                    if is_func_body(parent) {
+                        if !text.is_empty() {
+                            aggregate(
+                                &mut debloated,
+                                child,
+                                &text,
+                                &text_range,
+                                first_stmt_blk,
+                                filename,
+                            );
+                            text = "".to_string();
+                        }
+                        debloated.push(CLOSE_BRACKET.to_string());
+                    }
                     scope_env.lvl -= 1;
                 }
+                // the header of a loop keeps an empty body, its body being debloated on lines of
+                // its own
+                CLOSE_PARENTHESIS if matches!(parent.kind(), FOR_STMT | FOR_IN_STMT) => {
+                    text = append_text(&text, CLOSE_PARENTHESIS);
+                    text = append_text(&text, SEMICOLON);
+                }
+                // the annotation must be the only comment of a debloated statement; suppression
+                // comments are read from the original source
+                COMMENT => {}
                 SEMICOLON
                     if parent.kind() == BREAK_STMT
                         || parent.kind() == EMPTY_STMT
                         || parent.kind() == CONTINUE_STMT => {}
-                STRING_FRAGMENT => {
+                // literals are kept verbatim
+                STRING | TEMPLATE_STRING | REGEX => {
                     text = append_text(&text, child.text);
+                    skip = Some(child.info.range());
                 }
-                DOUBLE_QUOTE => {
-                    text = append_text(&text, DOUBLE_QUOTE);
+                EXPORT_CLAUSE => {
+                    skip = Some(child.info.range());
                 }
+                EXPORT | DEFAULT if parent.kind() == EXPORT_STMT => {}
                 COLON => {
                     if parent.kind() != SWITCH_CASE {
                         text = append_text(&text, child.text);
                     } else {
-                        aggregate(&mut debloated, &child, &text, first_stmt_blk, filename);
+                        aggregate(
+                            &mut debloated,
+                            &child,
+                            &text,
+                            &text_range,
+                            first_stmt_blk,
+                            filename,
+                        );
                         text = "".to_string();
                     }
                 }
                 IDENT => match parent.kind() {
-                    FUNC_DECL | CALL_EXPR => {
+                    FUNC_DECL | GENERATOR_FUNC_DECL | CALL_EXPR => {
                         text = append_text(&text, child.text);
                     }
                     _ => {
@@ -203,16 +382,29 @@ pub fn debloat_control_flow<'a>(nodes: &Vec<Node<'a>>, code: &'a str, filename:
                         text = append_text(&text, &ident);
                     }
                 },
-                _ => {
+                // `{ a }` binds `a` as `{ a : a_1_1 }`, which keeps the property it reads
+                SHORTHAND_PROPERTY_IDENT_PATTERN => {
+                    let ident = get_scoped_ident(&mut vars, child, &mut scope_env);
+                    text = append_text(&text, child.text);
+                    text = append_text(&text, COLON);
+                    text = append_text(&text, &ident);
+                }
+                // the tokens of other constructs are appended one by one
+                _ if child.info.child_count() == 0 => {
                     text = append_text(&text, child.text);
                 }
+                _ => {}
+            }
+            if text.len() > text_len {
+                if text_len == 0 {
+                    text_range = child.info.range();
+                }
+                text_range.end_byte = child.info.end_byte();
+                text_range.end_point = child.info.end_position();
             }
-            None
+            skip
         });
 
-        if node.kind() == FUNC_DECL {
-            debloated.push(CLOSE_BRACKET.to_string());
-        }
         if let Some(next_node) = node::get_next_node(nodes, &node) {
             node = next_node;
         } else {
@@ -222,12 +414,12 @@ pub fn debloat_control_flow<'a>(nodes: &Vec<Node<'a>>, code: &'a str, filename:
     debloated.join("\n")
 }
 
-pub fn debloat(filename: &str, debloated_filename: &str) {
-    let code = util::read_file(&filename).unwrap();
-    let tree = node::get_tree(&code);
+pub fn debloat(filename: &str, debloated_filename: &str) -> Result<()> {
+    let code = util::read_file(&filename)?;
+    let tree = node::get_tree(&code)?;
     let nodes = node::get_nodes(tree.walk(), Order::Pre, &code);
     let debloated_code = debloat_control_flow(&nodes, &code, filename);
-    util::jscode2file(debloated_filename, &debloated_code);
+    util::jscode2file(debloated_filename, &debloated_code)
 }
 
 #[cfg(test)]
@@ -238,7 +430,7 @@ mod tests {
     fn testSSA() {
         let filename = "test/debloat/scope.js";
         let debloated_filename = "test/debloat/scope_debloated.js";
-        debloat(filename, debloated_filename);
+        debloat(filename, debloated_filename).unwrap();
         let debloated_code = util::read_file(debloated_filename).unwrap();
         let debloated_code = debloated_code
             .split("\n")
@@ -274,4 +466,36 @@ a_0_0 = 12;"#;
         assert_eq!(debloated_code, expected);
         std::fs::remove_file(debloated_filename).unwrap();
     }
+
+    #[test]
+    fn test_shadowed_bindings() {
+        // destructured parameters and loop variables are renamed in the scope of the body they
+        // belong to, not resolved to the outer `id` and `a`, and loop headers still parse
+        let filename = "test/debloat/shadow.js";
+        let code = util::read_file(filename).unwrap();
+        let tree = node::get_tree(&code).unwrap();
+        let nodes = node::get_nodes(tree.walk(), Order::Pre, &code);
+        let debloated_code = debloat_control_flow(&nodes, &code, filename);
+        assert!(!node::get_tree(&debloated_code)
+            .unwrap()
+            .root_node()
+            .has_error());
+        let debloated_code = debloated_code
+            .lines()
+            .map(|line| line.split(" //").next().unwrap())
+            .collect::<Vec<_>>();
+        let expected = vec![
+            "var id_0_0 = \"s\" ;",
+            "var a_0_0 = \"t\" ;",
+            "function foo ( { id : id_1_1 } , xs_1_1 ) {",
+            "for ( const [ a_2_1 ] of xs_1_1 ) ;",
+            "let b_2_1 = a_2_1 ;",
+            "for ( let i_1_1 = 0 ; i_1_1 < xs_1_1 . length ; i_1_1 ++ ) ;",
+            "let c_2_2 = i_1_1 ;",
+            "return id_1_1 + 1 ;",
+            "}",
+            "foo ( { id : 1 } , [ [ 1 ] ] ) ;",
+        ];
+        assert_eq!(debloated_code, expected);
+    }
 }
@@ -0,0 +1,197 @@
+use crate::jssyntax::JSTyp;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+pub const CMP_MISMATCH: &str = "cmp-mismatch";
+pub const ARITH_COERCION: &str = "arith-coercion";
+pub const BIGINT_MIX: &str = "bigint-mix";
+pub const BIGINT_USHR: &str = "bigint-ushr";
+pub const SYMBOL_COERCION: &str = "symbol-coercion";
+pub const UNSUPPORTED_CONSTRUCT: &str = "unsupported-construct";
+pub const UNUSED_SUPPRESSION: &str = "unused-suppression";
+pub const TRACE_MISMATCH: &str = "trace-mismatch";
+pub const BUILTIN_COERCION: &str = "builtin-coercion";
+pub const BUILTIN_TYPE_ERROR: &str = "builtin-type-error";
+pub const STUB_ARG_MISMATCH: &str = "stub-arg-mismatch";
+pub const UNDECLARED_GLOBAL: &str = "undeclared-global";
+pub const NULLISH_PROPERTY: &str = "nullish-property";
+pub const MAYBE_NULLISH_PROPERTY: &str = "maybe-nullish-property";
+pub const NULLISH_DEREFERENCE: &str = "nullish-dereference";
+pub const MAYBE_NULLISH_DEREFERENCE: &str = "maybe-nullish-dereference";
+
+/// (rule id, default severity, description)
+pub const RULES: &[(&str, Severity, &str)] = &[
+    (
+        CMP_MISMATCH,
+        Severity::Warning,
+        "Comparison between values of different types",
+    ),
+    (
+        ARITH_COERCION,
+        Severity::Warning,
+        "Arithmetic operation that implicitly coerces its operands",
+    ),
+    (
+        BIGINT_MIX,
+        Severity::Error,
+        "BigInt mixed with another type throws a TypeError",
+    ),
+    (
+        BIGINT_USHR,
+        Severity::Error,
+        "Unsigned right shift of a BigInt throws a TypeError",
+    ),
+    (
+        SYMBOL_COERCION,
+        Severity::Error,
+        "Symbol converted to a number or string throws a TypeError",
+    ),
+    (
+        UNSUPPORTED_CONSTRUCT,
+        Severity::Note,
+        "Construct the analysis does not understand; its value is Unknown",
+    ),
+    (
+        UNUSED_SUPPRESSION,
+        Severity::Warning,
+        "Suppression comment that matches no diagnostic",
+    ),
+    (
+        TRACE_MISMATCH,
+        Severity::Warning,
+        "Parameter whose type observed at runtime was not inferred",
+    ),
+    (
+        BUILTIN_COERCION,
+        Severity::Warning,
+        "Argument that a builtin function implicitly coerces",
+    ),
+    (
+        BUILTIN_TYPE_ERROR,
+        Severity::Error,
+        "Argument on which a builtin function throws a TypeError",
+    ),
+    (
+        STUB_ARG_MISMATCH,
+        Severity::Warning,
+        "Argument of a type the stub declaration of the callee does not accept",
+    ),
+    (
+        UNDECLARED_GLOBAL,
+        Severity::Warning,
+        "Identifier declared neither in the module nor in the environment",
+    ),
+    (
+        NULLISH_PROPERTY,
+        Severity::Error,
+        "Property read or method call on `null` or `undefined`, which throws a TypeError",
+    ),
+    (
+        MAYBE_NULLISH_PROPERTY,
+        Severity::Warning,
+        "Property read or method call on a value that may be `null` or `undefined`",
+    ),
+    (
+        NULLISH_DEREFERENCE,
+        Severity::Error,
+        "Call, destructuring or for-of iteration of `null` or `undefined`, which throws a TypeError",
+    ),
+    (
+        MAYBE_NULLISH_DEREFERENCE,
+        Severity::Warning,
+        "Call, destructuring or for-of iteration of a value that may be `null` or `undefined`",
+    ),
+];
+
+pub fn default_severity(rule: &str) -> Severity {
+    RULES
+        .iter()
+        .find(|(id, _, _)| *id == rule)
+        .map(|(_, severity, _)| *severity)
+        .unwrap_or(Severity::Warning)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Note => write!(f, "note"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A range in the original (not debloated) source. Lines and columns are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Span {
+    pub file: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start_line, self.start_column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// inferred types of the operands, in source order
+    pub operand_typs: Vec<JSTyp>,
+    /// operand spans labelled with their types
+    pub labels: Vec<Label>,
+    /// explanations of how an operand got its type, e.g., where an `Undefined` came from
+    pub notes: Vec<Label>,
+}
+
+/// `Diagnostic` read back, e.g., from the cache, before its rule id is mapped to `RULES`
+#[derive(Deserialize)]
+struct RawDiagnostic {
+    rule: String,
+    severity: Severity,
+    message: String,
+    span: Span,
+    operand_typs: Vec<JSTyp>,
+    labels: Vec<Label>,
+    notes: Vec<Label>,
+}
+/// Derived `Deserialize` would require `'static` input for the rule id
+impl<'de> Deserialize<'de> for Diagnostic {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawDiagnostic::deserialize(deserializer)?;
+        let rule = RULES
+            .iter()
+            .map(|(id, _, _)| *id)
+            .find(|id| *id == raw.rule)
+            .ok_or_else(|| de::Error::custom(format!("unknown rule `{}`", raw.rule)))?;
+        Ok(Self {
+            rule,
+            severity: raw.severity,
+            message: raw.message,
+            span: raw.span,
+            operand_typs: raw.operand_typs,
+            labels: raw.labels,
+            notes: raw.notes,
+        })
+    }
+}
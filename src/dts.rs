@@ -0,0 +1,520 @@
+use crate::analysis::Analysis;
+use crate::diagnostic::Span;
+use crate::jssyntax::{
+    JSTyp, ARGS, ARRAY, ARROW_FUNC, CALL_EXPR, COMMENT, EXPORT_SPECIFIER, EXPORT_STMT, FALSE, FUNC,
+    FUNC_DECL, FUNC_EXPR, GENERATOR_FUNC_DECL, IDENT, IMPORT_CLAUSE, IMPORT_SPECIFIER,
+    LEXICAL_DECL, MEMBER_EXPR, METHOD_DEF, NAMESPACE_IMPORT, NULL, NUMBER, OBJECT, PAIR,
+    PARENTHESIZED_EXPR, PROPERTY_IDENT, RETURN_STMT, SHORTHAND_PROPERTY_IDENT, STRING,
+    TEMPLATE_STRING, TRUE, UNDEFINED, VARIABLE_DECLARATION, VAR_DECL,
+};
+use crate::project::{Module, Project, DEFAULT_EXPORT};
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+/// Nodes whose `return` statements belong to another function than the enclosing declaration
+const FUNC_KINDS: [&str; 6] = [
+    FUNC_DECL,
+    FUNC_EXPR,
+    FUNC,
+    ARROW_FUNC,
+    METHOD_DEF,
+    GENERATOR_FUNC_DECL,
+];
+
+/// TypeScript type of a value of any of `typs`, e.g., `number | undefined`. An operation that
+/// throws produces no value, so `Never` is left out; a type that could not be inferred makes the
+/// whole type `unknown`.
+pub fn ts_typ(typs: &[JSTyp]) -> String {
+    union(alternatives(typs))
+}
+
+/// TypeScript types of `typs`, one per type
+fn alternatives(typs: &[JSTyp]) -> Vec<String> {
+    typs.iter()
+        .filter(|typ| !typ.is_never())
+        .map(|typ| match typ {
+            JSTyp::Unknown | JSTyp::Never => "unknown",
+            JSTyp::Bool => "boolean",
+            JSTyp::Null => "null",
+            JSTyp::Undefined => "undefined",
+            JSTyp::Number => "number",
+            JSTyp::BigInt => "bigint",
+            JSTyp::String => "string",
+            JSTyp::Symbol => "symbol",
+            JSTyp::Object => "object",
+            JSTyp::Array => "unknown[]",
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// `typs` joined with `|`, nullable types last
+fn union(mut typs: Vec<String>) -> String {
+    if typs.is_empty() {
+        return "never".to_string();
+    }
+    if typs.iter().any(|typ| typ == "unknown") {
+        return "unknown".to_string();
+    }
+    typs.sort_by_key(|typ| (typ == "null" || typ == "undefined", typ.clone()));
+    typs.dedup();
+    typs.join(" | ")
+}
+
+/// Declarations of the exported functions and variables of `module` of `project` in a `.d.ts`
+/// file. A parameter has every type it was called with, a function returns every type it was inferred to
+/// return and a variable has the type of its initializer. An object or array literal that is
+/// returned or assigned is declared with its shape, e.g., `{ w: number; h: number }` or
+/// `string[]`. What the inference could not type is `unknown`.
+pub fn declarations(analysis: &Analysis, project: &Project, module: &Module) -> String {
+    let code = module.origin.as_str();
+    let root = module.tree.root_node();
+    let mut locals = vec![];
+    for local in module.exports.values() {
+        if !locals.contains(&local) {
+            locals.push(local);
+        }
+    }
+    let mut decls = locals
+        .into_iter()
+        .filter_map(|local| find_decl(root, code, local).map(|decl| (decl, local)))
+        .collect::<Vec<_>>();
+    decls.sort_by_key(|(decl, _)| decl.start_byte());
+
+    let mut lines = vec![];
+    let mut renamed = vec![];
+    for (decl, local) in decls {
+        let mut exported_as_local = false;
+        for (exported, _) in module.exports.iter().filter(|(_, other)| *other == local) {
+            if exported == local {
+                exported_as_local = true;
+            } else {
+                renamed.push(format!("{} as {}", local, exported));
+            }
+        }
+        let export = if exported_as_local { "export " } else { "" };
+        let declared = match decl.kind() {
+            FUNC_DECL => declare_func(analysis, project, module, decl, local),
+            _ => declare_var(analysis, module, decl, local),
+        };
+        lines.push(format!("{}declare {}", export, declared));
+    }
+    // e.g., `export default` and `module.exports = { g: f }`
+    renamed.sort_by_key(|specifier| specifier.ends_with(&format!(" as {}", DEFAULT_EXPORT)));
+    if !renamed.is_empty() {
+        lines.push(format!("export {{ {} }};", renamed.join(", ")));
+    }
+    if lines.is_empty() {
+        // still a module
+        lines.push("export {};".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The top-level declaration of the function or the declarator of the variable named `local`
+fn find_decl<'a>(root: Node<'a>, code: &str, local: &str) -> Option<Node<'a>> {
+    let mut cursor = root.walk();
+    let stmts = root.named_children(&mut cursor).collect::<Vec<_>>();
+    for stmt in stmts {
+        let stmt = match stmt.kind() {
+            EXPORT_STMT => match stmt.child_by_field_name("declaration") {
+                Some(decl) => decl,
+                None => continue,
+            },
+            _ => stmt,
+        };
+        match stmt.kind() {
+            FUNC_DECL => {
+                let name = stmt.child_by_field_name("name");
+                if name.is_some_and(|name| &code[name.byte_range()] == local) {
+                    return Some(stmt);
+                }
+            }
+            LEXICAL_DECL | VARIABLE_DECLARATION => {
+                let mut cursor = stmt.walk();
+                let declarator = stmt.named_children(&mut cursor).find(|declarator| {
+                    declarator.kind() == VAR_DECL
+                        && declarator
+                            .child_by_field_name("name")
+                            .is_some_and(|name| &code[name.byte_range()] == local)
+                });
+                if declarator.is_some() {
+                    return declarator;
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `function f(a: number): string;`
+fn declare_func(
+    analysis: &Analysis,
+    project: &Project,
+    module: &Module,
+    func: Node,
+    local: &str,
+) -> String {
+    let code = module.origin.as_str();
+    let mut params = vec![];
+    let callsites = callsites(project, module, local);
+    if let Some(formal) = func.child_by_field_name("parameters") {
+        let mut cursor = formal.walk();
+        for (idx, param) in formal.named_children(&mut cursor).enumerate() {
+            if param.kind() != IDENT {
+                // e.g., a destructuring pattern
+                params.push(format!("arg{}: unknown", idx));
+                continue;
+            }
+            let typs = analysis
+                .typs
+                .occurrences
+                .iter()
+                .filter(|occurrence| is_node(&occurrence.span, &module.filename, param))
+                .map(|occurrence| occurrence.typ.clone())
+                .collect::<Vec<_>>();
+            let typs = match &callsites {
+                Some(callsites) => with_ident_args(analysis, callsites, idx, typs),
+                None => typs,
+            };
+            // never called in the project
+            let typ = if typs.is_empty() {
+                "unknown".to_string()
+            } else {
+                ts_typ(&typs)
+            };
+            params.push(format!("{}: {}", &code[param.byte_range()], typ));
+        }
+    }
+    format!(
+        "function {}({}): {};",
+        local,
+        params.join(", "),
+        return_typ(analysis, module, func)
+    )
+}
+
+/// The arguments of every call of the function `local` of `module` in `project` and the filename
+/// of the caller, or `None` if the function is also referred to otherwise, e.g., as a method
+fn callsites<'p>(
+    project: &'p Project,
+    module: &Module,
+    local: &str,
+) -> Option<Vec<(&'p str, Node<'p>)>> {
+    let exported = module
+        .exports
+        .iter()
+        .filter(|(_, other)| *other == local)
+        .map(|(exported, _)| exported.as_str())
+        .collect::<Vec<_>>();
+    let mut callsites = vec![];
+    for caller in &project.modules {
+        let code = caller.origin.as_str();
+        let names = if caller.filename == module.filename {
+            vec![local]
+        } else {
+            caller
+                .imports
+                .iter()
+                .filter(|(_, import)| {
+                    import.module == module.filename
+                        && project.resolve_import(import) == Some(local)
+                })
+                .map(|(name, _)| name.as_str())
+                .collect()
+        };
+        let namespaces = caller
+            .namespaces
+            .iter()
+            .filter(|(_, imported)| **imported == module.filename)
+            .map(|(ns, _)| ns.as_str())
+            .collect::<Vec<_>>();
+        for node in traverse(caller.tree.walk(), Order::Pre) {
+            if node.kind() != IDENT {
+                continue;
+            }
+            let name = &code[node.byte_range()];
+            let parent = node.parent()?;
+            // e.g., `ns.f` of `import * as ns`
+            let callee = if namespaces.contains(&name) {
+                match parent.kind() {
+                    MEMBER_EXPR if parent.child_by_field_name("object") == Some(node) => {
+                        let property = parent.child_by_field_name("property")?;
+                        if !exported.contains(&&code[property.byte_range()]) {
+                            continue;
+                        }
+                        parent
+                    }
+                    NAMESPACE_IMPORT => continue,
+                    VAR_DECL if parent.child_by_field_name("name") == Some(node) => continue,
+                    _ => return None,
+                }
+            } else if names.contains(&name) {
+                node
+            } else {
+                continue;
+            };
+            let parent = callee.parent()?;
+            match parent.kind() {
+                CALL_EXPR if parent.child_by_field_name("function") == Some(callee) => {
+                    let args = parent
+                        .child_by_field_name("arguments")
+                        .filter(|args| args.kind() == ARGS)?;
+                    callsites.push((caller.filename.as_str(), args));
+                }
+                FUNC_DECL if parent.child_by_field_name("name") == Some(callee) => {}
+                IMPORT_CLAUSE | IMPORT_SPECIFIER | EXPORT_SPECIFIER | EXPORT_STMT => {}
+                _ => return None,
+            }
+        }
+    }
+    Some(callsites)
+}
+
+/// `typs` of the parameter at `idx` with the type of the identifier arguments at `callsites` as
+/// inferred in the caller. The callsites seed the inference with `Unknown` for an identifier, so
+/// `Unknown` is left out once every other argument is a literal.
+fn with_ident_args(
+    analysis: &Analysis,
+    callsites: &[(&str, Node)],
+    idx: usize,
+    typs: Vec<JSTyp>,
+) -> Vec<JSTyp> {
+    if !typs.iter().any(JSTyp::is_unknown) {
+        return typs;
+    }
+    let mut idents = vec![];
+    for (filename, args) in callsites {
+        let mut cursor = args.walk();
+        let arg = args
+            .named_children(&mut cursor)
+            // keep the position of the following arguments
+            .filter(|arg| arg.kind() != COMMENT)
+            .nth(idx);
+        match arg {
+            Some(arg) if arg.kind() == IDENT => {
+                match analysis.typs_at(filename, arg.start_byte()) {
+                    Some((_, arg_typs)) if !arg_typs.is_empty() => idents.extend(arg_typs),
+                    _ => return typs,
+                }
+            }
+            Some(arg)
+                if matches!(
+                    arg.kind(),
+                    TRUE | FALSE | NULL | UNDEFINED | NUMBER | STRING | OBJECT | ARRAY
+                ) => {}
+            // `undefined`
+            None => {}
+            _ => return typs,
+        }
+    }
+    let mut joined = typs
+        .into_iter()
+        .filter(|typ| !typ.is_unknown())
+        .collect::<Vec<_>>();
+    for typ in idents {
+        if !joined.contains(&typ) {
+            joined.push(typ);
+        }
+    }
+    joined
+}
+
+/// Every type `func` was inferred to return. The type of an object or array is refined with the
+/// shape of the literals returned if every `return` statement returns a literal or a variable.
+fn return_typ(analysis: &Analysis, module: &Module, func: Node) -> String {
+    let inferred = match func.child_by_field_name("name") {
+        Some(name) => analysis
+            .typs
+            .returns
+            .iter()
+            .filter(|(span, _)| is_node(span, &module.filename, name))
+            .map(|(_, typ)| typ.clone())
+            .collect::<Vec<_>>(),
+        None => vec![],
+    };
+    if inferred.is_empty() {
+        return "unknown".to_string();
+    }
+    let mut returned = vec![];
+    let Some(body) = func.child_by_field_name("body") else {
+        return ts_typ(&inferred);
+    };
+    collect_returns(body, &mut returned);
+    let literals = returned
+        .iter()
+        .map(|stmt| match stmt.named_child(0) {
+            Some(value) => literal_typ(analysis, module, value),
+            None => Some(vec!["undefined".to_string()]),
+        })
+        .collect::<Option<Vec<_>>>();
+    match literals {
+        Some(typs) if !inferred.iter().any(JSTyp::is_unknown) => {
+            let mut typs = typs.concat();
+            // e.g., `undefined` of a path without `return`
+            typs.extend(alternatives(
+                &inferred
+                    .into_iter()
+                    .filter(|typ| !matches!(typ, JSTyp::Object | JSTyp::Array))
+                    .collect::<Vec<_>>(),
+            ));
+            union(typs)
+        }
+        _ => ts_typ(&inferred),
+    }
+}
+
+/// `return` statements of the function whose body is `node`, not of the functions nested in it
+fn collect_returns<'a>(node: Node<'a>, returned: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        if child.kind() == RETURN_STMT {
+            returned.push(child);
+        } else if !FUNC_KINDS.contains(&child.kind()) {
+            collect_returns(child, returned);
+        }
+    }
+}
+
+/// `const c: number;`
+fn declare_var(analysis: &Analysis, module: &Module, declarator: Node, local: &str) -> String {
+    let keyword = match declarator.parent() {
+        // `const` or `let`
+        Some(decl) if decl.kind() == LEXICAL_DECL => decl.child(0).map_or("let", |kw| kw.kind()),
+        _ => "var",
+    };
+    let typ = declarator
+        .child_by_field_name("value")
+        .and_then(|value| literal_typ(analysis, module, value))
+        .map_or_else(|| "unknown".to_string(), union);
+    format!("{} {}: {};", keyword, local, typ)
+}
+
+/// Types of a literal, of an object or array literal with the types of its elements, or of a
+/// variable as inferred at `node`. `None` for other expressions.
+fn literal_typ(analysis: &Analysis, module: &Module, node: Node) -> Option<Vec<String>> {
+    let code = module.origin.as_str();
+    let typ = match node.kind() {
+        TRUE | FALSE => "boolean".to_string(),
+        NULL => "null".to_string(),
+        UNDEFINED => "undefined".to_string(),
+        NUMBER if code[node.byte_range()].ends_with('n') => "bigint".to_string(),
+        NUMBER => "number".to_string(),
+        STRING | TEMPLATE_STRING => "string".to_string(),
+        PARENTHESIZED_EXPR => return literal_typ(analysis, module, node.named_child(0)?),
+        IDENT | SHORTHAND_PROPERTY_IDENT => {
+            let typs = analysis
+                .typs_at(&module.filename, node.start_byte())
+                .map(|(_, typs)| typs)
+                .unwrap_or_default();
+            if typs.is_empty() {
+                return Some(vec!["unknown".to_string()]);
+            }
+            return Some(alternatives(&typs));
+        }
+        ARRAY => {
+            let mut cursor = node.walk();
+            let elements = node
+                .named_children(&mut cursor)
+                .map(|element| literal_typ(analysis, module, element))
+                .collect::<Option<Vec<_>>>()
+                .map_or_else(|| vec!["unknown".to_string()], |elements| elements.concat());
+            let element = if elements.is_empty() {
+                "unknown".to_string()
+            } else {
+                union(elements)
+            };
+            if element.contains(" | ") {
+                format!("({})[]", element)
+            } else {
+                format!("{}[]", element)
+            }
+        }
+        OBJECT => object_shape(analysis, module, node).unwrap_or_else(|| "object".to_string()),
+        _ => return None,
+    };
+    Some(vec![typ])
+}
+
+/// `{ a: number; b: string }`, or `None` if a property is computed or spread
+fn object_shape(analysis: &Analysis, module: &Module, node: Node) -> Option<String> {
+    let code = module.origin.as_str();
+    let mut properties = vec![];
+    let mut cursor = node.walk();
+    for property in node.named_children(&mut cursor) {
+        let (key, typ) = match property.kind() {
+            SHORTHAND_PROPERTY_IDENT => (
+                &code[property.byte_range()],
+                literal_typ(analysis, module, property),
+            ),
+            PAIR => {
+                let key = property.child_by_field_name("key")?;
+                if !matches!(key.kind(), PROPERTY_IDENT | STRING | NUMBER) {
+                    return None;
+                }
+                let value = property.child_by_field_name("value")?;
+                (
+                    &code[key.byte_range()],
+                    literal_typ(analysis, module, value),
+                )
+            }
+            _ => return None,
+        };
+        properties.push(format!(
+            "{}: {}",
+            key,
+            typ.map_or_else(|| "unknown".to_string(), union)
+        ));
+    }
+    if properties.is_empty() {
+        return Some("{}".to_string());
+    }
+    Some(format!("{{ {} }}", properties.join("; ")))
+}
+
+fn is_node(span: &Span, filename: &str, node: Node) -> bool {
+    span.file == filename
+        && span.start_byte == node.start_byte()
+        && span.end_byte == node.end_byte()
+}
+
+/// `lib.js` is declared by `lib.d.ts`, `lib.mjs` by `lib.d.mts` and `lib.cjs` by `lib.d.cts`
+pub fn dts_filename(filename: &str) -> String {
+    for (ext, dts) in [(".mjs", ".d.mts"), (".cjs", ".d.cts"), (".js", ".d.ts")] {
+        if let Some(stem) = filename.strip_suffix(ext) {
+            return format!("{}{}", stem, dts);
+        }
+    }
+    format!("{}.d.ts", filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::config::Config;
+    use crate::project::Project;
+
+    #[test]
+    fn test_ts_typ() {
+        assert_eq!(
+            ts_typ(&[JSTyp::Undefined, JSTyp::Number, JSTyp::Never]),
+            "number | undefined"
+        );
+        assert_eq!(ts_typ(&[JSTyp::Array, JSTyp::Bool]), "boolean | unknown[]");
+        assert_eq!(ts_typ(&[JSTyp::Object, JSTyp::Unknown]), "unknown");
+        assert_eq!(dts_filename("lib/a.js"), "lib/a.d.ts");
+        assert_eq!(dts_filename("a.cjs"), "a.d.cts");
+    }
+
+    #[test]
+    fn test_declarations() {
+        let filename = "test/dts/input.js";
+        let project = Project::load(&[filename.to_string()], 1).unwrap();
+        let analysis = analysis::analyze_project_typs(&project, &Config::default()).unwrap();
+        let declared = declarations(&analysis, &project, project.module(filename).unwrap());
+        let expected = crate::util::read_file("test/dts/expected.d.ts").unwrap();
+        assert_eq!(declared, expected);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::diagnostic::{self, Diagnostic, UNDECLARED_GLOBAL};
+use crate::error::Result;
+use crate::jssyntax::{
+    ARROW_FUNC, ASSIGNMENT_PATTERN, CATCH_CLAUSE, CLASS, CLASS_DECL, EXPORT_SPECIFIER,
+    FORMAL_PARAMS, FOR_IN_STMT, FUNC_DECL, FUNC_EXPR, GENERATOR_FUNC, GENERATOR_FUNC_DECL, IDENT,
+    IMPORT_CLAUSE, IMPORT_SPECIFIER, NAMESPACE_IMPORT, OBJECT_ASSIGNMENT_PATTERN, PAIR_PATTERN,
+    SHORTHAND_PROPERTY_IDENT, SHORTHAND_PROPERTY_IDENT_PATTERN, TYPEOF, UNARY_EXPR, VAR_DECL,
+};
+use crate::report;
+use crate::stubs::Stubs;
+use serde::Deserialize;
+use std::collections::HashSet;
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+const COMMON: &str = include_str!("env/common.json");
+const BROWSER: &str = include_str!("env/browser.json");
+const NODE: &str = include_str!("env/node.json");
+const WORKER: &str = include_str!("env/worker.json");
+
+/// Environment the analyzed code runs in, which declares its globals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// `window`, `document`, `localStorage`, ...
+    Browser,
+    /// `process`, `require`, `Buffer`, ...
+    Node,
+    /// `self`, `postMessage`, `importScripts`, ...
+    Worker,
+    /// Only the globals of ECMAScript
+    None,
+}
+
+impl Profile {
+    pub const ALL: &'static [Profile] = &[Profile::Browser, Profile::Node, Profile::Worker];
+
+    fn declarations(self) -> Option<&'static str> {
+        match self {
+            Self::Browser => Some(BROWSER),
+            Self::Node => Some(NODE),
+            Self::Worker => Some(WORKER),
+            Self::None => None,
+        }
+    }
+}
+
+/// Declares the globals of `profiles` in `stubs`. The globals every host has, e.g., `console`,
+/// are declared unless the only profile is `none`.
+pub fn load(stubs: &mut Stubs, profiles: &[Profile]) -> Result<()> {
+    let files = profiles
+        .iter()
+        .filter_map(|profile| profile.declarations())
+        .collect::<Vec<_>>();
+    if !files.is_empty() {
+        stubs.add_env(COMMON)?;
+    }
+    for file in files {
+        stubs.add_env(file)?;
+    }
+    Ok(())
+}
+
+/// Reports the identifiers of `code`, the original source of `filename`, that no declaration of
+/// the module binds and `is_declared` does not declare. Scopes are not told apart, so a name bound
+/// anywhere in the module is bound everywhere. `arguments` and the operand of `typeof`, which
+/// tests whether a global exists, are not reported.
+pub fn check_undeclared(
+    filename: &str,
+    code: &str,
+    tree: &Tree,
+    is_declared: impl Fn(&str) -> bool,
+) -> Vec<Diagnostic> {
+    let mut bound = HashSet::new();
+    for node in traverse(tree.walk(), Order::Pre) {
+        collect_bindings(node, code, &mut bound);
+    }
+    traverse(tree.walk(), Order::Pre)
+        .filter(|node| matches!(node.kind(), IDENT | SHORTHAND_PROPERTY_IDENT))
+        .filter(|node| {
+            let name = &code[node.byte_range()];
+            !bound.contains(name) && !is_declared(name) && name != "arguments"
+        })
+        .filter(|node| is_reference(*node))
+        .map(|node| {
+            let span = report::byte_span(filename, code, node.start_byte(), node.end_byte());
+            Diagnostic {
+                rule: UNDECLARED_GLOBAL,
+                severity: diagnostic::default_severity(UNDECLARED_GLOBAL),
+                message: format!(
+                    "`{}` is declared neither in the module nor in the environment",
+                    &code[node.byte_range()]
+                ),
+                span,
+                operand_typs: vec![],
+                labels: vec![],
+                notes: vec![],
+            }
+        })
+        .collect()
+}
+
+/// Names `node` declares
+fn collect_bindings<'a>(node: Node, code: &'a str, bound: &mut HashSet<&'a str>) {
+    let field = |name| node.child_by_field_name(name);
+    let patterns = match node.kind() {
+        VAR_DECL | FUNC_DECL | FUNC_EXPR | GENERATOR_FUNC_DECL | GENERATOR_FUNC | CLASS_DECL
+        | CLASS => vec![field("name")],
+        ARROW_FUNC | CATCH_CLAUSE => vec![field("parameter")],
+        // without `let`, `const` or `var`, the loop assigns an existing variable
+        FOR_IN_STMT if field("kind").is_some() => vec![field("left")],
+        IMPORT_SPECIFIER => vec![field("alias").or(field("name"))],
+        FORMAL_PARAMS | IMPORT_CLAUSE | NAMESPACE_IMPORT => {
+            let mut cursor = node.walk();
+            let children = node.named_children(&mut cursor).map(Some).collect();
+            children
+        }
+        _ => vec![],
+    };
+    for pattern in patterns.into_iter().flatten() {
+        bind_pattern(pattern, code, bound);
+    }
+}
+
+/// Names a binding pattern declares, e.g., `a` and `c` of `{ a, b: [c] = [] }`
+fn bind_pattern<'a>(node: Node, code: &'a str, bound: &mut HashSet<&'a str>) {
+    match node.kind() {
+        IDENT | SHORTHAND_PROPERTY_IDENT_PATTERN => {
+            bound.insert(&code[node.byte_range()]);
+        }
+        PAIR_PATTERN => {
+            if let Some(value) = node.child_by_field_name("value") {
+                bind_pattern(value, code, bound);
+            }
+        }
+        ASSIGNMENT_PATTERN | OBJECT_ASSIGNMENT_PATTERN => {
+            if let Some(left) = node.child_by_field_name("left") {
+                bind_pattern(left, code, bound);
+            }
+        }
+        _ => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                bind_pattern(child, code, bound);
+            }
+        }
+    }
+}
+
+/// Whether the identifier `node` refers to a variable rather than naming an import, an export or
+/// the operand of `typeof`
+fn is_reference(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return true;
+    };
+    match parent.kind() {
+        UNARY_EXPR => parent
+            .child_by_field_name("operator")
+            .is_none_or(|operator| operator.kind() != TYPEOF),
+        IMPORT_SPECIFIER => false,
+        // `export { a as b }` refers to `a` only, and `export { a } from "m"` to no local name
+        EXPORT_SPECIFIER => {
+            let reexported = parent
+                .parent()
+                .and_then(|clause| clause.parent())
+                .and_then(|export| export.child_by_field_name("source"))
+                .is_some();
+            !reexported && parent.child_by_field_name("alias") != Some(node)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins;
+    use crate::project::Module;
+
+    fn undeclared(profiles: &[Profile], code: &str) -> Vec<String> {
+        let mut stubs = Stubs::default();
+        load(&mut stubs, profiles).unwrap();
+        let module = Module::parse("test.js", code.to_string()).unwrap();
+        check_undeclared(&module.filename, &module.origin, &module.tree, |name| {
+            builtins::is_global(name) || stubs.global(name).is_some()
+        })
+        .into_iter()
+        .map(|diag| diag.message.split('`').nth(1).unwrap().to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_check_undeclared() {
+        let code = r#"
+import { a as b } from "./lib";
+export { b as c };
+function f(x, { y, z: [w] = [] }, ...rest) {
+  const g = (v) => v + x + y + w + rest.length;
+  try { g(1); } catch (e) { return e; }
+  for (const k in process.env) { console.log(k, arguments); }
+  if (typeof window !== "undefined") { return { document, b }; }
+  return Math.max(missing, 1);
+}
+"#;
+        assert_eq!(
+            undeclared(&[Profile::Node], code),
+            vec!["document", "missing"]
+        );
+        assert_eq!(
+            undeclared(&[Profile::Browser], code),
+            vec!["process", "missing"]
+        );
+        assert_eq!(
+            undeclared(&[Profile::None], code),
+            vec!["process", "console", "document", "missing"]
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let mut stubs = Stubs::default();
+        load(&mut stubs, Profile::ALL).unwrap();
+        // `setTimeout` of browsers returns a number and that of Node.js an object
+        let Some(crate::stubs::Decl::Function(set_timeout)) = stubs.global("setTimeout") else {
+            panic!("`setTimeout` is not a function");
+        };
+        assert_eq!(set_timeout.ret, crate::jssyntax::JSTyp::Unknown);
+        assert!(stubs.global("document").is_some());
+        assert!(stubs.global("postMessage").is_some());
+    }
+}
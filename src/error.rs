@@ -0,0 +1,52 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum TypeinferError {
+    /// Reading or writing a file failed
+    Io(std::io::Error),
+    /// tree-sitter could not produce a syntax tree
+    Parse(String),
+    /// A debloated node has no source mapping annotation (`// [Loc] ...`) to report against
+    MissingAnnot(String),
+    /// A source mapping annotation exists but could not be interpreted
+    MalformedAnnot(String),
+    /// Invalid command line arguments
+    Usage(String),
+    /// Invalid `typeinfer.toml` or `--set` override
+    Config(String),
+    /// Unreadable baseline file
+    Baseline(String),
+    /// Malformed Language Server Protocol message
+    Protocol(String),
+    /// Unreadable runtime type trace
+    Trace(String),
+    /// Invalid declaration stub file
+    Stub(String),
+}
+
+pub type Result<T> = std::result::Result<T, TypeinferError>;
+
+impl fmt::Display for TypeinferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Parse(msg) => write!(f, "parse error: {}", msg),
+            Self::MissingAnnot(code) => write!(f, "no source mapping annotation for `{}`", code),
+            Self::MalformedAnnot(annot) => write!(f, "malformed annotation `{}`", annot),
+            Self::Usage(msg) => write!(f, "{}", msg),
+            Self::Config(msg) => write!(f, "config error: {}", msg),
+            Self::Baseline(msg) => write!(f, "baseline error: {}", msg),
+            Self::Protocol(msg) => write!(f, "protocol error: {}", msg),
+            Self::Trace(msg) => write!(f, "trace error: {}", msg),
+            Self::Stub(msg) => write!(f, "stub error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TypeinferError {}
+
+impl From<std::io::Error> for TypeinferError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
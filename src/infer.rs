@@ -1,28 +1,82 @@
-use crate::debloat::NON_BRANCH_ANNOT;
+use crate::builtins::{self, Violation};
+use crate::callgraph::{self, Methods};
+use crate::debloat::{self, NON_BRANCH_ANNOT};
+use crate::diagnostic::Span;
+use crate::error::Result;
 use crate::jssyntax::{
-    JSOp, JSTyp, ADD, ASSIGNMENT_STMT, BINARY_EXPR, CALL_EXPR, CLOSE_BRACKET, COMMENT, DIV, EQ,
-    EXPR_STMT, FALSE, FORMAL_PARAMS, FUNC_DECL, GE, GT, IDENT, LE, LEXICAL_DECL, LT, MUL, NEQ,
-    NULL, NUMBER, OBJECT, OPEN_BRACKET, RETURN_STMT, SEQ, SNEQ, STMT_BLK, STRING, SUB, TRUE,
-    UNDEFINED,
+    JSOp, JSTyp, ADD, ADD_ASSIGN, AND, AND_ASSIGN, ARGS, ARRAY, ARRAY_PATTERN, ARROW_FUNC,
+    ASSIGNMENT_STMT, AUGMENTED_ASSIGNMENT_STMT, BINARY_EXPR, BIT_AND, BIT_AND_ASSIGN, BIT_OR,
+    BIT_OR_ASSIGN, BIT_XOR, BIT_XOR_ASSIGN, CALL_EXPR, COMMENT, DIV, DIV_ASSIGN, EQ, EXP,
+    EXPR_STMT, EXP_ASSIGN, FALSE, FORMAL_PARAMS, FOR_IN_STMT, FUNC_DECL, FUNC_EXPR, GE, GT, IDENT,
+    IN, INSTANCEOF, LE, LEXICAL_DECL, LT, MEMBER_EXPR, METHOD_DEF, MOD, MOD_ASSIGN, MUL,
+    MUL_ASSIGN, NEQ, NEW_EXPR, NULL, NULLISH, NULLISH_ASSIGN, NUMBER, OBJECT, OBJECT_PATTERN, OF,
+    OR, OR_ASSIGN, PAIR, PARENTHESIZED_EXPR, PROPERTY_IDENT, RETURN_STMT, SEQ, SHL, SHL_ASSIGN,
+    SHORTHAND_PROPERTY_IDENT, SHR, SHR_ASSIGN, SNEQ, SPREAD_ELEMENT, STMT_BLK, STRING, SUB,
+    SUBSCRIPT_EXPR, SUB_ASSIGN, THIS, TRUE, UNDEFINED, USHR, USHR_ASSIGN, VAR_DECL,
 };
 use crate::node::{self, Node};
-use std::collections::{HashMap, HashSet};
-use tree_sitter_traversal::Order;
+use crate::report::{Dereference, Reporter};
+use crate::stubs::Decl;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-type VarMap = HashMap<(usize, String), HashSet<(usize, JSTyp)>>; // <(scope, variable), (parent node id, jstyp)>
-fn varmap_to_string(varmap: &VarMap) -> String {
-    let mut s = "".to_string();
-    let mut keys = varmap.keys().collect::<Vec<_>>();
-    keys.sort();
-    for k in keys {
-        let (_, var) = k;
-        let mut typ_str = "".to_string();
-        for (_, typ) in varmap.get(k).unwrap() {
-            typ_str = format!("{} {:?},", typ_str, typ);
-        }
-        s = format!("{} {}: ({})", s, var, &typ_str[1..typ_str.len() - 1]);
-    }
-    s
+pub type VarMap = HashMap<(usize, String), HashSet<(usize, JSTyp)>>; // <(scope, variable), (parent node id, jstyp)>
+
+/// What a module knows of the functions it can call, keyed by the name they are called by
+#[derive(Debug, Clone, Default)]
+pub struct Summaries {
+    /// return types of the functions of the project
+    pub returns: HashMap<String, JSTyp>,
+    /// declarations of the globals and of the bindings of the packages it imports, from the stub
+    /// files
+    pub stubs: BTreeMap<String, Decl>,
+    /// functions of `returns` called as methods of the object literals of the module
+    pub methods: Methods,
+}
+
+/// Property types of an object keyed by property name, e.g., of the receiver of a method call
+pub type Props = BTreeMap<String, JSTyp>;
+
+/// How a function is called, i.e., what it sees beyond its own code and its arguments
+pub struct Call<'s> {
+    /// what the module of the function knows of the functions it calls
+    pub summaries: &'s Summaries,
+    /// property types of `this` when it is called as a method
+    pub this: &'s Props,
+    /// original location of the call, if any
+    pub callsite: Option<&'s Span>,
+}
+
+/// Pseudo variable holding the types a function returns
+const RETURN_VAR: &str = "<return>";
+
+/// Allocation site of the object created by `node`, an object or array literal or a `new`
+/// expression, e.g., `@12` for the literal at byte 12 of the debloated code. The heap keeps the
+/// properties of each site apart.
+fn alloc_site(node: &Node) -> String {
+    format!("@{}", node.info.start_byte())
+}
+
+/// Allocation site standing for the object of the variable `loc` that the function does not
+/// create, e.g., of a parameter or `this`
+fn own_site(loc: &str) -> String {
+    format!("@{}", loc)
+}
+
+/// Pseudo variable holding the types of the property `prop` of the objects allocated at `site`,
+/// e.g., `@12.w`. The site itself holds `Object` once every property of its objects is known.
+fn prop_var(site: &str, prop: &str) -> String {
+    format!("{}.{}", site, prop)
+}
+
+/// Pseudo variable recording that the variable or property `loc` may point to the objects of
+/// `site`, e.g., `o_1_1->@12`
+fn points_to_var(loc: &str, site: &str) -> String {
+    format!("{}->{}", loc, site)
+}
+
+/// Whether `var` is a pseudo variable of the heap rather than a variable of the function
+fn is_heap_var(var: &str) -> bool {
+    var.contains(['.', '@'])
 }
 
 fn is_overwritable(
@@ -72,43 +126,128 @@ fn overwrite_var(vars: &mut VarMap, scope: usize, var: &str, typ: JSTyp, parent_
     vars.insert((scope, var.to_string()), s);
 }
 
+/// Declares the environment `globals` that `func` uses without declaring them itself
+pub fn declare_globals<'a>(
+    vars: &mut VarMap,
+    globals: &BTreeMap<String, JSTyp>,
+    func: &Node<'a>,
+    code: &'a str,
+) {
+    let mut declared = HashSet::new();
+    let mut used = vec![];
+    node::run_subtree(func, code, |child, _| {
+        if child.kind() == IDENT {
+            match child.info.parent().map(|parent| parent.kind()) {
+                Some(VAR_DECL) | Some(FORMAL_PARAMS) => {
+                    declared.insert(debloat::orig_ident(child.text));
+                }
+                _ => used.push(child.text),
+            }
+        }
+        None
+    });
+    for var in used {
+        let name = debloat::orig_ident(var);
+        if let (false, Some(typ)) = (declared.contains(name), globals.get(name)) {
+            insert_var(vars, 0, var, typ.clone(), 0);
+        }
+    }
+}
+
+/// Infers `node` with `param_typs` passed by `call` and returns the type of its return value. A
+/// function returning values of several types returns `Unknown`. A function called as a method
+/// sees the properties of its receiver as `this`.
 pub fn run_func<'a>(
     vars: &mut VarMap,
+    reporter: &mut Reporter,
+    call: &Call,
     param_typs: &Vec<JSTyp>,
     nodes: &Vec<Node<'a>>,
     node: &Node<'a>,
-    code: &str,
-) {
+    code: &'a str,
+) -> Result<JSTyp> {
     assert_eq!(node.kind(), FUNC_DECL);
+    let func_name = node::get_field(node, "name", code).map_or("", |name| name.text);
+    let Call {
+        summaries,
+        this,
+        callsite,
+    } = *call;
     let mut scope = 0;
-    node::run_subtree(node, code, |child, last| {
+    if !this.is_empty() {
+        let site = own_site(THIS);
+        insert_var(vars, scope, THIS, JSTyp::Object, 0);
+        point(vars, scope, THIS, std::slice::from_ref(&site), 0, true);
+        for (prop, typ) in this {
+            insert_var(vars, scope, &prop_var(&site, prop), typ.clone(), 0);
+        }
+    }
+    node::try_run_subtree(node, code, |child, _| {
         match child.kind() {
             FORMAL_PARAMS => {
                 for (idx, param_child) in get_func_params(child, code).iter().enumerate() {
-                    let parent_id = node::get_parent_id(node::get_annot(param_child, code));
-                    insert_var(
-                        vars,
-                        0,
-                        param_child.text,
-                        param_typs[idx].clone(),
-                        parent_id,
-                    );
+                    if param_child.kind() != IDENT {
+                        // a destructured parameter throws without an object, e.g., `f({ a })`
+                        // called without an argument
+                        if let OBJECT_PATTERN | ARRAY_PATTERN = param_child.kind() {
+                            let typ = param_typs.get(idx).cloned().unwrap_or(JSTyp::Undefined);
+                            let dereference = Dereference::Destructure;
+                            report_dereference(
+                                reporter,
+                                param_child,
+                                param_child,
+                                param_child.text,
+                                &[typ],
+                                dereference,
+                                code,
+                            )?;
+                        }
+                        // e.g., default values and destructuring patterns
+                        reporter.report_unsupported(param_child, code)?;
+                        continue;
+                    }
+                    let parent_id = node::get_parent_id(node::get_annot(param_child, code)?)?;
+                    // a parameter without an argument is `undefined`
+                    let typ = param_typs.get(idx).cloned().unwrap_or(JSTyp::Undefined);
+                    if let (JSTyp::Undefined, Some(callsite)) = (&typ, callsite) {
+                        let reason = if idx < param_typs.len() {
+                            format!("`{}` is called with `undefined`", func_name)
+                        } else {
+                            format!("`{}` is called without it", func_name)
+                        };
+                        reporter.trace_undefined_at(param_child.text, callsite.clone(), &reason);
+                    }
+                    reporter.record_typ(param_child, code, &typ)?;
+                    insert_var(vars, 0, param_child.text, typ, parent_id);
                 }
             }
-            STMT_BLK => run_stmt_blk(&mut scope, vars, nodes, child, code),
+            STMT_BLK => run_stmt_blk(&mut scope, vars, reporter, summaries, nodes, child, code)?,
             _ => {}
         }
-        Some(child.info.range())
-    });
+        Ok(Some(child.info.range()))
+    })?;
+    let returned = match vars.get(&(0, RETURN_VAR.to_string())) {
+        Some(typs) => typs.iter().map(|(_, typ)| typ.clone()).collect(),
+        None => vec![JSTyp::Undefined],
+    };
+    if let Some(name) = node::get_field(node, "name", code) {
+        for typ in &returned {
+            reporter.record_return(&name, code, typ)?;
+        }
+    }
+    let typ = match returned.as_slice() {
+        [typ] => typ.clone(),
+        _ => JSTyp::Unknown,
+    };
+    Ok(typ)
 }
 
 fn get_func_params<'a>(node: &Node<'a>, code: &'a str) -> Vec<Node<'a>> {
     assert_eq!(node.kind(), FORMAL_PARAMS);
     let mut params = vec![];
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            IDENT => params.push(child.clone()),
-            _ => {}
+    node::run_subtree(node, code, |child, _| {
+        if child.info.is_named() && child.kind() != COMMENT {
+            params.push(child.clone());
         }
         Some(child.info.range())
     });
@@ -118,246 +257,1407 @@ fn get_func_params<'a>(node: &Node<'a>, code: &'a str) -> Vec<Node<'a>> {
 fn run_stmt_blk<'a>(
     scope: &mut usize,
     vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
     nodes: &Vec<Node<'a>>,
     node: &Node<'a>,
-    code: &str,
-) {
+    code: &'a str,
+) -> Result<()> {
     assert_eq!(node.kind(), STMT_BLK);
-    node::run_subtree(node, code, |child, last| {
+    node::try_run_subtree(node, code, |child, _| {
+        if let LEXICAL_DECL | ASSIGNMENT_STMT | EXPR_STMT | RETURN_STMT = child.kind() {
+            record_env(vars, reporter, child, code)?;
+        }
         match child.kind() {
             LEXICAL_DECL => {
-                run_lexical_decl(scope, vars, child, code);
+                run_lexical_decl(scope, vars, reporter, summaries, child, code)?;
             }
             ASSIGNMENT_STMT => {
-                let (lhs, typ, parent_id) = run_assignment_stmt(scope, vars, child, code);
-                insert_var(vars, *scope, lhs, typ.clone(), parent_id);
+                if let Some((lhs, typ, parent_id)) =
+                    run_assignment_stmt(scope, vars, reporter, summaries, child, code)?
+                {
+                    insert_var(vars, *scope, lhs, typ, parent_id);
+                }
             }
             EXPR_STMT => {
-                run_expr_stmt(scope, vars, child, code);
+                run_expr_stmt(scope, vars, reporter, summaries, child, code)?;
+            }
+            FOR_IN_STMT => {
+                run_for_in_stmt(scope, vars, reporter, summaries, child, code)?;
             }
             STMT_BLK => {
-                run_stmt_blk(scope, vars, nodes, child, code);
+                run_stmt_blk(scope, vars, reporter, summaries, nodes, child, code)?;
             }
             RETURN_STMT => {
-                run_return_stmt(child, code);
-                println!("vars: {}", varmap_to_string(&vars));
+                run_return_stmt(scope, vars, reporter, summaries, child, code)?;
             }
             _ => {}
         }
-        Some(child.info.range())
+        Ok(Some(child.info.range()))
     })
 }
 
-fn run_return_stmt<'a>(node: &Node<'a>, code: &str) -> String {
+/// Records the types of the variables before `stmt` by their original names. A variable with
+/// several types, or whose original name several variables share, is `Unknown`.
+fn record_env<'a>(
+    vars: &VarMap,
+    reporter: &mut Reporter,
+    stmt: &Node<'a>,
+    code: &'a str,
+) -> Result<()> {
+    if !reporter.records_typs() {
+        return Ok(());
+    }
+    let mut env = BTreeMap::new();
+    for ((_, var), typs) in vars {
+        if var == RETURN_VAR || is_heap_var(var) {
+            continue;
+        }
+        let typ = match typs.iter().next() {
+            Some((_, typ)) if typs.len() == 1 => typ.clone(),
+            _ => JSTyp::Unknown,
+        };
+        env.entry(debloat::orig_ident(var).to_string())
+            .and_modify(|other| {
+                if *other != typ {
+                    *other = JSTyp::Unknown;
+                }
+            })
+            .or_insert(typ);
+    }
+    reporter.record_env(stmt, code, env)
+}
+
+/// The type of the returned value is recorded as `RETURN_VAR`
+fn run_return_stmt<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<()> {
     assert_eq!(node.kind(), RETURN_STMT);
-    let mut ident = "".to_string();
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            IDENT => ident = child.text.to_string(),
-            _ => {}
+    let typ = match node.info.named_child(0) {
+        Some(expr) => {
+            let expr = Node {
+                info: expr,
+                text: &code[expr.byte_range()],
+            };
+            run_expr(scope, vars, reporter, summaries, &expr, code)?
         }
-        Some(child.info.range())
-    });
-    ident
+        None => JSTyp::Undefined,
+    };
+    let parent_id = node::get_parent_id(node::get_annot(node, code)?)?;
+    insert_var(vars, *scope, RETURN_VAR, typ, parent_id);
+    Ok(())
 }
 
-fn run_expr_stmt<'a>(scope: &mut usize, vars: &mut VarMap, node: &Node<'a>, code: &str) {
+fn run_expr_stmt<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<()> {
     assert_eq!(node.kind(), EXPR_STMT);
-    node::run_subtree(node, code, |child, last| {
+    node::try_run_subtree(node, code, |child, _| {
         match child.kind() {
             BINARY_EXPR => {
-                run_binary_expr(scope, vars, child, code, &None);
-                return Some(child.info.range());
+                run_binary_expr(scope, vars, reporter, summaries, child, code)?;
+                return Ok(Some(child.info.range()));
             }
             ASSIGNMENT_STMT => {
-                let (lhs, typ, parent_id) = run_assignment_stmt(scope, vars, child, code);
-                if !overwrite_typ(*scope, vars, &node, code, lhs, typ.clone()) {
-                    insert_var(vars, *scope, lhs, typ.clone(), parent_id);
+                if let Some((lhs, typ, parent_id)) =
+                    run_assignment_stmt(scope, vars, reporter, summaries, child, code)?
+                {
+                    if !overwrite_typ(*scope, vars, node, code, lhs, typ.clone())? {
+                        insert_var(vars, *scope, lhs, typ, parent_id);
+                    }
+                }
+                return Ok(Some(child.info.range()));
+            }
+            AUGMENTED_ASSIGNMENT_STMT => {
+                if let Some((lhs, typ, parent_id)) =
+                    run_augmented_assignment_stmt(scope, vars, reporter, summaries, child, code)?
+                {
+                    if !overwrite_typ(*scope, vars, node, code, lhs, typ.clone())? {
+                        insert_var(vars, *scope, lhs, typ, parent_id);
+                    }
+                }
+                return Ok(Some(child.info.range()));
+            }
+            CALL_EXPR | MEMBER_EXPR if short_circuits(vars, *scope, child, code) => {
+                return Ok(Some(child.info.range()));
+            }
+            // calling `null` or `undefined`, or a method of them, throws before the arguments are
+            // evaluated
+            CALL_EXPR if run_callee(scope, vars, reporter, child, code)? => {
+                return Ok(Some(child.info.range()));
+            }
+            // e.g., `g(u.quux);` of a function of the project, whose arguments are read
+            CALL_EXPR if callee_summary(summaries, child, code).is_some() => {
+                run_args(
+                    scope,
+                    vars,
+                    reporter,
+                    summaries,
+                    child,
+                    code,
+                    |_, _, _, _| Ok(false),
+                )?;
+                return Ok(Some(child.info.range()));
+            }
+            // e.g., `u.bar;` or the condition `u.b` of `if (u.b)`, which reads `bar` of `u`
+            MEMBER_EXPR if is_stmt_value(node, child) => {
+                run_member_expr(scope, vars, reporter, summaries, child, code)?;
+                return Ok(Some(child.info.range()));
+            }
+            // e.g., `arr.forEach(f);`, whose arguments are checked. The receiver of a builtin method
+            // other than a variable or a literal is not evaluated, which would note it as
+            // unsupported.
+            CALL_EXPR if callee_summary(summaries, child, code).is_none() => {
+                let typ = match run_stub_call(scope, vars, reporter, summaries, child, code)? {
+                    Some(typ) => Some(typ),
+                    None if has_simple_callee(child, code) => {
+                        run_builtin_call(scope, vars, reporter, summaries, child, code)?
+                    }
+                    None => None,
+                };
+                if typ.is_some() {
+                    return Ok(Some(child.info.range()));
                 }
-                return Some(child.info.range());
             }
             _ => {}
         }
-        None
+        Ok(None)
     })
 }
 
-fn run_lexical_decl<'a>(scope: &mut usize, vars: &mut VarMap, node: &Node<'a>, code: &str) {
+/// Whether `expr` is the value of the expression statement `stmt`, e.g., `u.bar` of `u.bar;`, or
+/// of the debloated condition of a branch, e.g., `u.b` of `( u.b );`
+fn is_stmt_value(stmt: &Node, expr: &Node) -> bool {
+    let mut parent = expr.info.parent();
+    if let Some(paren) = parent.filter(|paren| paren.kind() == PARENTHESIZED_EXPR) {
+        parent = paren.parent();
+    }
+    parent.is_some_and(|parent| parent.id() == stmt.info.id())
+}
+
+/// The debloated header of a `for-in` or `for-of` loop, e.g., `for ( const x_2_1 of xs_1_1 ) ;`,
+/// whose body follows it. Iterating over `null` or `undefined` throws.
+fn run_for_in_stmt<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<()> {
+    assert_eq!(node.kind(), FOR_IN_STMT);
+    let Some(iterable) = node::get_field(node, "right", code) else {
+        return Ok(());
+    };
+    let typ = run_expr(scope, vars, reporter, summaries, &iterable, code)?;
+    match node::get_field(node, "operator", code) {
+        Some(op) if op.kind() == OF => {}
+        // `for-in` iterates over the keys of any value
+        _ => return Ok(()),
+    }
+    let typs = locations(vars, *scope, &iterable, code)
+        .and_then(|locs| locations_typs(vars, *scope, &locs))
+        .unwrap_or_else(|| vec![typ]);
+    let dereference = Dereference::Iterate;
+    report_dereference(
+        reporter,
+        node,
+        &iterable,
+        iterable.text,
+        &typs,
+        dereference,
+        code,
+    )?;
+    Ok(())
+}
+
+fn run_lexical_decl<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<()> {
     assert_eq!(node.kind(), LEXICAL_DECL);
-    let mut ident = None;
-    let mut typ = JSTyp::Undefined;
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            OPEN_BRACKET => {
-                *scope += 1;
+    let parent_id = node::get_parent_id(node::get_annot(node, code)?)?;
+    node::try_run_subtree(node, code, |child, _| {
+        if child.kind() != VAR_DECL {
+            return Ok(Some(child.info.range()));
+        }
+        match node::get_field(child, "name", code) {
+            Some(ident) if ident.kind() == IDENT => {
+                let value = node::get_field(child, "value", code);
+                let typ = match &value {
+                    Some(value) => run_expr(scope, vars, reporter, summaries, value, code)?,
+                    None => JSTyp::Undefined,
+                };
+                if typ == JSTyp::Undefined {
+                    trace_undefined(reporter, ident.text, &ident, value.as_ref(), code)?;
+                }
+                reporter.record_typ(&ident, code, &typ)?;
+                insert_var(vars, *scope, ident.text, typ, parent_id);
+                if let Some(value) = &value {
+                    let sites = run_sites(scope, vars, reporter, summaries, value, code)?;
+                    point(vars, *scope, ident.text, &sites, parent_id, true);
+                }
             }
-            CLOSE_BRACKET => {
-                *scope -= 1;
+            // destructuring patterns, whose bindings are not typed
+            Some(pattern) => {
+                if let (OBJECT_PATTERN | ARRAY_PATTERN, Some(value)) =
+                    (pattern.kind(), node::get_field(child, "value", code))
+                {
+                    let typ = run_expr(scope, vars, reporter, summaries, &value, code)?;
+                    let typs = locations(vars, *scope, &value, code)
+                        .and_then(|locs| locations_typs(vars, *scope, &locs))
+                        .unwrap_or_else(|| vec![typ]);
+                    let dereference = Dereference::Destructure;
+                    report_dereference(
+                        reporter,
+                        child,
+                        &value,
+                        value.text,
+                        &typs,
+                        dereference,
+                        code,
+                    )?;
+                }
+                reporter.report_unsupported(&pattern, code)?;
             }
-            IDENT => {
-                if let Some(ident_) = ident {
-                    let parent_id = node::get_parent_id(node::get_annot(node, code));
-                    insert_var(vars, *scope, ident_, typ.clone(), parent_id);
+            None => reporter.report_unsupported(child, code)?,
+        }
+        Ok(Some(child.info.range()))
+    })
+}
+
+/// returns `None` if the assignment target is not a plain variable. A property, e.g.,
+/// `obj.prop = ...`, is assigned here in every object `obj` may point to.
+fn run_assignment_stmt<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<Option<(&'a str, JSTyp, usize)>> {
+    assert_eq!(node.kind(), ASSIGNMENT_STMT);
+    let (lhs, rhs) = match (
+        node::get_field(node, "left", code),
+        node::get_field(node, "right", code),
+    ) {
+        (Some(lhs), Some(rhs)) => (lhs, rhs),
+        _ => {
+            reporter.report_unsupported(node, code)?;
+            return Ok(None);
+        }
+    };
+    let typ = run_expr(scope, vars, reporter, summaries, &rhs, code)?;
+    let parent_id = node::get_parent_id(node::get_annot(node, code)?)?;
+    let sites = run_sites(scope, vars, reporter, summaries, &rhs, code)?;
+    // an assignment on every path replaces what the target pointed to
+    let strong = on_every_path(node, code);
+    match lhs.kind() {
+        IDENT => point(vars, *scope, lhs.text, &sites, parent_id, strong),
+        MEMBER_EXPR => {
+            let Some(cells) = target_props(vars, *scope, &lhs, code) else {
+                // e.g., `f().prop = ...`
+                reporter.report_unsupported(&lhs, code)?;
+                return Ok(None);
+            };
+            // a property of one of several objects may keep its type
+            let strong = strong && cells.len() == 1;
+            for cell in &cells {
+                if strong {
+                    overwrite_var(vars, *scope, cell, typ.clone(), parent_id);
+                } else {
+                    insert_var(vars, *scope, cell, typ.clone(), parent_id);
                 }
-                ident = Some(child.text);
+                point(vars, *scope, cell, &sites, parent_id, strong);
             }
-            TRUE | FALSE => {
-                typ = JSTyp::Bool;
+            return Ok(None);
+        }
+        _ => {
+            // e.g., `obj[key] = ...`
+            reporter.report_unsupported(&lhs, code)?;
+            return Ok(None);
+        }
+    }
+    if typ == JSTyp::Undefined {
+        trace_undefined(reporter, lhs.text, &lhs, Some(&rhs), code)?;
+    }
+    reporter.record_typ(&lhs, code, &typ)?;
+    Ok(Some((lhs.text, typ, parent_id)))
+}
+
+/// Whether the assignment `node` is a statement of its own that runs on every path
+fn on_every_path<'a>(node: &Node<'a>, code: &'a str) -> bool {
+    node.info.parent().is_some_and(|stmt| {
+        stmt.kind() == EXPR_STMT
+            && is_non_branch(
+                &Node {
+                    info: stmt,
+                    text: &code[stmt.byte_range()],
+                },
+                code,
+            )
+    })
+}
+
+/// Records why `var` declared or assigned at `target` is `undefined`
+fn trace_undefined<'a>(
+    reporter: &mut Reporter,
+    var: &str,
+    target: &Node<'a>,
+    value: Option<&Node<'a>>,
+    code: &'a str,
+) -> Result<()> {
+    match value {
+        None => reporter.trace_undefined(var, target, code, "it is declared without a value"),
+        Some(value) if value.kind() == UNDEFINED => {
+            reporter.trace_undefined(var, value, code, "it is assigned `undefined`")
+        }
+        Some(value) if value.kind() == IDENT => {
+            reporter.copy_undefined_origin(var, value.text);
+            Ok(())
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// `lhs op= rhs` is checked as the implied `lhs op rhs` and its result becomes the type of `lhs`
+fn run_augmented_assignment_stmt<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<Option<(&'a str, JSTyp, usize)>> {
+    assert_eq!(node.kind(), AUGMENTED_ASSIGNMENT_STMT);
+    let (lhs, rhs, op) = match (
+        node::get_field(node, "left", code),
+        node::get_field(node, "right", code),
+        node::get_field(node, "operator", code),
+    ) {
+        (Some(lhs), Some(rhs), Some(op)) => (lhs, rhs, op),
+        _ => {
+            reporter.report_unsupported(node, code)?;
+            return Ok(None);
+        }
+    };
+    let op = match op.kind() {
+        ADD_ASSIGN => JSOp::Add,
+        SUB_ASSIGN => JSOp::Sub,
+        MUL_ASSIGN => JSOp::Mul,
+        DIV_ASSIGN => JSOp::Div,
+        MOD_ASSIGN => JSOp::Mod,
+        EXP_ASSIGN => JSOp::Exp,
+        SHL_ASSIGN => JSOp::Shl,
+        SHR_ASSIGN => JSOp::Shr,
+        USHR_ASSIGN => JSOp::UShr,
+        BIT_AND_ASSIGN => JSOp::BitAnd,
+        BIT_OR_ASSIGN => JSOp::BitOr,
+        BIT_XOR_ASSIGN => JSOp::BitXor,
+        AND_ASSIGN => JSOp::And,
+        OR_ASSIGN => JSOp::Or,
+        NULLISH_ASSIGN => JSOp::Nullish,
+        _ => {
+            reporter.report_unsupported(&op, code)?;
+            return Ok(None);
+        }
+    };
+    let rhs_typ = run_expr(scope, vars, reporter, summaries, &rhs, code)?;
+    // e.g., `o.w += 1`, whose property is assigned in every object `o` may point to
+    if lhs.kind() == MEMBER_EXPR {
+        let lhs_typ = run_member_expr(scope, vars, reporter, summaries, &lhs, code)?;
+        if lhs_typ.is_never() {
+            return Ok(None);
+        }
+        let Some(cells) = target_props(vars, *scope, &lhs, code) else {
+            reporter.report_unsupported(&lhs, code)?;
+            return Ok(None);
+        };
+        let typ = op.execute(reporter, &lhs_typ, &rhs_typ, node, code)?;
+        let parent_id = node::get_parent_id(node::get_annot(node, code)?)?;
+        let strong = on_every_path(node, code) && cells.len() == 1;
+        for cell in &cells {
+            if strong {
+                overwrite_var(vars, *scope, cell, typ.clone(), parent_id);
+            } else {
+                insert_var(vars, *scope, cell, typ.clone(), parent_id);
             }
-            NULL => {
-                typ = JSTyp::Null;
+        }
+        return Ok(None);
+    }
+    if lhs.kind() != IDENT {
+        reporter.report_unsupported(&lhs, code)?;
+        return Ok(None);
+    }
+    let lhs_typ = kind2typ(&lhs, vars, *scope, lhs.text, code);
+    let typ = op.execute(reporter, &lhs_typ, &rhs_typ, node, code)?;
+    reporter.record_typ(&lhs, code, &typ)?;
+    let parent_id = node::get_parent_id(node::get_annot(node, code)?)?;
+    Ok(Some((lhs.text, typ, parent_id)))
+}
+
+/// Infers the type of an expression. Constructs that are not supported yet are `Unknown`.
+fn run_expr<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<JSTyp> {
+    match node.kind() {
+        // the rest of a short-circuited optional chain is not evaluated, e.g., `.y` of `o?.x.y`
+        MEMBER_EXPR | SUBSCRIPT_EXPR | CALL_EXPR if short_circuits(vars, *scope, node, code) => {
+            Ok(JSTyp::Undefined)
+        }
+        BINARY_EXPR => run_binary_expr(scope, vars, reporter, summaries, node, code),
+        PARENTHESIZED_EXPR => match node.info.named_child(0) {
+            Some(expr) => {
+                let expr = Node {
+                    info: expr,
+                    text: &code[expr.byte_range()],
+                };
+                run_expr(scope, vars, reporter, summaries, &expr, code)
             }
-            UNDEFINED => {
-                typ = JSTyp::Undefined;
+            None => Ok(JSTyp::Unknown),
+        },
+        IDENT => {
+            let mut typ = kind2typ(node, vars, *scope, node.text, code);
+            if !vars.contains_key(&(*scope, node.text.to_string())) {
+                if let Some((_, decl)) = stub_decl(vars, summaries, node, code) {
+                    typ = decl.typ();
+                }
             }
-            NUMBER => {
-                typ = number2typ(child);
+            reporter.record_typ(node, code, &typ)?;
+            Ok(typ)
+        }
+        TRUE | FALSE | NULL | UNDEFINED | NUMBER | STRING | OBJECT | ARRAY => {
+            Ok(kind2typ(node, vars, *scope, node.text, code))
+        }
+        // the receiver of a method call; `this` of another function is not known
+        THIS => Ok(path_typ(vars, *scope, THIS).unwrap_or(JSTyp::Unknown)),
+        // a new object of the heap, whose properties the constructor sets
+        NEW_EXPR => {
+            let throws = run_args(
+                scope,
+                vars,
+                reporter,
+                summaries,
+                node,
+                code,
+                |_, _, _, _| Ok(false),
+            )?;
+            Ok(if throws { JSTyp::Never } else { JSTyp::Object })
+        }
+        CALL_EXPR => {
+            if run_callee(scope, vars, reporter, node, code)? {
+                return Ok(JSTyp::Never);
             }
-            STRING => {
-                typ = JSTyp::String;
+            if let Some(typ) = callee_summary(summaries, node, code) {
+                let throws = run_args(
+                    scope,
+                    vars,
+                    reporter,
+                    summaries,
+                    node,
+                    code,
+                    |_, _, _, _| Ok(false),
+                )?;
+                return Ok(if throws { JSTyp::Never } else { typ });
             }
-            CALL_EXPR if is_symbol_call(child, code) => {
-                typ = JSTyp::Symbol;
+            if let Some(typ) = run_stub_call(scope, vars, reporter, summaries, node, code)? {
+                return Ok(typ);
             }
-            OBJECT => {
-                typ = JSTyp::Object;
+            match run_builtin_call(scope, vars, reporter, summaries, node, code)? {
+                Some(typ) => Ok(typ),
+                None => {
+                    reporter.report_unsupported(node, code)?;
+                    Ok(JSTyp::Unknown)
+                }
             }
-            BINARY_EXPR => {
-                typ = run_binary_expr(scope, vars, child, code, &None);
+        }
+        MEMBER_EXPR => run_member_expr(scope, vars, reporter, summaries, node, code),
+        _ => {
+            reporter.report_unsupported(node, code)?;
+            Ok(JSTyp::Unknown)
+        }
+    }
+}
+
+/// Return type of the function called by `node` if it is called by name or as a method of an
+/// object literal
+pub fn callee_summary<'a>(summaries: &Summaries, node: &Node<'a>, code: &'a str) -> Option<JSTyp> {
+    assert_eq!(node.kind(), CALL_EXPR);
+    match node::get_field(node, "function", code) {
+        Some(func) if func.kind() == IDENT => summaries.returns.get(func.text).cloned(),
+        Some(func) if func.kind() == MEMBER_EXPR => {
+            let (name, _) = callgraph::method(summaries, &func, code)?;
+            summaries.returns.get(name).cloned()
+        }
+        _ => None,
+    }
+}
+
+/// Variables and properties `node` may refer to, e.g., `o_1_1` for `o` and `@12.w` for `o.w` if
+/// `o` points to the object allocated at `@12`; `None` if it is computed, read through an optional
+/// chain or of no object of the heap
+fn locations<'a>(
+    vars: &VarMap,
+    scope: usize,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Option<Vec<String>> {
+    match node.kind() {
+        IDENT => Some(vec![node.text.to_string()]),
+        THIS => Some(vec![THIS.to_string()]),
+        MEMBER_EXPR if node::get_field(node, "optional_chain", code).is_none() => {
+            let object = node::get_field(node, "object", code)?;
+            let property = node::get_field(node, "property", code)?;
+            if property.kind() != PROPERTY_IDENT {
+                return None;
             }
-            _ => {}
+            let locs = locations(vars, scope, &object, code)?;
+            let props = sites(vars, scope, &locs)
+                .iter()
+                .map(|site| prop_var(site, property.text))
+                .collect::<Vec<_>>();
+            (!props.is_empty()).then_some(props)
         }
-        None
-    });
-    let parent_id = node::get_parent_id(node::get_annot(node, code));
-    insert_var(vars, *scope, ident.unwrap(), typ, parent_id);
+        _ => None,
+    }
 }
 
-fn run_assignment_stmt<'a>(
-    scope: &mut usize,
+/// Allocation sites of the objects the variables or properties `locs` may point to
+fn sites(vars: &VarMap, scope: usize, locs: &[String]) -> Vec<String> {
+    let mut sites = vec![];
+    for loc in locs {
+        let prefix = points_to_var(loc, "");
+        sites.extend(
+            vars.keys()
+                .filter(|(scope_, _)| *scope_ == scope)
+                .filter_map(|(_, var)| var.strip_prefix(&prefix))
+                .map(str::to_string),
+        );
+    }
+    sites.sort();
+    sites.dedup();
+    sites
+}
+
+/// Makes the variable or property `loc` point to the objects of `sites` after the statement of
+/// `parent_id`. The objects it points to on other paths are kept unless `strong`.
+fn point(
+    vars: &mut VarMap,
+    scope: usize,
+    loc: &str,
+    sites: &[String],
+    parent_id: usize,
+    strong: bool,
+) {
+    let prefix = points_to_var(loc, "");
+    let stale = vars
+        .iter()
+        .filter(|((scope_, var), _)| *scope_ == scope && var.starts_with(&prefix))
+        .filter(|(_, pids)| strong || pids.iter().all(|(pid, _)| *pid == parent_id))
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    for key in stale {
+        vars.remove(&key);
+    }
+    for site in sites {
+        insert_var(
+            vars,
+            scope,
+            &points_to_var(loc, site),
+            JSTyp::Object,
+            parent_id,
+        );
+    }
+}
+
+/// Forgets that every property of the objects the variables or properties `locs` point to is
+/// known, since a function they are passed to may add some
+fn escape(vars: &mut VarMap, scope: usize, locs: &[String]) {
+    for site in sites(vars, scope, locs) {
+        vars.remove(&(scope, site));
+    }
+}
+
+/// Properties the member expression `node` assigns, e.g., `@12.w` of `o.w = ...` if `o` points
+/// to the object allocated at `@12`. A variable pointing to no object, e.g., a parameter, gets an
+/// object of its own, whose other properties are not known.
+fn target_props<'a>(
     vars: &mut VarMap,
+    scope: usize,
     node: &Node<'a>,
     code: &'a str,
-) -> (&'a str, JSTyp, usize) {
-    assert_eq!(node.kind(), ASSIGNMENT_STMT);
-    let mut lhs = "";
-    let mut typ = JSTyp::Undefined;
-    let mut eq_before = true;
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            IDENT if eq_before == true => {
-                lhs = child.text;
-            }
-            EQ => {
-                eq_before = false;
+) -> Option<Vec<String>> {
+    assert_eq!(node.kind(), MEMBER_EXPR);
+    if let Some(props) = locations(vars, scope, node, code) {
+        return Some(props);
+    }
+    let object = node::get_field(node, "object", code)?;
+    let property = node::get_field(node, "property", code)?;
+    if !matches!(object.kind(), IDENT | THIS) || property.kind() != PROPERTY_IDENT {
+        return None;
+    }
+    let loc = locations(vars, scope, &object, code)?.pop()?;
+    point(vars, scope, &loc, &[own_site(&loc)], 0, false);
+    locations(vars, scope, node, code)
+}
+
+/// Every type the variable or property `path` holds; `None` if it is not tracked
+fn path_typs(vars: &VarMap, scope: usize, path: &str) -> Option<Vec<JSTyp>> {
+    let mut typs = vars
+        .get(&(scope, path.to_string()))?
+        .iter()
+        .map(|(_, typ)| typ.clone())
+        .collect::<Vec<_>>();
+    typs.sort();
+    typs.dedup();
+    Some(typs)
+}
+
+/// Type of the variable or property `path`, `Unknown` if it holds several types
+fn path_typ(vars: &VarMap, scope: usize, path: &str) -> Option<JSTyp> {
+    match path_typs(vars, scope, path)?.as_slice() {
+        [typ] => Some(typ.clone()),
+        _ => Some(JSTyp::Unknown),
+    }
+}
+
+/// Every type the variables or properties `locs` hold; `None` if none is tracked. A property
+/// an object lacks is `undefined` if every property of the object is known and `Unknown`
+/// otherwise.
+fn locations_typs(vars: &VarMap, scope: usize, locs: &[String]) -> Option<Vec<JSTyp>> {
+    let mut typs = vec![];
+    let mut tracked = false;
+    for loc in locs {
+        if let Some(loc_typs) = path_typs(vars, scope, loc) {
+            tracked = true;
+            typs.extend(loc_typs);
+            continue;
+        }
+        let complete = loc
+            .rsplit_once('.')
+            .is_some_and(|(site, _)| vars.contains_key(&(scope, site.to_string())));
+        tracked |= complete;
+        typs.push(if complete {
+            JSTyp::Undefined
+        } else {
+            JSTyp::Unknown
+        });
+    }
+    typs.sort();
+    typs.dedup();
+    tracked.then_some(typs)
+}
+
+/// Type of the variables or properties `locs`, `Unknown` if they hold several types
+fn locations_typ(vars: &VarMap, scope: usize, locs: &[String]) -> Option<JSTyp> {
+    match locations_typs(vars, scope, locs)?.as_slice() {
+        [typ] => Some(typ.clone()),
+        _ => Some(JSTyp::Unknown),
+    }
+}
+
+/// The variable named `name` in the original source, e.g., of `{ w }`, whose shorthand property
+/// is not debloated
+fn orig_var(vars: &VarMap, scope: usize, name: &str) -> Option<String> {
+    let mut matching = vars
+        .keys()
+        .filter(|(scope_, var)| {
+            *scope_ == scope && !is_heap_var(var) && debloat::orig_ident(var) == name
+        })
+        .map(|(_, var)| var.clone());
+    match (matching.next(), matching.next()) {
+        (Some(var), None) => Some(var),
+        _ => None,
+    }
+}
+
+/// Allocation sites of the objects `value` evaluates to: a new object for an object or array
+/// literal or a `new` expression, and the objects a variable or property points to, so that,
+/// e.g., `p` of `let p = o` is an alias of `o`
+fn run_sites<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    value: &Node<'a>,
+    code: &'a str,
+) -> Result<Vec<String>> {
+    match value.kind() {
+        OBJECT => Ok(vec![run_object(
+            scope, vars, reporter, summaries, value, code,
+        )?]),
+        ARRAY | NEW_EXPR => Ok(vec![alloc_site(value)]),
+        PARENTHESIZED_EXPR => match value.info.named_child(0) {
+            Some(expr) => {
+                let expr = Node {
+                    info: expr,
+                    text: &code[expr.byte_range()],
+                };
+                run_sites(scope, vars, reporter, summaries, &expr, code)
             }
-            TRUE | FALSE => {
-                typ = JSTyp::Bool;
+            None => Ok(vec![]),
+        },
+        _ => Ok(locations(vars, *scope, value, code)
+            .map(|locs| sites(vars, *scope, &locs))
+            .unwrap_or_default()),
+    }
+}
+
+/// Allocates the object of the literal `node` and returns its site. Its properties have the types
+/// of their values, nested literals being objects of their own.
+fn run_object<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<String> {
+    assert_eq!(node.kind(), OBJECT);
+    let site = alloc_site(node);
+    // whether every property of the object is known
+    let mut complete = true;
+    let mut cursor = node.info.walk();
+    let members = node
+        .info
+        .named_children(&mut cursor)
+        .map(|member| Node {
+            info: member,
+            text: &code[member.byte_range()],
+        })
+        .collect::<Vec<_>>();
+    for member in members {
+        match member.kind() {
+            PAIR => {
+                let (Some(key), Some(value)) = (
+                    node::get_field(&member, "key", code),
+                    node::get_field(&member, "value", code),
+                ) else {
+                    continue;
+                };
+                let name = match key.kind() {
+                    PROPERTY_IDENT | NUMBER => key.text,
+                    STRING => key.text.trim_matches(|c| c == '"' || c == '\''),
+                    _ => {
+                        // e.g., `[key]: value`
+                        complete = false;
+                        continue;
+                    }
+                };
+                let prop = prop_var(&site, name);
+                // functions are not typed
+                let typ = if matches!(value.kind(), ARROW_FUNC | FUNC_EXPR) {
+                    JSTyp::Unknown
+                } else {
+                    run_expr(scope, vars, reporter, summaries, &value, code)?
+                };
+                let sites = run_sites(scope, vars, reporter, summaries, &value, code)?;
+                overwrite_var(vars, *scope, &prop, typ, 0);
+                point(vars, *scope, &prop, &sites, 0, true);
             }
-            NULL => {
-                typ = JSTyp::Null;
+            SHORTHAND_PROPERTY_IDENT => {
+                let prop = prop_var(&site, member.text);
+                // a function of the module, e.g., `{ area }`, is not typed
+                let var = orig_var(vars, *scope, member.text)
+                    .filter(|_| !summaries.returns.contains_key(member.text));
+                let typ = var
+                    .as_ref()
+                    .and_then(|var| path_typ(vars, *scope, var))
+                    .unwrap_or(JSTyp::Unknown);
+                let sites = sites(vars, *scope, &Vec::from_iter(var));
+                overwrite_var(vars, *scope, &prop, typ, 0);
+                point(vars, *scope, &prop, &sites, 0, true);
             }
-            UNDEFINED => {
-                typ = JSTyp::Undefined;
+            METHOD_DEF => match node::get_field(&member, "name", code) {
+                Some(name) if name.kind() == PROPERTY_IDENT => {
+                    overwrite_var(vars, *scope, &prop_var(&site, name.text), JSTyp::Unknown, 0);
+                }
+                _ => complete = false,
+            },
+            COMMENT => {}
+            // e.g., `...other`
+            _ => complete = false,
+        }
+    }
+    if complete {
+        overwrite_var(vars, *scope, &site, JSTyp::Object, 0);
+    }
+    Ok(site)
+}
+
+/// Type of a property read, e.g., `o.w`, `this.w`, `Math.PI` or `s.length`. Reading a property
+/// of `null` or `undefined` is reported and throws, unless through an optional chain.
+fn run_member_expr<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<JSTyp> {
+    assert_eq!(node.kind(), MEMBER_EXPR);
+    if let Some((_, decl)) = stub_decl(vars, summaries, node, code) {
+        return Ok(decl.typ());
+    }
+    if short_circuits(vars, *scope, node, code) {
+        return Ok(JSTyp::Undefined);
+    }
+    let (Some(object), Some(property)) = (
+        node::get_field(node, "object", code),
+        node::get_field(node, "property", code),
+    ) else {
+        reporter.report_unsupported(node, code)?;
+        return Ok(JSTyp::Unknown);
+    };
+    if let Some(namespace) = builtin_namespace(vars, &object) {
+        if let Some(typ) = builtins::static_property(namespace, property.text) {
+            return Ok(typ);
+        }
+        reporter.report_unsupported(node, code)?;
+        return Ok(JSTyp::Unknown);
+    }
+    let typ = run_expr(scope, vars, reporter, summaries, &object, code)?;
+    if typ.is_never() {
+        return Ok(JSTyp::Never);
+    }
+    let typs = locations(vars, *scope, &object, code)
+        .and_then(|locs| locations_typs(vars, *scope, &locs))
+        .unwrap_or_else(|| vec![typ.clone()]);
+    // `o?.w` is `undefined` if `o` is
+    let optional = node::get_field(node, "optional_chain", code).is_some();
+    if optional && typs.iter().all(JSTyp::is_nullish) {
+        return Ok(JSTyp::Undefined);
+    }
+    if report_nullish(reporter, node, &object, &property, &typs, code)? {
+        return Ok(JSTyp::Never);
+    }
+    if let Some(typ) =
+        locations(vars, *scope, node, code).and_then(|locs| locations_typ(vars, *scope, &locs))
+    {
+        return Ok(typ);
+    }
+    match builtins::property(&typ, property.text) {
+        Some(typ) => Ok(typ),
+        None => {
+            reporter.report_unsupported(node, code)?;
+            Ok(JSTyp::Unknown)
+        }
+    }
+}
+
+/// Whether the optional chain `node` belongs to short-circuits at a `?.` whose object is always
+/// `null` or `undefined`, e.g., `o?.x.y`, `o?.x.f()` and `o?.x[k]` if `o` is `null`; the chain
+/// then evaluates to `undefined`. Parentheses end a chain, so `(o?.x).y` throws.
+fn short_circuits<'a>(vars: &VarMap, scope: usize, node: &Node<'a>, code: &'a str) -> bool {
+    let inner = match node.kind() {
+        MEMBER_EXPR | SUBSCRIPT_EXPR => node::get_field(node, "object", code),
+        CALL_EXPR => node::get_field(node, "function", code),
+        _ => None,
+    };
+    let Some(inner) = inner else {
+        return false;
+    };
+    let nullish = || match inner.kind() {
+        NULL | UNDEFINED => true,
+        _ => locations(vars, scope, &inner, code)
+            .and_then(|locs| locations_typs(vars, scope, &locs))
+            .is_some_and(|typs| !typs.is_empty() && typs.iter().all(JSTyp::is_nullish)),
+    };
+    let optional = node::get_field(node, "optional_chain", code).is_some();
+    (optional && nullish()) || short_circuits(vars, scope, &inner, code)
+}
+
+/// Checks the callee of the call `node` and the receiver of a method call, e.g., `o` of `o.f()`,
+/// if their types are tracked. Returns whether the call throws because either is `null` or
+/// `undefined`. The callee may add properties to the objects of its receiver and arguments.
+fn run_callee<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<bool> {
+    assert_eq!(node.kind(), CALL_EXPR);
+    let mut reached = vec![];
+    if let Some(arguments) = node::get_field(node, "arguments", code).filter(|a| a.kind() == ARGS) {
+        let mut cursor = arguments.info.walk();
+        for arg in arguments.info.named_children(&mut cursor) {
+            let arg = Node {
+                info: arg,
+                text: &code[arg.byte_range()],
+            };
+            reached.extend(locations(vars, *scope, &arg, code).unwrap_or_default());
+        }
+    }
+    let Some(func) = node::get_field(node, "function", code) else {
+        escape(vars, *scope, &reached);
+        return Ok(false);
+    };
+    let receiver = (func.kind() == MEMBER_EXPR)
+        .then(|| {
+            let object = node::get_field(&func, "object", code)?;
+            let property = node::get_field(&func, "property", code)?;
+            Some((object, property))
+        })
+        .flatten();
+    if let Some((object, property)) = receiver {
+        let locs = locations(vars, *scope, &object, code);
+        let typs = locs
+            .as_ref()
+            .and_then(|locs| locations_typs(vars, *scope, locs));
+        reached.extend(locs.unwrap_or_default());
+        if let Some(typs) = typs {
+            if report_nullish(reporter, &func, &object, &property, &typs, code)? {
+                escape(vars, *scope, &reached);
+                return Ok(true);
             }
-            NUMBER => {
-                typ = number2typ(child);
+        }
+    }
+    escape(vars, *scope, &reached);
+    if node::get_field(node, "optional_chain", code).is_some() {
+        return Ok(false);
+    }
+    // e.g., a callback parameter called without an argument. A property the object lacks may be
+    // inherited, e.g., `toString`, and is not checked.
+    let callee = match func.kind() {
+        // a callee is not renamed by debloating
+        IDENT => orig_var(vars, *scope, func.text).map(|var| vec![var]),
+        MEMBER_EXPR => locations(vars, *scope, &func, code),
+        _ => None,
+    };
+    let Some(callee) = callee else {
+        return Ok(false);
+    };
+    let Some(typs) = callee
+        .iter()
+        .map(|loc| path_typs(vars, *scope, loc))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Ok(false);
+    };
+    let mut typs = typs.concat();
+    typs.sort();
+    typs.dedup();
+    let var = match callee.as_slice() {
+        [var] => var.as_str(),
+        _ => func.text,
+    };
+    report_dereference(reporter, node, &func, var, &typs, Dereference::Call, code)
+}
+
+/// Reports reading `property` of `object` in the member expression `node` if `object` may be
+/// `null` or `undefined` despite the guards around it, which an optional chain allows. Returns
+/// whether it is always, so that the read throws.
+fn report_nullish<'a>(
+    reporter: &mut Reporter,
+    node: &Node<'a>,
+    object: &Node<'a>,
+    property: &Node<'a>,
+    typs: &[JSTyp],
+    code: &'a str,
+) -> Result<bool> {
+    if node::get_field(node, "optional_chain", code).is_some()
+        || !typs.iter().any(JSTyp::is_nullish)
+    {
+        return Ok(false);
+    }
+    let typs = reporter.narrow(object, code, typs)?;
+    if !typs.iter().any(JSTyp::is_nullish) {
+        return Ok(false);
+    }
+    reporter.report_nullish_property(node, object, code, property.text, &typs)?;
+    Ok(typs.iter().all(JSTyp::is_nullish))
+}
+
+/// Reports `dereference` of `value`, which reads the variable `var`, in `node` if its types
+/// `typs` may be `null` or `undefined` despite the guards around it. Returns whether it is always,
+/// so that `node` throws.
+fn report_dereference<'a>(
+    reporter: &mut Reporter,
+    node: &Node<'a>,
+    value: &Node<'a>,
+    var: &str,
+    typs: &[JSTyp],
+    dereference: Dereference,
+    code: &'a str,
+) -> Result<bool> {
+    if !typs.iter().any(JSTyp::is_nullish) {
+        return Ok(false);
+    }
+    let typs = reporter.narrow(value, code, typs)?;
+    if !typs.iter().any(JSTyp::is_nullish) {
+        return Ok(false);
+    }
+    reporter.report_nullish_dereference(node, value, var, code, dereference, &typs)?;
+    Ok(typs.iter().all(JSTyp::is_nullish))
+}
+
+pub fn is_symbol_call<'a>(node: &Node<'a>, code: &'a str) -> bool {
+    assert_eq!(node.kind(), CALL_EXPR);
+    match node::get_field(node, "function", code) {
+        Some(func) => func.kind() == IDENT && func.text == "Symbol",
+        None => false,
+    }
+}
+
+/// Type of a call of a builtin function or method, e.g., `Math.max(a, 1)` or `s.slice(1)`, whose
+/// arguments are checked against its signature. `None` if the callee is not a builtin.
+fn run_builtin_call<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<Option<JSTyp>> {
+    assert_eq!(node.kind(), CALL_EXPR);
+    let Some(func) = node::get_field(node, "function", code) else {
+        return Ok(None);
+    };
+    let (name, builtin) = match func.kind() {
+        IDENT if !is_shadowed(vars, func.text) => match builtins::global(func.text) {
+            Some(builtin) => (func.text.to_string(), builtin),
+            None => return Ok(None),
+        },
+        MEMBER_EXPR => {
+            let (Some(object), Some(property)) = (
+                node::get_field(&func, "object", code),
+                node::get_field(&func, "property", code),
+            ) else {
+                return Ok(None);
+            };
+            if let Some(namespace) = builtin_namespace(vars, &object) {
+                match builtins::static_method(namespace, property.text) {
+                    Some(builtin) => (format!("{}.{}", namespace, property.text), builtin),
+                    None => return Ok(None),
+                }
+            } else if builtins::is_method(property.text) {
+                let typ = run_expr(scope, vars, reporter, summaries, &object, code)?;
+                match builtins::method(&typ, property.text) {
+                    Some(builtin) => (format!("{:?}.prototype.{}", typ, property.text), builtin),
+                    None => return Ok(None),
+                }
+            } else {
+                return Ok(None);
             }
-            STRING => {
-                typ = JSTyp::String;
+        }
+        _ => return Ok(None),
+    };
+
+    let throws = run_args(
+        scope,
+        vars,
+        reporter,
+        summaries,
+        node,
+        code,
+        |reporter, idx, arg, typ| {
+            let Some(param) = builtin.param(idx) else {
+                return Ok(false);
+            };
+            let violation = reporter.check_builtin_arg(node, arg, code, &name, param, typ)?;
+            Ok(violation == Some(Violation::Throws))
+        },
+    )?;
+    // a call that throws produces no value
+    Ok(Some(if throws {
+        JSTyp::Never
+    } else {
+        builtin.ret.clone()
+    }))
+}
+
+/// Type of a call of a function declared by a stub file, e.g., `_.chunk(a, 2)` of a package
+/// imported as `_`, whose arguments are checked against its declaration. `None` if the callee is
+/// not declared.
+fn run_stub_call<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<Option<JSTyp>> {
+    assert_eq!(node.kind(), CALL_EXPR);
+    let Some(func) = node::get_field(node, "function", code) else {
+        return Ok(None);
+    };
+    let Some((name, Decl::Function(signature))) = stub_decl(vars, summaries, &func, code) else {
+        return Ok(None);
+    };
+    let throws = run_args(
+        scope,
+        vars,
+        reporter,
+        summaries,
+        node,
+        code,
+        |reporter, idx, arg, typ| {
+            match signature.param(idx) {
+                Some(expected) if !expected.accepts(typ) => {
+                    reporter.report_stub_arg(node, arg, code, &name, expected, typ)?;
+                }
+                _ => {}
             }
-            CALL_EXPR if is_symbol_call(child, code) => {
-                typ = JSTyp::Symbol;
+            Ok(false)
+        },
+    )?;
+    Ok(Some(if throws {
+        JSTyp::Never
+    } else {
+        signature.ret.clone()
+    }))
+}
+
+/// Infers the arguments of the call `node` and checks each with `check`, given its position,
+/// unless it follows a spread argument. `check` returns whether the call throws on the argument.
+/// Returns whether the call throws, e.g., because an argument does not produce a value.
+fn run_args<'a>(
+    scope: &mut usize,
+    vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+    mut check: impl FnMut(&mut Reporter, usize, &Node<'a>, &JSTyp) -> Result<bool>,
+) -> Result<bool> {
+    let mut args = vec![];
+    if let Some(arguments) = node::get_field(node, "arguments", code).filter(|a| a.kind() == ARGS) {
+        let mut cursor = arguments.info.walk();
+        for arg in arguments.info.named_children(&mut cursor) {
+            if arg.kind() != COMMENT {
+                args.push(Node {
+                    info: arg,
+                    text: &code[arg.byte_range()],
+                });
             }
-            OBJECT => {
-                typ = JSTyp::Object;
+        }
+    }
+    let mut throws = false;
+    // a spread argument hides the position of the following ones
+    let mut in_order = true;
+    for (idx, arg) in args.iter().enumerate() {
+        // functions are not typed; a callback is called by the callee and not analyzed here
+        if matches!(arg.kind(), ARROW_FUNC | FUNC_EXPR) {
+            continue;
+        }
+        in_order &= arg.kind() != SPREAD_ELEMENT;
+        let typ = run_expr(scope, vars, reporter, summaries, arg, code)?;
+        throws |= typ.is_never();
+        if in_order {
+            throws |= check(reporter, idx, arg, &typ)?;
+        }
+    }
+    Ok(throws)
+}
+
+/// Declaration of the stubs `node` refers to, e.g., `_.chunk` of a package imported as `_` or a
+/// declared global, and its name in messages. A variable of the function shadows a declaration.
+fn stub_decl<'s, 'a>(
+    vars: &VarMap,
+    summaries: &'s Summaries,
+    node: &Node<'a>,
+    code: &'a str,
+) -> Option<(String, &'s Decl)> {
+    match node.kind() {
+        IDENT => {
+            // a callee is not renamed by debloating
+            let name = match node.info.parent().map(|parent| parent.kind()) {
+                Some(CALL_EXPR) => node.text,
+                _ => debloat::orig_ident(node.text),
+            };
+            if is_shadowed(vars, name) {
+                return None;
             }
-            BINARY_EXPR => {
-                typ = run_binary_expr(scope, vars, child, code, &None);
+            let decl = summaries.stubs.get(name)?;
+            Some((name.to_string(), decl))
+        }
+        MEMBER_EXPR => {
+            let object = node::get_field(node, "object", code)?;
+            let property = node::get_field(node, "property", code)?;
+            match stub_decl(vars, summaries, &object, code)? {
+                (name, Decl::Object(properties)) => {
+                    let decl = properties.get(property.text)?;
+                    Some((format!("{}.{}", name, property.text), decl))
+                }
+                _ => None,
             }
-            _ => {}
         }
-        Some(child.info.range())
-    });
-    let parent_id = node::get_parent_id(node::get_annot(node, code));
-    //insert_var(vars, *scope, lhs, typ.clone(), parent_id);
-    (lhs, typ, parent_id)
+        _ => None,
+    }
+}
+
+/// The global object `object` refers to, e.g., `Math`, unless a variable of the function shadows it
+fn builtin_namespace<'a>(vars: &VarMap, object: &Node<'a>) -> Option<&'a str> {
+    if object.kind() != IDENT {
+        return None;
+    }
+    // the debloated name of `Math` is, e.g., `Math_1_1`
+    let name = debloat::orig_ident(object.text);
+    (builtins::is_namespace(name) && !is_shadowed(vars, name)).then_some(name)
+}
+
+/// Whether a variable of the analyzed function is named `name` in the original source
+fn is_shadowed(vars: &VarMap, name: &str) -> bool {
+    vars.keys()
+        .any(|(_, var)| !is_heap_var(var) && debloat::orig_ident(var) == name)
+}
+
+/// Whether `node` calls a function by name or a method of a variable or a literal
+fn has_simple_callee<'a>(node: &Node<'a>, code: &'a str) -> bool {
+    match node::get_field(node, "function", code) {
+        Some(func) if func.kind() == IDENT => true,
+        Some(func) if func.kind() == MEMBER_EXPR => matches!(
+            node::get_field(&func, "object", code).map(|object| object.info.kind()),
+            Some(IDENT | STRING | NUMBER | ARRAY)
+        ),
+        _ => false,
+    }
 }
 
-pub fn is_symbol_call<'a>(node: &Node<'a>, code: &str) -> bool {
+/// Return type of the function declared by a stub file that `node` calls, like `builtin_call_typ`
+pub fn stub_call_typ<'a>(summaries: &Summaries, node: &Node<'a>, code: &'a str) -> Option<JSTyp> {
     assert_eq!(node.kind(), CALL_EXPR);
-    let children = node::get_nodes(node.info.walk(), Order::Pre, code);
-    if children[1].kind() == IDENT && children[1].text == "Symbol" {
-        true
-    } else {
-        false
+    let func = node::get_field(node, "function", code)?;
+    match stub_decl(&VarMap::new(), summaries, &func, code)? {
+        (_, Decl::Function(signature)) => Some(signature.ret.clone()),
+        _ => None,
     }
 }
 
+/// Type of a call of a builtin by name, without checking its arguments, e.g., of an argument
+/// whose callsite is gathered before the caller is analyzed
+pub fn builtin_call_typ<'a>(node: &Node<'a>, code: &'a str) -> Option<JSTyp> {
+    assert_eq!(node.kind(), CALL_EXPR);
+    let func = node::get_field(node, "function", code)?;
+    let builtin = match func.kind() {
+        IDENT => builtins::global(func.text)?,
+        MEMBER_EXPR => {
+            let object = node::get_field(&func, "object", code)?;
+            let property = node::get_field(&func, "property", code)?;
+            let namespace = debloat::orig_ident(object.text);
+            if object.kind() != IDENT || !builtins::is_namespace(namespace) {
+                return None;
+            }
+            builtins::static_method(namespace, property.text)?
+        }
+        _ => return None,
+    };
+    Some(builtin.ret.clone())
+}
+
+/// Type of a property of a global object, e.g., `Math.PI`, like `builtin_call_typ`
+pub fn builtin_property_typ<'a>(node: &Node<'a>, code: &'a str) -> Option<JSTyp> {
+    assert_eq!(node.kind(), MEMBER_EXPR);
+    let object = node::get_field(node, "object", code)?;
+    let property = node::get_field(node, "property", code)?;
+    if object.kind() != IDENT {
+        return None;
+    }
+    builtins::static_property(debloat::orig_ident(object.text), property.text)
+}
+
 fn run_binary_expr<'a>(
     scope: &mut usize,
     vars: &mut VarMap,
+    reporter: &mut Reporter,
+    summaries: &Summaries,
     node: &Node<'a>,
-    code: &str,
-    last_calc_typ: &Option<JSTyp>,
-) -> JSTyp {
+    code: &'a str,
+) -> Result<JSTyp> {
     assert_eq!(node.kind(), BINARY_EXPR);
-    let (mut lhs, mut rhs, mut op) = (None, None, None);
-    node::run_subtree(node, code, |child, last| {
-        match child.kind() {
-            BINARY_EXPR => {
-                lhs = Some(run_binary_expr(scope, vars, child, code, &lhs));
-            }
-            IDENT | TRUE | FALSE | NULL | UNDEFINED | NUMBER | STRING | OBJECT | CALL_EXPR => {
-                if node.kind() != CALL_EXPR || is_symbol_call(child, code) {
-                    if last_calc_typ.is_some() && lhs.is_none() {
-                        lhs = last_calc_typ.clone();
-                        rhs = Some(kind2typ(child, vars, *scope, child.text, code));
-                    } else if lhs.is_none() {
-                        lhs = Some(kind2typ(child, vars, *scope, child.text, code));
-                    } else {
-                        rhs = Some(kind2typ(child, vars, *scope, child.text, code));
-                    }
-                }
-            }
+    let (lhs, rhs, op) = match (
+        node::get_field(node, "left", code),
+        node::get_field(node, "right", code),
+        node::get_field(node, "operator", code),
+    ) {
+        (Some(lhs), Some(rhs), Some(op)) => (lhs, rhs, op),
+        _ => {
+            reporter.report_unsupported(node, code)?;
+            return Ok(JSTyp::Unknown);
+        }
+    };
+    let lhs = run_expr(scope, vars, reporter, summaries, &lhs, code)?;
+    let rhs = run_expr(scope, vars, reporter, summaries, &rhs, code)?;
+    let op = match op.kind() {
+        EQ => JSOp::Eq,
+        NEQ => JSOp::Neq,
+        SEQ => JSOp::Seq,
+        SNEQ => JSOp::Sneq,
+        GT => JSOp::Gt,
+        GE => JSOp::Ge,
+        LT => JSOp::Lt,
+        LE => JSOp::Le,
 
-            EQ => op = Some(JSOp::Eq),
-            NEQ => op = Some(JSOp::Neq),
-            SEQ => op = Some(JSOp::Seq),
-            SNEQ => op = Some(JSOp::Sneq),
-            GT => op = Some(JSOp::Gt),
-            GE => op = Some(JSOp::Ge),
-            LT => op = Some(JSOp::Lt),
-            LE => op = Some(JSOp::Le),
+        ADD => JSOp::Add,
+        SUB => JSOp::Sub,
+        MUL => JSOp::Mul,
+        DIV => JSOp::Div,
+        MOD => JSOp::Mod,
+        EXP => JSOp::Exp,
 
-            ADD => op = Some(JSOp::Add),
-            SUB => op = Some(JSOp::Sub),
-            MUL => op = Some(JSOp::Mul),
-            DIV => op = Some(JSOp::Div),
-            _ => {}
+        SHL => JSOp::Shl,
+        SHR => JSOp::Shr,
+        USHR => JSOp::UShr,
+        BIT_AND => JSOp::BitAnd,
+        BIT_OR => JSOp::BitOr,
+        BIT_XOR => JSOp::BitXor,
+
+        AND => JSOp::And,
+        OR => JSOp::Or,
+        NULLISH => JSOp::Nullish,
+
+        IN | INSTANCEOF => return Ok(JSTyp::Bool),
+        _ => {
+            reporter.report_unsupported(&op, code)?;
+            return Ok(JSTyp::Unknown);
         }
-        Some(child.info.range())
-    });
-    let (lhs, rhs, op) = (lhs.unwrap(), rhs.unwrap(), op.unwrap());
-    op.execute(&lhs, &rhs, node, code)
+    };
+    op.execute(reporter, &lhs, &rhs, node, code)
 }
 
 fn overwrite_typ<'a>(
     scope: usize,
     vars: &mut VarMap,
     node: &Node<'a>,
-    code: &str,
+    code: &'a str,
     var: &str,
     typ: JSTyp,
-) -> bool {
-    let next_sib = node.info.next_sibling().unwrap();
-    if next_sib.kind() == COMMENT && code[next_sib.byte_range()].contains(NON_BRANCH_ANNOT) {
-        println!("vars: {}", varmap_to_string(&vars));
-        let parent_id = node::get_parent_id(node::get_annot(node, code));
-        overwrite_var(vars, scope, var, typ, parent_id);
-        true
-    } else {
-        false
+) -> Result<bool> {
+    if !is_non_branch(node, code) {
+        return Ok(false);
+    }
+    let parent_id = node::get_parent_id(node::get_annot(node, code)?)?;
+    overwrite_var(vars, scope, var, typ, parent_id);
+    Ok(true)
+}
+
+/// Whether the statement `node` runs on every path of the function
+fn is_non_branch<'a>(node: &Node<'a>, code: &'a str) -> bool {
+    match node.info.next_sibling() {
+        Some(next_sib) => {
+            next_sib.kind() == COMMENT && code[next_sib.byte_range()].contains(NON_BRANCH_ANNOT)
+        }
+        None => false,
     }
 }
 
@@ -367,11 +1667,21 @@ fn stop<'a>(node: &Node<'a>) {
     loop {}
 }
 
-fn kind2typ<'a>(node: &Node<'a>, vars: &mut VarMap, scope: usize, text: &str, code: &str) -> JSTyp {
+fn kind2typ<'a>(
+    node: &Node<'a>,
+    vars: &mut VarMap,
+    scope: usize,
+    text: &str,
+    code: &'a str,
+) -> JSTyp {
     match node.kind() {
         IDENT => {
-            let typs = vars.get(&(scope, text.to_string())).unwrap();
-            if typs.len() > 1 {
+            // an identifier declared outside of the analyzed function is not tracked
+            let typs = match vars.get(&(scope, text.to_string())) {
+                Some(typs) => typs,
+                None => return JSTyp::Unknown,
+            };
+            if typs.len() != 1 {
                 return JSTyp::Unknown;
             } else {
                 //return typs.iter().next().cloned();
@@ -386,7 +1696,8 @@ fn kind2typ<'a>(node: &Node<'a>, vars: &mut VarMap, scope: usize, text: &str, co
         TRUE | FALSE => JSTyp::Bool,
         CALL_EXPR if is_symbol_call(node, code) => JSTyp::Symbol,
         OBJECT => JSTyp::Object,
-        _ => unimplemented!(),
+        ARRAY => JSTyp::Array,
+        _ => JSTyp::Unknown,
     }
 }
 
@@ -403,36 +1714,334 @@ pub fn number2typ<'a>(node: &Node<'a>) -> JSTyp {
 mod tests {
     use super::*;
     use crate::callgraph;
+    use crate::debloat;
+    use crate::diagnostic::{Diagnostic, ARITH_COERCION, BIGINT_MIX, SYMBOL_COERCION};
     use crate::report;
     use crate::util;
+    use tree_sitter_traversal::Order;
 
-    #[test]
-    fn testTypeViolation() {
-        let debloated_filename = "debloated.js";
+    /// Debloats `filename` in memory and infers the first function with the argument types of the
+    /// first callsite of `func`
+    fn infer_fixture(filename: &str, func: &str) -> (VarMap, Vec<Diagnostic>) {
+        infer_fixture_with(filename, func, &Summaries::default())
+    }
+
+    /// `infer_fixture` with what is known of the other functions `func` calls
+    fn infer_fixture_with(
+        filename: &str,
+        func: &str,
+        summaries: &Summaries,
+    ) -> (VarMap, Vec<Diagnostic>) {
+        let origin = util::read_file(filename).unwrap();
+        let origin_tree = node::get_tree(&origin).unwrap();
+        let origin_nodes = node::get_nodes(origin_tree.walk(), Order::Pre, &origin);
+        let code = debloat::debloat_control_flow(&origin_nodes, &origin, filename);
 
-        // 2. Run infer
-        let code = util::read_file(&debloated_filename).unwrap();
-        let tree = node::get_tree(&code);
+        let tree = node::get_tree(&code).unwrap();
         let mut nodes = node::get_nodes(tree.walk(), Order::Pre, &code);
-        let target_callsites = callgraph::gather_callsites("foo", &nodes[0], &code);
+        let target_callsites =
+            callgraph::gather_callsites(func, &Summaries::default(), &nodes[0], &code);
         nodes.remove(0);
         let mut vars = HashMap::new();
-        let param_typs = &target_callsites[0].1;
-        run_func(&mut vars, param_typs, &nodes, &nodes[0], &code);
+        let mut reporter = Reporter::default();
+        let (_, param_typs, this) = &target_callsites[0];
+        let callsite = reporter
+            .get_orig_span(&target_callsites[0].0, &code)
+            .unwrap();
+        let call = Call {
+            summaries,
+            this,
+            callsite: Some(&callsite),
+        };
+        run_func(
+            &mut vars,
+            &mut reporter,
+            &call,
+            param_typs,
+            &nodes,
+            &nodes[0],
+            &code,
+        )
+        .unwrap();
+        (vars, reporter.into_diagnostics())
+    }
+
+    /// `<severity>[<rule>] <message>` followed by the source line and location
+    fn render(diags: &[Diagnostic]) -> Vec<String> {
+        diags
+            .iter()
+            .map(|diag| {
+                let code = util::read_file(&diag.span.file).unwrap();
+                let line = code.split("\n").nth(diag.span.start_line - 1).unwrap();
+                format!(
+                    "{}[{}] {}\n{} ({})",
+                    diag.severity, diag.rule, diag.message, line, diag.span
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn testTypeViolation() {
+        let (_, diags) = infer_fixture("example/example.js", "foo");
 
         let expected_violations = vec![
-            "[Detected cmp violation] Undefined == Number 
-  if (a == 10) { (example/example.js:3:4)",
-            "[Detected arithmetic violation] Undefined + Number 
-    if (a+10 < 30) { (example/example.js:4:6)",
-            "[Detected arithmetic violation] Bool + Bool 
-      b = false + true; (example/example.js:6:4)",
+            "warning[cmp-mismatch]: Undefined == Number
+ --> example/example.js:3:7
+  |
+1 | function foo(a) {
+2 |   let b,c,d;
+3 |   if (a == 10) {
+  |       ~~~~~~~
+  |       ^ Undefined
+  |            ^^ Number
+4 |     if (a+10 < 30) {
+5 |       b = 200 + 100 === 10;
+  |
+  = note: `a` is Undefined because `foo` is called with `undefined` at example/example.js:19:11",
+            "warning[arith-coercion]: Undefined + Number
+ --> example/example.js:4:9
+  |
+2 |   let b,c,d;
+3 |   if (a == 10) {
+4 |     if (a+10 < 30) {
+  |         ~~~~
+  |         ^ Undefined
+  |           ^^ Number
+5 |       b = 200 + 100 === 10;
+6 |       b = false + true;
+  |
+  = note: `a` is Undefined because `foo` is called with `undefined` at example/example.js:19:11",
+            "warning[arith-coercion]: Bool + Bool
+ --> example/example.js:6:11
+  |
+4 |     if (a+10 < 30) {
+5 |       b = 200 + 100 === 10;
+6 |       b = false + true;
+  |           ~~~~~~~~~~~~
+  |           ^^^^^ Bool
+  |                   ^^^^ Bool
+7 |     } else {
+8 |       b = \"hello\";",
         ];
 
-        let violations = report::get_report_history();
+        let violations = diags
+            .iter()
+            .map(|diag| report::render_diagnostic(diag, false).unwrap())
+            .collect::<Vec<_>>();
         assert_eq!(violations.len(), expected_violations.len());
         for (violation, expected) in violations.iter().zip(expected_violations) {
             assert_eq!(violation, expected);
         }
     }
+
+    #[test]
+    fn test_compound_assignment() {
+        let (vars, diags) = infer_fixture("test/infer/compound_assign.js", "foo");
+
+        let expected_violations = vec![
+            "warning[arith-coercion] String + Number\n  s += 1; (test/infer/compound_assign.js:3:3)",
+            "warning[arith-coercion] Number - Bool\n  n -= true; (test/infer/compound_assign.js:6:3)",
+            "warning[arith-coercion] Number + String\n  o.w += \"px\"; (test/infer/compound_assign.js:9:3)",
+        ];
+        assert_eq!(render(&diags), expected_violations);
+
+        let typs_of = |var: &str| {
+            vars.get(&(0, var.to_string()))
+                .unwrap()
+                .iter()
+                .map(|(_, typ)| typ.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(typs_of("s_1_1"), vec![JSTyp::String]);
+        assert_eq!(typs_of("n_1_1"), vec![JSTyp::Number]);
+        // the property is updated in the heap
+        assert_eq!(typs_of("w_1_1"), vec![JSTyp::String]);
+    }
+
+    #[test]
+    fn test_runtime_type_error() {
+        let (vars, diags) = infer_fixture("test/infer/runtime_type_error.js", "foo");
+
+        // `m = n * 2` is not reported again since `n` never holds a value
+        let expected_violations = vec![
+            "error[bigint-mix] BigInt + Number\n  n = big + 1; (test/infer/runtime_type_error.js:4:7)",
+            "error[symbol-coercion] Symbol + String\n  t = s + \"x\"; (test/infer/runtime_type_error.js:10:7)",
+        ];
+        assert_eq!(render(&diags), expected_violations);
+        assert!(vars
+            .get(&(0, "m_1_1".to_string()))
+            .unwrap()
+            .iter()
+            .all(|(_, typ)| typ.is_never()));
+    }
+
+    #[test]
+    fn test_unsupported_construct() {
+        let (vars, diags) = infer_fixture("test/infer/unsupported.js", "foo");
+
+        let expected_notes = vec![
+            "note[unsupported-construct] unsupported construct `assignment_pattern`\nfunction foo(a, c = 1) { (test/infer/unsupported.js:1:17)",
+            "note[unsupported-construct] unsupported construct `object_pattern`\n  let {x} = a; (test/infer/unsupported.js:2:7)",
+            "note[unsupported-construct] unsupported construct `member_expression`\n  let n = a.length + 1; (test/infer/unsupported.js:3:11)",
+        ];
+        assert_eq!(render(&diags), expected_notes);
+        assert!(vars.contains_key(&(0, "n_1_1".to_string())));
+    }
+
+    #[test]
+    fn test_builtins() {
+        let (vars, diags) = infer_fixture("test/infer/builtins.js", "foo");
+
+        let expected_violations = vec![
+            "warning[builtin-coercion] `Math.max` expects a Number, found String\n  let d = Math.max(s, n); (test/infer/builtins.js:5:11)",
+            "error[builtin-type-error] `Object.keys` expects an Object, found Null\n  let e = Object.keys(null); (test/infer/builtins.js:6:11)",
+            "error[builtin-type-error] `Array.prototype.forEach` expects a function, found Number\n  parts.forEach(n); (test/infer/builtins.js:8:3)",
+        ];
+        assert_eq!(render(&diags), expected_violations);
+
+        let typs_of = |var: &str| {
+            vars.get(&(0, var.to_string()))
+                .unwrap()
+                .iter()
+                .map(|(_, typ)| typ.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(typs_of("a_1_1"), vec![JSTyp::Number]);
+        assert_eq!(typs_of("b_1_1"), vec![JSTyp::Number]);
+        assert_eq!(typs_of("c_1_1"), vec![JSTyp::String]);
+        assert_eq!(typs_of("d_1_1"), vec![JSTyp::Number]);
+        // the call throws
+        assert_eq!(typs_of("e_1_1"), vec![JSTyp::Never]);
+    }
+
+    #[test]
+    fn test_members() {
+        let (vars, diags) = infer_fixture("test/infer/members.js", "foo");
+
+        let expected_violations = vec![
+            "warning[arith-coercion] Number * String\n  let area = rect.w * rect.h; (test/infer/members.js:3:14)",
+            "error[nullish-property] cannot read `length` of Undefined\n  let n = none.length; (test/infer/members.js:8:11)",
+            "warning[maybe-nullish-property] reading `w` of Null | Object may throw\n  let w = maybe.w; (test/infer/members.js:13:11)",
+        ];
+        assert_eq!(render(&diags), expected_violations);
+
+        let typs_of = |var: &str| {
+            vars.get(&(0, var.to_string()))
+                .unwrap()
+                .iter()
+                .map(|(_, typ)| typ.clone())
+                .collect::<Vec<_>>()
+        };
+        // `rect.h` is a Number once assigned
+        assert_eq!(typs_of("x_1_1"), vec![JSTyp::Number]);
+        // `rect` lacks `name`
+        assert_eq!(typs_of("label_1_1"), vec![JSTyp::Undefined]);
+        assert_eq!(typs_of("n_1_1"), vec![JSTyp::Never]);
+        assert_eq!(typs_of("safe_1_1"), vec![JSTyp::Undefined]);
+    }
+
+    #[test]
+    fn test_heap() {
+        let (vars, diags) = infer_fixture("test/infer/heap.js", "foo");
+
+        // `a.w` and `b.w` are apart although `o` may point to both
+        let expected_violations = vec![
+            "warning[arith-coercion] Number * String\n  let x = a.w * b.w; (test/infer/heap.js:8:11)",
+        ];
+        assert_eq!(render(&diags), expected_violations);
+
+        let typs_of = |var: &str| {
+            vars.get(&(0, var.to_string()))
+                .unwrap()
+                .iter()
+                .map(|(_, typ)| typ.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(typs_of("q_1_1"), vec![JSTyp::Unknown]);
+        // assigned through `alias` and `d`
+        assert_eq!(typs_of("y_1_1"), vec![JSTyp::String]);
+        assert_eq!(typs_of("z_1_1"), vec![JSTyp::Number]);
+        assert_eq!(typs_of("m_1_1"), vec![JSTyp::Number]);
+    }
+
+    #[test]
+    fn test_dereference() {
+        let summaries = Summaries {
+            returns: HashMap::from([("g".to_string(), JSTyp::Number)]),
+            ..Summaries::default()
+        };
+        let (_, diags) = infer_fixture_with("test/infer/dereference.js", "foo", &summaries);
+
+        let expected_violations = vec![
+            "error[nullish-dereference] cannot destructure Undefined\nfunction foo(flag, cb, { id }) { (test/infer/dereference.js:1:24)",
+            "note[unsupported-construct] unsupported construct `object_pattern`\nfunction foo(flag, cb, { id }) { (test/infer/dereference.js:1:24)",
+            "warning[maybe-nullish-property] reading `w` of Null | Object may throw\n  let v = b.w; (test/infer/dereference.js:10:11)",
+            "warning[maybe-nullish-property] reading `items` of Null | Object may throw\n  for (const x of b.items) { (test/infer/dereference.js:11:19)",
+            "error[nullish-dereference] cannot iterate over Undefined\n  for (const x of none) { (test/infer/dereference.js:15:3)",
+            "error[nullish-dereference] cannot destructure Undefined\n  let { w } = none; (test/infer/dereference.js:18:7)",
+            "note[unsupported-construct] unsupported construct `object_pattern`\n  let { w } = none; (test/infer/dereference.js:18:7)",
+            "error[nullish-dereference] cannot call Undefined\n  cb(); (test/infer/dereference.js:19:3)",
+            "error[nullish-property] cannot read `bar` of Undefined\n  none2.bar; (test/infer/dereference.js:21:3)",
+            "error[nullish-property] cannot read `quux` of Undefined\n  g(none2.quux); (test/infer/dereference.js:22:5)",
+            "error[nullish-property] cannot read `b` of Undefined\n  if (none2.b) { (test/infer/dereference.js:23:7)",
+        ];
+        assert_eq!(render(&diags), expected_violations);
+        // `cb` is not passed by the callsite
+        let call = diags
+            .iter()
+            .find(|diag| diag.message == "cannot call Undefined")
+            .unwrap();
+        assert_eq!(call.notes.len(), 1);
+    }
+
+    #[test]
+    fn test_diagnostic_spans() {
+        let (_, diags) = infer_fixture("test/infer/runtime_type_error.js", "foo");
+        let rules = diags.iter().map(|diag| diag.rule).collect::<Vec<_>>();
+        assert_eq!(rules, vec![BIGINT_MIX, SYMBOL_COERCION]);
+        assert!(diags.iter().all(|diag| diag.rule != ARITH_COERCION));
+
+        // `big + 1` on line 4 and its operands
+        let diag = &diags[0];
+        assert_eq!(diag.operand_typs, vec![JSTyp::BigInt, JSTyp::Number]);
+        assert_eq!(
+            (
+                diag.span.start_line,
+                diag.span.start_column,
+                diag.span.end_column
+            ),
+            (4, 7, 14)
+        );
+        let labels = diag
+            .labels
+            .iter()
+            .map(|label| (label.span.start_column, label.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec![(7, "BigInt"), (13, "Number")]);
+    }
+
+    #[test]
+    fn test_undefined_origin() {
+        let (_, diags) = infer_fixture("test/infer/undefined_origin.js", "foo");
+
+        let notes = diags
+            .iter()
+            .map(|diag| {
+                diag.notes
+                    .iter()
+                    .map(|note| format!("{} at {}", note.message, note.span))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            vec![
+                // `d` holds the value of `c`
+                vec!["`c` is Undefined because it is declared without a value at test/infer/undefined_origin.js:2:7"],
+                vec!["`e` is Undefined because it is assigned `undefined` at test/infer/undefined_origin.js:4:11"],
+                vec!["`b` is Undefined because `foo` is called without it at test/infer/undefined_origin.js:10:1"],
+            ]
+        );
+    }
 }
@@ -1,6 +1,274 @@
-pub fn gen_assert(typ1: &str, typ2: &str) -> String {
-    format!(
-        "if (typeof({}) !== typeof({})) throw `type assertion failed ${{{}}} !== ${{{}}}`",
-        typ1, typ2, typ1, typ2
-    )
+use crate::analysis::Analysis;
+use crate::diagnostic::Span;
+use crate::jssyntax::{
+    JSTyp, ASSIGNMENT_STMT, AUGMENTED_ASSIGNMENT_STMT, COMMENT, EXPR_STMT, FUNC_DECL, IDENT,
+    LEXICAL_DECL, PROGRAM, STMT_BLK, STRING, SWITCH_CASE, VARIABLE_DECLARATION, VAR_DECL,
+};
+use crate::node::{self, Node};
+use crate::project::Module;
+use crate::report;
+use serde_json::json;
+use tree_sitter_traversal::Order;
+
+/// Runtime check of a value of one of `typs`, e.g., `typeof a === "number" || a === null`.
+/// `None` if any value passes: a type was not inferred, or no value was. `typs` has no `Never`.
+fn runtime_check(var: &str, typs: &[JSTyp]) -> Option<String> {
+    if typs.is_empty() || typs.contains(&JSTyp::Unknown) {
+        return None;
+    }
+    let checks = typs
+        .iter()
+        .map(|typ| match typ {
+            JSTyp::Unknown | JSTyp::Never => unreachable!(),
+            JSTyp::Null => format!("{} === null", var),
+            // `typeof null` is also "object"
+            JSTyp::Object => format!(
+                "(typeof {0} === \"object\" && {0} !== null && !Array.isArray({0}))",
+                var
+            ),
+            JSTyp::Array => format!("Array.isArray({})", var),
+            _ => format!("typeof {} === \"{}\"", var, typeof_name(typ)),
+        })
+        .collect::<Vec<_>>();
+    Some(checks.join(" || "))
+}
+
+/// Result of the `typeof` operator on a value of `typ`
+pub fn typeof_name(typ: &JSTyp) -> &'static str {
+    match typ {
+        JSTyp::Bool => "boolean",
+        JSTyp::Undefined => "undefined",
+        JSTyp::Number => "number",
+        JSTyp::BigInt => "bigint",
+        JSTyp::String => "string",
+        JSTyp::Symbol => "symbol",
+        _ => "object",
+    }
+}
+
+/// Statement throwing a `TypeError` if `var` does not hold a value of one of `typs`, the types
+/// inferred at `span`
+pub fn gen_assert(var: &str, typs: &[JSTyp], span: &Span) -> Option<String> {
+    // an operation that throws produces no value
+    let mut typs = typs
+        .iter()
+        .filter(|typ| !typ.is_never())
+        .cloned()
+        .collect::<Vec<_>>();
+    typs.sort();
+    typs.dedup();
+    let check = runtime_check(var, &typs)?;
+    let inferred = typs
+        .iter()
+        .map(|typ| format!("{:?}", typ))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    let message = json!(format!(
+        "type assertion failed at {}: `{}` is inferred as {}, found ",
+        span, var, inferred
+    ));
+    Some(format!(
+        "if (!({})) throw new TypeError({} + typeof {});",
+        check, message, var
+    ))
+}
+
+/// Source of a module with runtime type assertions inserted
+#[derive(Debug, Default, PartialEq)]
+pub struct Instrumented {
+    pub code: String,
+    /// number of assertions inserted
+    pub asserts: usize,
+}
+
+/// Inserts an assertion that the runtime type of a variable is one of its inferred types at the
+/// entry of every function for its parameters and after every declaration and assignment of a
+/// variable in a statement list. The assertions are inserted on the line of the statement, so the
+/// line numbers of the stack traces are those of the original source.
+pub fn instrument(analysis: &Analysis, module: &Module) -> Instrumented {
+    let code = module.origin.as_str();
+    let nodes = node::get_nodes(module.tree.walk(), Order::Pre, code);
+    let mut instrumented = Instrumented::default();
+    let mut inserts = vec![];
+    let mut check = |ident: tree_sitter::Node, byte: usize, prefix: &str| {
+        let span = report::byte_span(&module.filename, code, ident.start_byte(), ident.end_byte());
+        let typs = analysis
+            .typs
+            .occurrences
+            .iter()
+            .filter(|occurrence| {
+                occurrence.span.file == span.file
+                    && occurrence.span.start_byte == span.start_byte
+                    && occurrence.span.end_byte == span.end_byte
+            })
+            .map(|occurrence| occurrence.typ.clone())
+            .collect::<Vec<_>>();
+        let assert = gen_assert(&code[ident.byte_range()], &typs, &span);
+        if let Some(assert) = &assert {
+            inserts.push((byte, format!("{} {}", prefix, assert)));
+        }
+        assert.is_some()
+    };
+
+    for func in nodes.iter().filter(|node| node.kind() == FUNC_DECL) {
+        let (Some(params), Some(body)) = (
+            func.info.child_by_field_name("parameters"),
+            func.info.child_by_field_name("body"),
+        ) else {
+            continue;
+        };
+        let (entry, mut prefix) = prologue_end(body, code);
+        let mut cursor = params.walk();
+        for param in params.named_children(&mut cursor) {
+            if param.kind() == IDENT && check(param, entry, prefix) {
+                prefix = "";
+            }
+        }
+    }
+
+    for stmt in nodes.iter() {
+        // a single statement, e.g., the body of `if` without braces, is not followed by another
+        let in_list = matches!(
+            stmt.info.parent().map(|parent| parent.kind()),
+            Some(STMT_BLK | PROGRAM | SWITCH_CASE)
+        );
+        if !in_list {
+            continue;
+        }
+        let assigned = assigned_idents(stmt);
+        if assigned.is_empty() {
+            continue;
+        }
+        let end = stmt.info.end_byte();
+        // `let a = 1` ends at the end of the line
+        let mut prefix = if stmt.text.ends_with(';') { "" } else { ";" };
+        for ident in assigned {
+            if check(ident, end, prefix) {
+                prefix = "";
+            }
+        }
+    }
+    instrumented.asserts = inserts.len();
+
+    // the parameters are checked in order
+    inserts.sort_by_key(|(byte, _)| *byte);
+    let mut rewritten = code.to_string();
+    for (byte, text) in inserts.into_iter().rev() {
+        rewritten.insert_str(byte, &text);
+    }
+    instrumented.code = rewritten;
+    instrumented
+}
+
+/// Byte of the function body `body` after which a statement can be inserted, right after `{` or
+/// after its directive prologue, e.g., `"use strict";`, which a statement before it would turn
+/// into a plain expression. A directive without `;` needs one before the inserted statement.
+fn prologue_end(body: tree_sitter::Node, code: &str) -> (usize, &'static str) {
+    let mut end = (body.start_byte() + 1, "");
+    let mut cursor = body.walk();
+    for stmt in body.named_children(&mut cursor) {
+        if stmt.kind() == COMMENT {
+            continue;
+        }
+        let directive = stmt.kind() == EXPR_STMT
+            && stmt.named_child_count() == 1
+            && stmt
+                .named_child(0)
+                .is_some_and(|expr| expr.kind() == STRING);
+        if !directive {
+            break;
+        }
+        let prefix = if code[stmt.byte_range()].ends_with(';') {
+            ""
+        } else {
+            ";"
+        };
+        end = (stmt.end_byte(), prefix);
+    }
+    end
+}
+
+/// Variables declared or assigned by `stmt`, e.g., `a` and `b` of `let a = 1, b;` or `a` of
+/// `a += 1;`
+fn assigned_idents<'a>(stmt: &Node<'a>) -> Vec<tree_sitter::Node<'a>> {
+    let mut idents = vec![];
+    let mut cursor = stmt.info.walk();
+    match stmt.kind() {
+        LEXICAL_DECL | VARIABLE_DECLARATION => {
+            for declarator in stmt.info.named_children(&mut cursor) {
+                if declarator.kind() != VAR_DECL {
+                    continue;
+                }
+                if let Some(name) = declarator
+                    .child_by_field_name("name")
+                    .filter(|name| name.kind() == IDENT)
+                {
+                    idents.push(name);
+                }
+            }
+        }
+        EXPR_STMT => {
+            let lhs = stmt
+                .info
+                .named_child(0)
+                .filter(|expr| matches!(expr.kind(), ASSIGNMENT_STMT | AUGMENTED_ASSIGNMENT_STMT))
+                .and_then(|expr| expr.child_by_field_name("left"))
+                .filter(|lhs| lhs.kind() == IDENT);
+            idents.extend(lhs);
+        }
+        _ => {}
+    }
+    idents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::config::Config;
+    use crate::project::Project;
+    use crate::util;
+
+    #[test]
+    fn test_gen_assert() {
+        let span = report::byte_span("a.js", "let a = 1;", 4, 5);
+        assert_eq!(
+            gen_assert("a", &[JSTyp::Null, JSTyp::Never, JSTyp::String], &span).unwrap(),
+            "if (!(a === null || typeof a === \"string\")) throw new TypeError(\"type assertion \
+             failed at a.js:1:5: `a` is inferred as Null | String, found \" + typeof a);"
+        );
+        assert_eq!(
+            gen_assert("a", &[JSTyp::Number, JSTyp::Unknown], &span),
+            None
+        );
+        assert_eq!(gen_assert("a", &[JSTyp::Never], &span), None);
+    }
+
+    #[test]
+    fn test_instrument() {
+        let filename = "test/instrument/input.js";
+        let project = Project::load(&[filename.to_string()], 1).unwrap();
+        let analysis = analysis::analyze_project_typs(&project, &Config::default()).unwrap();
+        let instrumented = instrument(&analysis, project.module(filename).unwrap());
+        let expected = util::read_file("test/instrument/expected.js").unwrap();
+        assert_eq!(instrumented.code, expected);
+        assert_eq!(instrumented.asserts, 9);
+        // the line numbers are kept
+        assert_eq!(
+            instrumented.code.lines().count(),
+            project.modules[0].origin.lines().count()
+        );
+    }
+
+    #[test]
+    fn test_instrument_directives() {
+        // the parameters are checked after `"use strict"`, which stays a directive
+        let filename = "test/instrument/strict.js";
+        let project = Project::load(&[filename.to_string()], 1).unwrap();
+        let analysis = analysis::analyze_project_typs(&project, &Config::default()).unwrap();
+        let instrumented = instrument(&analysis, project.module(filename).unwrap());
+        let expected = util::read_file("test/instrument/strict_expected.js").unwrap();
+        assert_eq!(instrumented.code, expected);
+        assert_eq!(instrumented.asserts, 3);
+    }
 }
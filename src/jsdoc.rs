@@ -0,0 +1,276 @@
+use crate::analysis::Analysis;
+use crate::diagnostic::Span;
+use crate::jssyntax::{
+    JSTyp, COMMENT, EXPORT_STMT, FUNC_DECL, IDENT, LEXICAL_DECL, STMT_BLK, VARIABLE_DECLARATION,
+    VAR_DECL,
+};
+use crate::node::{self, Node};
+use crate::project::Module;
+use tree_sitter_traversal::Order;
+
+const JSDOC_START: &str = "/**";
+
+/// JSDoc type of a value of any of `typs`, e.g., `number|undefined`. An operation that throws
+/// produces no value, so `Never` is left out.
+pub fn jsdoc_typ(typs: &[JSTyp]) -> String {
+    let mut typs = typs
+        .iter()
+        .filter(|typ| !typ.is_never())
+        .cloned()
+        .collect::<Vec<_>>();
+    if typs.is_empty() {
+        return "never".to_string();
+    }
+    if typs.contains(&JSTyp::Unknown) {
+        return "*".to_string();
+    }
+    // nullable types read as `number|undefined`
+    typs.sort_by_key(|typ| (matches!(typ, JSTyp::Null | JSTyp::Undefined), typ.clone()));
+    typs.dedup();
+    typs.iter()
+        .map(|typ| match typ {
+            JSTyp::Unknown => "*",
+            JSTyp::Never => "never",
+            JSTyp::Bool => "boolean",
+            JSTyp::Null => "null",
+            JSTyp::Undefined => "undefined",
+            JSTyp::Number => "number",
+            JSTyp::BigInt => "bigint",
+            JSTyp::String => "string",
+            JSTyp::Symbol => "symbol",
+            JSTyp::Object => "object",
+            JSTyp::Array => "Array",
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Source of a module with JSDoc blocks inserted
+#[derive(Debug, Default, PartialEq)]
+pub struct Annotated {
+    pub code: String,
+    /// number of functions annotated with `@param` and `@returns`
+    pub funcs: usize,
+    /// number of declarations annotated with `@type`
+    pub decls: usize,
+}
+
+/// Inserts a JSDoc block with the inferred types of the parameters and of the return value above
+/// every function declaration of `module`, and a `@type` block above every declaration of a
+/// single variable in a function. The type of a variable is every type it holds in its
+/// function. Blocks are inserted as whole lines indented like the statement, so the rest of the
+/// source is kept as it is. A statement that already has a JSDoc block is left alone, so
+/// annotating twice changes nothing.
+pub fn annotate(analysis: &Analysis, module: &Module) -> Annotated {
+    let code = module.origin.as_str();
+    let nodes = node::get_nodes(module.tree.walk(), Order::Pre, code);
+    let mut annotated = Annotated::default();
+    let mut inserts = vec![];
+    for func in nodes.iter().filter(|node| node.kind() == FUNC_DECL) {
+        // `export function` is documented above `export`
+        let stmt = match func.info.parent() {
+            Some(parent) if parent.kind() == EXPORT_STMT => parent,
+            _ => func.info,
+        };
+        if has_jsdoc(stmt, code) {
+            continue;
+        }
+        let mut tags = vec![];
+        if let Some(params) = node::get_field(func, "parameters", code) {
+            let mut cursor = params.info.walk();
+            for param in params.info.named_children(&mut cursor) {
+                if param.kind() != IDENT {
+                    continue;
+                }
+                let typs = analysis
+                    .typs
+                    .occurrences
+                    .iter()
+                    .filter(|occurrence| is_node(&occurrence.span, &module.filename, param))
+                    .map(|occurrence| occurrence.typ.clone())
+                    .collect::<Vec<_>>();
+                tags.push(format!(
+                    "@param {{{}}} {}",
+                    jsdoc_typ(&typs),
+                    &code[param.byte_range()]
+                ));
+            }
+        }
+        if let Some(name) = node::get_field(func, "name", code) {
+            let typs = analysis
+                .typs
+                .returns
+                .iter()
+                .filter(|(span, _)| is_node(span, &module.filename, name.info))
+                .map(|(_, typ)| typ.clone())
+                .collect::<Vec<_>>();
+            if !typs.is_empty() {
+                tags.push(format!("@returns {{{}}}", jsdoc_typ(&typs)));
+            }
+        }
+        if tags.is_empty() {
+            continue;
+        }
+        let mut lines = vec![JSDOC_START.to_string()];
+        lines.extend(tags.iter().map(|tag| format!(" * {}", tag)));
+        lines.push(" */".to_string());
+        let inline = format!("{} {} */", JSDOC_START, tags.join(" "));
+        inserts.push(insertion(code, stmt.start_byte(), &lines, &inline));
+        annotated.funcs += 1;
+    }
+
+    for decl in nodes
+        .iter()
+        .filter(|node| matches!(node.kind(), LEXICAL_DECL | VARIABLE_DECLARATION))
+    {
+        // e.g., not the initializer of a `for` loop
+        let in_block = decl.info.parent().map(|parent| parent.kind()) == Some(STMT_BLK);
+        if !in_block || has_jsdoc(decl.info, code) {
+            continue;
+        }
+        let mut cursor = decl.info.walk();
+        let declarators = decl
+            .info
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() == VAR_DECL)
+            .collect::<Vec<_>>();
+        let name = match declarators.as_slice() {
+            [declarator] => declarator.child_by_field_name("name"),
+            // `@type` would apply to every variable
+            _ => None,
+        };
+        let Some(name) = name.filter(|name| name.kind() == IDENT) else {
+            continue;
+        };
+        let Some(var) = analysis
+            .typs
+            .occurrences
+            .iter()
+            .find(|occurrence| is_node(&occurrence.span, &module.filename, name))
+            .map(|occurrence| &occurrence.var)
+        else {
+            continue;
+        };
+        let func = ancestor_func(decl);
+        let typs = analysis
+            .typs
+            .occurrences
+            .iter()
+            .filter(|occurrence| {
+                occurrence.var == *var
+                    && occurrence.span.file == module.filename
+                    && func.as_ref().is_none_or(|func| {
+                        func.start_byte() <= occurrence.span.start_byte
+                            && occurrence.span.end_byte <= func.end_byte()
+                    })
+            })
+            .map(|occurrence| occurrence.typ.clone())
+            .collect::<Vec<_>>();
+        if typs.iter().all(|typ| typ.is_unknown()) {
+            continue;
+        }
+        let block = format!("{} @type {{{}}} */", JSDOC_START, jsdoc_typ(&typs));
+        inserts.push(insertion(
+            code,
+            decl.info.start_byte(),
+            std::slice::from_ref(&block),
+            &block,
+        ));
+        annotated.decls += 1;
+    }
+
+    inserts.sort_by_key(|(byte, _)| *byte);
+    let mut rewritten = code.to_string();
+    for (byte, text) in inserts.into_iter().rev() {
+        rewritten.insert_str(byte, &text);
+    }
+    annotated.code = rewritten;
+    annotated
+}
+
+fn is_node(span: &Span, filename: &str, node: tree_sitter::Node) -> bool {
+    span.file == filename
+        && span.start_byte == node.start_byte()
+        && span.end_byte == node.end_byte()
+}
+
+fn ancestor_func<'a>(node: &Node<'a>) -> Option<tree_sitter::Node<'a>> {
+    let mut parent = node.info.parent();
+    while let Some(node) = parent {
+        if node.kind() == FUNC_DECL {
+            return Some(node);
+        }
+        parent = node.parent();
+    }
+    None
+}
+
+/// Whether a JSDoc block comes right before `stmt`
+fn has_jsdoc(stmt: tree_sitter::Node, code: &str) -> bool {
+    match stmt.prev_sibling() {
+        Some(prev) if prev.kind() == COMMENT => {
+            code[prev.byte_range()].starts_with(JSDOC_START)
+                && code[prev.end_byte()..stmt.start_byte()].trim().is_empty()
+        }
+        _ => false,
+    }
+}
+
+/// Where to insert what to document the statement starting at `start`: `lines` above it, indented
+/// like it, or `inline` in front of it if it does not start its line
+fn insertion(code: &str, start: usize, lines: &[String], inline: &str) -> (usize, String) {
+    let line_start = code[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let indent = &code[line_start..start];
+    if !indent.trim().is_empty() {
+        return (start, format!("{} ", inline));
+    }
+    let newline = if code.contains("\r\n") { "\r\n" } else { "\n" };
+    let text = lines
+        .iter()
+        .map(|line| format!("{}{}{}", indent, line, newline))
+        .collect();
+    (line_start, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::config::Config;
+    use crate::project::Project;
+    use crate::util;
+
+    fn annotate_file(filename: &str, origin: String) -> Annotated {
+        let mut project = Project::default();
+        project
+            .modules
+            .push(Module::parse(filename, origin).unwrap());
+        let analysis = analysis::analyze_project_typs(&project, &Config::default()).unwrap();
+        annotate(&analysis, &project.modules[0])
+    }
+
+    #[test]
+    fn test_jsdoc_typ() {
+        assert_eq!(
+            jsdoc_typ(&[JSTyp::Undefined, JSTyp::Number, JSTyp::Undefined]),
+            "number|undefined"
+        );
+        assert_eq!(jsdoc_typ(&[JSTyp::String, JSTyp::Unknown]), "*");
+        assert_eq!(jsdoc_typ(&[JSTyp::Never, JSTyp::Bool]), "boolean");
+        assert_eq!(jsdoc_typ(&[]), "never");
+    }
+
+    #[test]
+    fn test_annotate() {
+        let filename = "test/jsdoc/input.js";
+        let annotated = annotate_file(filename, util::read_file(filename).unwrap());
+        let expected = util::read_file("test/jsdoc/expected.js").unwrap();
+        assert_eq!(annotated.code, expected);
+        assert_eq!((annotated.funcs, annotated.decls), (3, 3));
+
+        // the inserted blocks are not annotated again
+        let again = annotate_file(filename, annotated.code);
+        assert_eq!(again.code, expected);
+        assert_eq!((again.funcs, again.decls), (0, 0));
+    }
+}
@@ -1,7 +1,11 @@
+use crate::diagnostic::{ARITH_COERCION, BIGINT_MIX, BIGINT_USHR, CMP_MISMATCH, SYMBOL_COERCION};
+use crate::error::{Result, TypeinferError};
 use crate::node::Node;
-use crate::report;
+use crate::report::Reporter;
+use serde::{Deserialize, Serialize};
 
 use std::ops;
+use std::str::FromStr;
 
 pub const IF_STATEMENT: &str = "if_statement";
 pub const IF: &str = "if";
@@ -12,6 +16,11 @@ pub const CLOSE_BRACKET: &str = "}";
 pub const CLOSE_STATEMENT: &str = "}";
 pub const PROGRAM: &str = "program";
 pub const FUNC_DECL: &str = "function_declaration";
+pub const GENERATOR_FUNC_DECL: &str = "generator_function_declaration";
+pub const GENERATOR_FUNC: &str = "generator_function";
+pub const CLASS_DECL: &str = "class_declaration";
+pub const CLASS: &str = "class";
+pub const CATCH_CLAUSE: &str = "catch_clause";
 pub const SEMICOLON: &str = ";";
 pub const LEXICAL_DECL: &str = "lexical_declaration";
 pub const LET: &str = "let";
@@ -19,6 +28,7 @@ pub const VAR_DECL: &str = "variable_declarator";
 pub const IDENT: &str = "identifier";
 pub const BINARY_EXPR: &str = "binary_expression";
 pub const ASSIGNMENT_STMT: &str = "assignment_expression";
+pub const AUGMENTED_ASSIGNMENT_STMT: &str = "augmented_assignment_expression";
 pub const STMT_BLK: &str = "statement_block";
 pub const EXPR_STMT: &str = "expression_statement";
 pub const NUMBER: &str = "number";
@@ -27,6 +37,7 @@ pub const STRING_FRAGMENT: &str = "string_fragment";
 pub const RETURN_STMT: &str = "return_statement";
 pub const RETURN: &str = "return";
 pub const OBJECT: &str = "object";
+pub const ARRAY: &str = "array";
 pub const NULL: &str = "null";
 pub const COMMENT: &str = "comment";
 pub const FORMAL_PARAMS: &str = "formal_parameters";
@@ -46,6 +57,7 @@ pub const BREAK_STMT: &str = "break_statement";
 pub const BREAK: &str = "break";
 pub const FOR_STMT: &str = "for_statement";
 pub const FOR: &str = "for";
+pub const OF: &str = "of";
 pub const OPEN_PARENTHESIS: &str = "(";
 pub const CLOSE_PARENTHESIS: &str = ")";
 pub const EMPTY_STMT: &str = "empty_statement";
@@ -59,6 +71,39 @@ pub const FOR_IN_STMT: &str = "for_in_statement";
 pub const PARENTHESIZED_EXPR: &str = "parenthesized_expression";
 pub const DOUBLE_QUOTE: &str = "\"";
 pub const PAIR: &str = "pair";
+pub const TEMPLATE_STRING: &str = "template_string";
+pub const REGEX: &str = "regex";
+pub const IMPORT_STMT: &str = "import_statement";
+pub const IMPORT_CLAUSE: &str = "import_clause";
+pub const NAMED_IMPORTS: &str = "named_imports";
+pub const IMPORT_SPECIFIER: &str = "import_specifier";
+pub const NAMESPACE_IMPORT: &str = "namespace_import";
+pub const EXPORT_STMT: &str = "export_statement";
+pub const EXPORT_CLAUSE: &str = "export_clause";
+pub const EXPORT_SPECIFIER: &str = "export_specifier";
+pub const EXPORT: &str = "export";
+pub const DEFAULT: &str = "default";
+pub const OBJECT_PATTERN: &str = "object_pattern";
+pub const ARRAY_PATTERN: &str = "array_pattern";
+pub const SHORTHAND_PROPERTY_IDENT: &str = "shorthand_property_identifier";
+pub const SHORTHAND_PROPERTY_IDENT_PATTERN: &str = "shorthand_property_identifier_pattern";
+pub const PAIR_PATTERN: &str = "pair_pattern";
+pub const ASSIGNMENT_PATTERN: &str = "assignment_pattern";
+pub const OBJECT_ASSIGNMENT_PATTERN: &str = "object_assignment_pattern";
+pub const VARIABLE_DECLARATION: &str = "variable_declaration";
+pub const PROPERTY_IDENT: &str = "property_identifier";
+pub const ARROW_FUNC: &str = "arrow_function";
+pub const FUNC_EXPR: &str = "function_expression";
+pub const FUNC: &str = "function";
+pub const SPREAD_ELEMENT: &str = "spread_element";
+pub const THIS: &str = "this";
+pub const METHOD_DEF: &str = "method_definition";
+pub const NEW_EXPR: &str = "new_expression";
+pub const TERNARY_EXPR: &str = "ternary_expression";
+pub const UNARY_EXPR: &str = "unary_expression";
+pub const TYPEOF: &str = "typeof";
+pub const SUBSCRIPT_EXPR: &str = "subscript_expression";
+pub const THROW_STMT: &str = "throw_statement";
 
 pub const EQ: &str = "==";
 pub const NEQ: &str = "!=";
@@ -73,10 +118,42 @@ pub const ADD: &str = "+";
 pub const SUB: &str = "-";
 pub const MUL: &str = "*";
 pub const DIV: &str = "/";
+pub const MOD: &str = "%";
+pub const EXP: &str = "**";
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub const SHL: &str = "<<";
+pub const SHR: &str = ">>";
+pub const USHR: &str = ">>>";
+pub const BIT_AND: &str = "&";
+pub const BIT_OR: &str = "|";
+pub const BIT_XOR: &str = "^";
+
+pub const AND: &str = "&&";
+pub const OR: &str = "||";
+pub const NULLISH: &str = "??";
+pub const IN: &str = "in";
+pub const INSTANCEOF: &str = "instanceof";
+
+pub const ADD_ASSIGN: &str = "+=";
+pub const SUB_ASSIGN: &str = "-=";
+pub const MUL_ASSIGN: &str = "*=";
+pub const DIV_ASSIGN: &str = "/=";
+pub const MOD_ASSIGN: &str = "%=";
+pub const EXP_ASSIGN: &str = "**=";
+pub const SHL_ASSIGN: &str = "<<=";
+pub const SHR_ASSIGN: &str = ">>=";
+pub const USHR_ASSIGN: &str = ">>>=";
+pub const BIT_AND_ASSIGN: &str = "&=";
+pub const BIT_OR_ASSIGN: &str = "|=";
+pub const BIT_XOR_ASSIGN: &str = "^=";
+pub const AND_ASSIGN: &str = "&&=";
+pub const OR_ASSIGN: &str = "||=";
+pub const NULLISH_ASSIGN: &str = "??=";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum JSTyp {
     Unknown, // Top
+    Never,   // Bottom (the operation throws before producing a value)
     Bool,
     Null,
     Undefined,
@@ -85,19 +162,60 @@ pub enum JSTyp {
     String,
     Symbol,
     Object,
+    Array,
 }
 impl JSTyp {
     fn sub_mul_div(self, rhs: Self) -> Self {
         match (self, rhs) {
+            (Self::Never, _) | (_, Self::Never) => Self::Never,
             (Self::Unknown, _) | (_, Self::Unknown) => Self::Unknown,
             (Self::BigInt, Self::BigInt) => Self::BigInt,
-            (Self::Symbol, _) | (_, Self::Symbol) | (Self::BigInt, _) | (_, Self::BigInt) => {
-                unreachable!("Actual type error")
-            }
-            (Self::Object, _) | (_, Self::Object) => Self::String,
+            (Self::Symbol, _) | (_, Self::Symbol) => Self::Never,
+            (Self::BigInt, _) | (_, Self::BigInt) => Self::Never,
+            (Self::Object | Self::Array, _) | (_, Self::Object | Self::Array) => Self::String,
+            _ => Self::Number,
+        }
+    }
+    fn bitwise(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (Self::Never, _) | (_, Self::Never) => Self::Never,
+            (Self::Unknown, _) | (_, Self::Unknown) => Self::Unknown,
+            (Self::BigInt, Self::BigInt) => Self::BigInt,
+            (Self::Symbol, _) | (_, Self::Symbol) => Self::Never,
+            (Self::BigInt, _) | (_, Self::BigInt) => Self::Never,
+            _ => Self::Number,
+        }
+    }
+    fn unsigned_shift(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (Self::Never, _) | (_, Self::Never) => Self::Never,
+            (Self::Unknown, _) | (_, Self::Unknown) => Self::Unknown,
+            (Self::Symbol, _) | (_, Self::Symbol) => Self::Never,
+            (Self::BigInt, _) | (_, Self::BigInt) => Self::Never,
             _ => Self::Number,
         }
     }
+    /// `&&`, `||` and `??` evaluate to one of their operands
+    fn logical(self, rhs: Self, op: &JSOp) -> Self {
+        if self == Self::Unknown || self == Self::Never {
+            return self;
+        }
+        match (op, self.is_nullish()) {
+            (JSOp::And, true) | (JSOp::Nullish, false) => self,
+            (JSOp::Or | JSOp::Nullish, true) => rhs,
+            _ if self.is_same_typ(&rhs) => self,
+            _ => Self::Unknown,
+        }
+    }
+    pub fn is_unknown(&self) -> bool {
+        *self == Self::Unknown
+    }
+    pub fn is_never(&self) -> bool {
+        *self == Self::Never
+    }
+    pub fn is_nullish(&self) -> bool {
+        matches!(self, Self::Null | Self::Undefined)
+    }
     fn is_same_typ(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Bool, Self::Bool)
@@ -107,26 +225,46 @@ impl JSTyp {
             | (Self::BigInt, Self::BigInt)
             | (Self::String, Self::String)
             | (Self::Symbol, Self::Symbol)
-            | (Self::Object, Self::Object) => true,
+            | (Self::Object, Self::Object)
+            | (Self::Array, Self::Array) => true,
             _ => false,
         }
     }
 }
 
+/// Parses the `typeof` name of a type; `null` and `unknown` name the other two
+impl FromStr for JSTyp {
+    type Err = TypeinferError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "unknown" => Ok(Self::Unknown),
+            "boolean" => Ok(Self::Bool),
+            "null" => Ok(Self::Null),
+            "undefined" => Ok(Self::Undefined),
+            "number" => Ok(Self::Number),
+            "bigint" => Ok(Self::BigInt),
+            "string" => Ok(Self::String),
+            "symbol" => Ok(Self::Symbol),
+            "object" => Ok(Self::Object),
+            "array" => Ok(Self::Array),
+            _ => Err(TypeinferError::Config(format!("unknown type `{}`", s))),
+        }
+    }
+}
 impl ops::Add for JSTyp {
     type Output = Self;
     fn add(self, rhs: Self) -> Self {
         match (self, rhs) {
+            (Self::Never, _) | (_, Self::Never) => Self::Never,
             (Self::Unknown, _) | (_, Self::Unknown) => Self::Unknown,
+            (Self::Symbol, _) | (_, Self::Symbol) => Self::Never,
             (Self::BigInt, Self::BigInt) => Self::BigInt,
-            (Self::BigInt, Self::Object)
-            | (Self::Object, Self::BigInt)
+            (Self::BigInt, Self::Object | Self::Array)
+            | (Self::Object | Self::Array, Self::BigInt)
             | (Self::String, _)
             | (_, Self::String) => Self::String,
-            (Self::Symbol, _) | (_, Self::Symbol) | (Self::BigInt, _) | (_, Self::BigInt) => {
-                unreachable!("Actual type error")
-            }
-            (Self::Object, _) | (_, Self::Object) => Self::String,
+            (Self::BigInt, _) | (_, Self::BigInt) => Self::Never,
+            (Self::Object | Self::Array, _) | (_, Self::Object | Self::Array) => Self::String,
             _ => Self::Number,
         }
     }
@@ -149,6 +287,42 @@ impl ops::Div for JSTyp {
         self.sub_mul_div(rhs)
     }
 }
+impl ops::Rem for JSTyp {
+    type Output = Self;
+    fn rem(self, rhs: Self) -> Self {
+        self.sub_mul_div(rhs)
+    }
+}
+impl ops::Shl for JSTyp {
+    type Output = Self;
+    fn shl(self, rhs: Self) -> Self {
+        self.bitwise(rhs)
+    }
+}
+impl ops::Shr for JSTyp {
+    type Output = Self;
+    fn shr(self, rhs: Self) -> Self {
+        self.bitwise(rhs)
+    }
+}
+impl ops::BitAnd for JSTyp {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self {
+        self.bitwise(rhs)
+    }
+}
+impl ops::BitOr for JSTyp {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        self.bitwise(rhs)
+    }
+}
+impl ops::BitXor for JSTyp {
+    type Output = Self;
+    fn bitxor(self, rhs: Self) -> Self {
+        self.bitwise(rhs)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum JSOp {
@@ -167,6 +341,21 @@ pub enum JSOp {
     Sub,
     Mul,
     Div,
+    Mod,
+    Exp,
+
+    // Bitwise
+    Shl,
+    Shr,
+    UShr,
+    BitAnd,
+    BitOr,
+    BitXor,
+
+    // Logical
+    And,
+    Or,
+    Nullish,
 }
 impl JSOp {
     pub fn to_string(&self) -> String {
@@ -183,67 +372,142 @@ impl JSOp {
             Self::Sub => "-".to_string(),
             Self::Mul => "*".to_string(),
             Self::Div => "/".to_string(),
+            Self::Mod => "%".to_string(),
+            Self::Exp => "**".to_string(),
+            Self::Shl => "<<".to_string(),
+            Self::Shr => ">>".to_string(),
+            Self::UShr => ">>>".to_string(),
+            Self::BitAnd => "&".to_string(),
+            Self::BitOr => "|".to_string(),
+            Self::BitXor => "^".to_string(),
+            Self::And => "&&".to_string(),
+            Self::Or => "||".to_string(),
+            Self::Nullish => "??".to_string(),
         }
     }
-    pub fn execute<'a>(&self, a: &JSTyp, b: &JSTyp, node: &Node<'a>, code: &str) -> JSTyp {
+    pub fn execute<'a>(
+        &self,
+        reporter: &mut Reporter,
+        a: &JSTyp,
+        b: &JSTyp,
+        node: &Node<'a>,
+        code: &'a str,
+    ) -> Result<JSTyp> {
         match self {
             Self::Eq | Self::Neq | Self::Gt | Self::Ge | Self::Lt | Self::Le => {
+                if a.is_never() || b.is_never() {
+                    return Ok(JSTyp::Never);
+                }
+                if a.is_unknown() || b.is_unknown() {
+                    return Ok(JSTyp::Bool);
+                }
+                let relational = matches!(self, Self::Gt | Self::Ge | Self::Lt | Self::Le);
+                if relational && (*a == JSTyp::Symbol || *b == JSTyp::Symbol) {
+                    reporter.report_typ_op_violation(node, code, a, b, self, SYMBOL_COERCION)?;
+                    return Ok(JSTyp::Never);
+                }
                 if !a.is_same_typ(b) {
-                    report::report_typ_op_violation(
-                        node,
-                        code,
-                        a,
-                        b,
-                        self,
-                        "Detected cmp violation",
-                    );
+                    reporter.report_typ_op_violation(node, code, a, b, self, CMP_MISMATCH)?;
                 }
-                JSTyp::Bool
+                Ok(JSTyp::Bool)
             }
-            Self::Seq | Self::Sneq => JSTyp::Bool,
+            Self::Seq | Self::Sneq => Ok(JSTyp::Bool),
             Self::Add => {
-                self.arithmetic_typ_check(a, b, node, code);
-                a.clone() + b.clone()
+                self.arithmetic_typ_check(reporter, a, b, a.clone() + b.clone(), node, code)
             }
             Self::Sub => {
-                self.arithmetic_typ_check(a, b, node, code);
-                a.clone() - b.clone()
+                self.arithmetic_typ_check(reporter, a, b, a.clone() - b.clone(), node, code)
             }
             Self::Mul => {
-                self.arithmetic_typ_check(a, b, node, code);
-                a.clone() * b.clone()
+                self.arithmetic_typ_check(reporter, a, b, a.clone() * b.clone(), node, code)
             }
             Self::Div => {
-                self.arithmetic_typ_check(a, b, node, code);
-                a.clone() / b.clone()
+                self.arithmetic_typ_check(reporter, a, b, a.clone() / b.clone(), node, code)
+            }
+            Self::Mod => {
+                self.arithmetic_typ_check(reporter, a, b, a.clone() % b.clone(), node, code)
             }
+            Self::Exp => self.arithmetic_typ_check(
+                reporter,
+                a,
+                b,
+                a.clone().sub_mul_div(b.clone()),
+                node,
+                code,
+            ),
+            Self::Shl => {
+                self.arithmetic_typ_check(reporter, a, b, a.clone() << b.clone(), node, code)
+            }
+            Self::Shr => {
+                self.arithmetic_typ_check(reporter, a, b, a.clone() >> b.clone(), node, code)
+            }
+            Self::UShr => self.arithmetic_typ_check(
+                reporter,
+                a,
+                b,
+                a.clone().unsigned_shift(b.clone()),
+                node,
+                code,
+            ),
+            Self::BitAnd => {
+                self.arithmetic_typ_check(reporter, a, b, a.clone() & b.clone(), node, code)
+            }
+            Self::BitOr => {
+                self.arithmetic_typ_check(reporter, a, b, a.clone() | b.clone(), node, code)
+            }
+            Self::BitXor => {
+                self.arithmetic_typ_check(reporter, a, b, a.clone() ^ b.clone(), node, code)
+            }
+            Self::And | Self::Or | Self::Nullish => Ok(a.clone().logical(b.clone(), self)),
         }
     }
-    fn arithmetic_typ_check<'a>(&self, a: &JSTyp, b: &JSTyp, node: &Node<'a>, code: &str) {
+    /// Reports operands that get coerced; `typ` is the result of the operation and is `Never`
+    /// when the operation throws a TypeError at runtime
+    fn arithmetic_typ_check<'a>(
+        &self,
+        reporter: &mut Reporter,
+        a: &JSTyp,
+        b: &JSTyp,
+        typ: JSTyp,
+        node: &Node<'a>,
+        code: &'a str,
+    ) -> Result<JSTyp> {
+        // nothing is known to be wrong about an `Unknown` operand
+        if a.is_never() || b.is_never() || a.is_unknown() || b.is_unknown() {
+            return Ok(typ);
+        }
+        if typ.is_never() {
+            let rule = if *a == JSTyp::Symbol || *b == JSTyp::Symbol {
+                SYMBOL_COERCION
+            } else if matches!(self, Self::UShr) && *a == JSTyp::BigInt && *b == JSTyp::BigInt {
+                BIGINT_USHR
+            } else {
+                BIGINT_MIX
+            };
+            reporter.report_typ_op_violation(node, code, a, b, self, rule)?;
+            return Ok(typ);
+        }
         match self {
             Self::Add => match (a, b) {
                 (JSTyp::Number, JSTyp::Number) | (JSTyp::String, JSTyp::String) => {}
-                _ => report::report_typ_op_violation(
-                    node,
-                    code,
-                    a,
-                    b,
-                    self,
-                    "Detected arithmetic violation",
-                ),
+                _ => reporter.report_typ_op_violation(node, code, a, b, self, ARITH_COERCION)?,
             },
-            Self::Sub | Self::Mul | Self::Div => match (a, b) {
+            Self::Sub
+            | Self::Mul
+            | Self::Div
+            | Self::Mod
+            | Self::Exp
+            | Self::Shl
+            | Self::Shr
+            | Self::UShr
+            | Self::BitAnd
+            | Self::BitOr
+            | Self::BitXor => match (a, b) {
                 (JSTyp::Number, JSTyp::Number) => {}
-                _ => report::report_typ_op_violation(
-                    node,
-                    code,
-                    a,
-                    b,
-                    self,
-                    "Detected arithmetic violation",
-                ),
+                _ => reporter.report_typ_op_violation(node, code, a, b, self, ARITH_COERCION)?,
             },
             _ => unreachable!("Not expected arithmetic type"),
         }
+        Ok(typ)
     }
 }
@@ -0,0 +1,500 @@
+use crate::analysis::{self, Analysis};
+use crate::config::Config;
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::error::{Result, TypeinferError};
+use crate::output::TOOL_NAME;
+use crate::project::{Module, Project};
+use crate::report;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+
+const CONTENT_LENGTH: &str = "Content-Length:";
+/// `TextDocumentSyncKind.Full`: every change sends the whole document
+const SYNC_FULL: u32 = 1;
+/// JSON-RPC error code of a request the server does not handle
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code of a message whose header or content is malformed
+const PARSE_ERROR: i64 = -32700;
+/// `MessageType.Error` of `window/logMessage`
+const LOG_ERROR: u32 = 1;
+
+/// Reads a message framed by a `Content-Length` header; `None` at the end of the input. A
+/// malformed header is read to its end before it is reported, so that the next message can be
+/// read.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = Err(TypeinferError::Protocol(
+        "missing Content-Length".to_string(),
+    ));
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix(CONTENT_LENGTH) {
+            let value = value.trim();
+            length = value.parse::<usize>().map_err(|_| {
+                TypeinferError::Protocol(format!("invalid Content-Length `{}`", value))
+            });
+        }
+    }
+    let length = length?;
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| TypeinferError::Protocol(e.to_string()))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let content = message.to_string();
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Path of a `file://` URI
+pub fn uri2filename(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = vec![];
+    let mut idx = 0;
+    while idx < path.len() {
+        let escaped = (path[idx] == b'%')
+            .then(|| path.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            None => {
+                decoded.push(path[idx]);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// `file://` URI of `filename`, which is relative to the working directory or absolute
+pub fn filename2uri(filename: &str) -> String {
+    let path = std::path::absolute(filename).unwrap_or_else(|_| filename.into());
+    format!("file://{}", encode_path(&path.to_string_lossy()))
+}
+
+/// `path` with every byte but unreserved characters and `/` percent-encoded, as the path of a URI
+pub fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded += &format!("%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+/// Zero-based line and UTF-16 character of `byte` in `text`, as the protocol counts them
+fn position(text: &str, byte: usize) -> Value {
+    let byte = byte.min(text.len());
+    let point = report::byte2point(text, byte);
+    let character = text[byte - point.column..byte]
+        .chars()
+        .map(char::len_utf16)
+        .sum::<usize>();
+    json!({ "line": point.row, "character": character })
+}
+
+/// Byte of the zero-based `line` and UTF-16 `character` in `text`. A character past the end of
+/// the line is its end.
+fn offset(text: &str, line: usize, character: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => 0,
+        _ => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let mut units = 0;
+    for (idx, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + idx);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+fn lsp_severity(severity: Severity) -> u32 {
+    match severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    }
+}
+
+/// Language server publishing the diagnostics of the open documents and the modules they
+/// import, and showing the inferred types of identifiers on hover. Documents are analyzed with
+/// their unsaved content whenever they are opened or changed.
+pub struct Server {
+    config: Config,
+    /// content of the open documents keyed by filename
+    documents: BTreeMap<String, String>,
+    /// the last analysis, with its types and original sources
+    analysis: Analysis,
+    /// files the client shows diagnostics for
+    published: BTreeSet<String>,
+}
+
+impl Server {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            documents: BTreeMap::new(),
+            analysis: Analysis::default(),
+            published: BTreeSet::new(),
+        }
+    }
+
+    /// Responses and notifications to send for `message`. Notifications of the client that are
+    /// not handled are ignored.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            // a response to the server
+            None => return vec![],
+        };
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str();
+        match (method, message.get("id").cloned()) {
+            ("initialize", Some(id)) => vec![response(
+                id,
+                json!({
+                    "capabilities": { "textDocumentSync": SYNC_FULL, "hoverProvider": true },
+                    "serverInfo": { "name": TOOL_NAME, "version": env!("CARGO_PKG_VERSION") },
+                }),
+            )],
+            ("shutdown", Some(id)) => vec![response(id, Value::Null)],
+            ("textDocument/hover", Some(id)) => vec![response(id, self.hover(params))],
+            (_, Some(id)) => vec![error_response(
+                id,
+                METHOD_NOT_FOUND,
+                format!("unsupported method `{}`", method),
+            )],
+            ("textDocument/didOpen", None) => {
+                let text = params["textDocument"]["text"].as_str();
+                match (uri.and_then(uri2filename), text) {
+                    (Some(filename), Some(text)) => {
+                        self.documents.insert(filename, text.to_string());
+                        self.analyze()
+                    }
+                    _ => vec![],
+                }
+            }
+            ("textDocument/didChange", None) => {
+                // with full synchronization, the last change is the whole document
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                match (uri.and_then(uri2filename), text) {
+                    (Some(filename), Some(text)) => {
+                        self.documents.insert(filename, text.to_string());
+                        self.analyze()
+                    }
+                    _ => vec![],
+                }
+            }
+            ("textDocument/didClose", None) => match uri.and_then(uri2filename) {
+                Some(filename) if self.documents.remove(&filename).is_some() => self.analyze(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Publishes the diagnostics of every analyzed file and clears those of the files that have
+    /// none anymore. If the analysis fails, e.g., because an imported module cannot be read, the
+    /// error is logged and the previous diagnostics are kept.
+    fn analyze(&mut self) -> Vec<Value> {
+        match self.try_analyze() {
+            Ok(notifications) => notifications,
+            Err(e) => vec![notification(
+                "window/logMessage",
+                json!({ "type": LOG_ERROR, "message": e.to_string() }),
+            )],
+        }
+    }
+
+    fn try_analyze(&mut self) -> Result<Vec<Value>> {
+        let mut project = Project::default();
+        for (filename, text) in &self.documents {
+            project.modules.push(Module::parse(filename, text.clone())?);
+        }
+        project.load_imports(self.config.thread_count())?;
+        let analysis = analysis::analyze_project_typs(&project, &self.config)?;
+
+        let mut diags = self
+            .published
+            .iter()
+            .chain(self.documents.keys())
+            .map(|filename| (filename.clone(), vec![]))
+            .collect::<BTreeMap<_, _>>();
+        for diag in &analysis.diagnostics {
+            diags
+                .entry(diag.span.file.clone())
+                .or_default()
+                .push(self.diagnostic2lsp(diag));
+        }
+        self.analysis = analysis;
+        self.published = diags
+            .iter()
+            .filter(|(_, diags)| !diags.is_empty())
+            .map(|(filename, _)| filename.clone())
+            .collect();
+        Ok(diags
+            .into_iter()
+            .map(|(filename, diags)| {
+                notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": filename2uri(&filename), "diagnostics": diags }),
+                )
+            })
+            .collect())
+    }
+
+    /// Range of `span` in the source of the last analysis
+    fn range(&self, span: &Span) -> Value {
+        match self.analysis.sources.get(&span.file) {
+            Some(text) => json!({
+                "start": position(text, span.start_byte),
+                "end": position(text, span.end_byte),
+            }),
+            None => json!({
+                "start": { "line": span.start_line - 1, "character": span.start_column - 1 },
+                "end": { "line": span.end_line - 1, "character": span.end_column - 1 },
+            }),
+        }
+    }
+
+    fn diagnostic2lsp(&self, diag: &Diagnostic) -> Value {
+        let related = diag
+            .labels
+            .iter()
+            .chain(diag.notes.iter())
+            .map(|label| {
+                json!({
+                    "location": { "uri": filename2uri(&label.span.file), "range": self.range(&label.span) },
+                    "message": label.message,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "range": self.range(&diag.span),
+            "severity": lsp_severity(diag.severity),
+            "code": diag.rule,
+            "source": TOOL_NAME,
+            "message": diag.message,
+            "relatedInformation": related,
+        })
+    }
+
+    /// Every type the identifier under the cursor was inferred with, e.g., `a: Number | String`
+    fn hover(&self, params: &Value) -> Value {
+        let filename = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(uri2filename);
+        let position = &params["position"];
+        let byte = match (
+            filename
+                .as_ref()
+                .and_then(|filename| self.analysis.sources.get(filename)),
+            position["line"].as_u64(),
+            position["character"].as_u64(),
+        ) {
+            (Some(text), Some(line), Some(character)) => {
+                offset(text, line as usize, character as usize)
+            }
+            _ => None,
+        };
+        let (Some(filename), Some(byte)) = (filename, byte) else {
+            return Value::Null;
+        };
+        let Some((span, typs)) = self.analysis.typs_at(&filename, byte) else {
+            return Value::Null;
+        };
+        let typs = typs
+            .iter()
+            .map(|typ| format!("{:?}", typ))
+            .collect::<Vec<_>>();
+        let name = &self.analysis.sources[&filename][span.start_byte..span.end_byte];
+        json!({
+            "contents": { "kind": "plaintext", "value": format!("{}: {}", name, typs.join(" | ")) },
+            "range": self.range(&span),
+        })
+    }
+}
+
+/// Serves a client over stdin and stdout until it sends `exit`
+pub fn run(config: Config) -> Result<()> {
+    let mut reader = std::io::stdin().lock();
+    let mut writer = std::io::stdout().lock();
+    serve(&mut Server::new(config), &mut reader, &mut writer)
+}
+
+/// Handles the messages of `reader` until `exit`, the end of the input or an I/O error. A
+/// malformed message is answered with a parse error and the server keeps reading.
+fn serve(server: &mut Server, reader: &mut impl BufRead, writer: &mut impl Write) -> Result<()> {
+    loop {
+        let message = match read_message(reader) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(TypeinferError::Protocol(message)) => {
+                write_message(writer, &error_response(Value::Null, PARSE_ERROR, message))?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(writer, &reply)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} });
+        let mut framed = vec![];
+        write_message(&mut framed, &message).unwrap();
+        write_message(&mut framed, &message).unwrap();
+        let mut reader = framed.as_slice();
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+        assert!(read_message(&mut "Content-Type: json\r\n\r\n{}".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_serve_malformed() {
+        let mut input = b"Content-Length: 5\r\n\r\n{bad}".to_vec();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} });
+        write_message(&mut input, &request).unwrap();
+        let mut output = vec![];
+        let mut server = Server::new(Config::default());
+        serve(&mut server, &mut input.as_slice(), &mut output).unwrap();
+
+        let mut reader = output.as_slice();
+        let error = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(error["id"], Value::Null);
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+        let reply = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(reply["id"], 1);
+        assert!(reply["result"]["capabilities"].is_object());
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_position() {
+        let text = "let s = \"😀\";\nlet n = s - 1;\n";
+        let byte = text.find(';').unwrap();
+        assert_eq!(position(text, byte), json!({ "line": 0, "character": 12 }));
+        assert_eq!(offset(text, 0, 12), Some(byte));
+        assert_eq!(offset(text, 1, 4), text.find("n ="));
+        assert_eq!(offset(text, 1, 100), Some(text.len() - 1));
+        assert_eq!(offset(text, 5, 0), None);
+
+        let filename = "/tmp/a b/é.js";
+        assert_eq!(filename2uri(filename), "file:///tmp/a%20b/%C3%A9.js");
+        assert_eq!(
+            uri2filename(&filename2uri(filename)).as_deref(),
+            Some(filename)
+        );
+    }
+
+    #[test]
+    fn test_server() {
+        let uri = filename2uri("test/lsp.js");
+        let mut server = Server::new(Config::default());
+        let replies = server.handle(&json!({ "id": 1, "method": "initialize", "params": {} }));
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        let code = "function foo(a) {\n  let b = a + 1;\n}\nfoo(true);\n";
+        let replies = server.handle(&json!({
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "languageId": "javascript", "version": 1, "text": code } },
+        }));
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(replies[0]["params"]["uri"], uri);
+        let diags = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0]["message"], "Bool + Number");
+        assert_eq!(diags[0]["code"], "arith-coercion");
+        assert_eq!(
+            diags[0]["range"],
+            json!({ "start": { "line": 1, "character": 10 }, "end": { "line": 1, "character": 15 } })
+        );
+
+        let hover = |server: &mut Server, line: u64, character: u64| {
+            let replies = server.handle(&json!({
+                "id": 2,
+                "method": "textDocument/hover",
+                "params": { "textDocument": { "uri": uri }, "position": { "line": line, "character": character } },
+            }));
+            replies[0]["result"].clone()
+        };
+        let result = hover(&mut server, 1, 10);
+        assert_eq!(result["contents"]["value"], "a: Bool");
+        assert_eq!(
+            result["range"]["start"],
+            json!({ "line": 1, "character": 10 })
+        );
+        assert_eq!(hover(&mut server, 0, 13)["contents"]["value"], "a: Bool");
+        assert_eq!(hover(&mut server, 1, 6)["contents"]["value"], "b: Number");
+        assert_eq!(hover(&mut server, 1, 12), Value::Null);
+
+        // the unsaved content is analyzed and the fixed diagnostic is cleared
+        let fixed = code.replace("foo(true)", "foo(1)");
+        let replies = server.handle(&json!({
+            "method": "textDocument/didChange",
+            "params": { "textDocument": { "uri": uri, "version": 2 }, "contentChanges": [{ "text": fixed }] },
+        }));
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(hover(&mut server, 1, 10)["contents"]["value"], "a: Number");
+
+        let replies =
+            server.handle(&json!({ "id": 3, "method": "textDocument/definition", "params": {} }));
+        assert_eq!(replies[0]["error"]["code"], METHOD_NOT_FOUND);
+        assert!(server
+            .handle(&json!({ "method": "initialized", "params": {} }))
+            .is_empty());
+    }
+}
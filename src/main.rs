@@ -1,52 +1,283 @@
+pub mod analysis;
+pub mod baseline;
+pub mod builtins;
+pub mod cache;
 pub mod callgraph;
+pub mod cli;
+pub mod config;
 pub mod debloat;
+pub mod diagnostic;
+pub mod dts;
+pub mod env;
+pub mod error;
 pub mod infer;
 pub mod instrument;
+pub mod jsdoc;
 pub mod jssyntax;
+pub mod lsp;
+pub mod narrow;
 pub mod node;
+pub mod output;
+pub mod parallel;
+pub mod project;
 pub mod report;
+pub mod stubs;
+pub mod suppress;
+pub mod trace;
 pub mod util;
+pub mod watch;
 
+use crate::baseline::Baseline;
+use crate::cli::Command;
+use crate::config::Config;
+use crate::error::{Result, TypeinferError};
 use crate::node::Node;
-use std::collections::HashMap;
+use crate::project::Project;
+use std::path::{Component, Path};
 use tree_sitter_traversal::Order;
 
-fn dump_origin_source() {
-    let code = util::read_file("example/example.js").unwrap();
-    let tree = node::get_tree(&code);
+const DEFAULT_FILE: &str = "example/example.js";
+
+fn dump_origin_source(filename: &str) -> Result<()> {
+    let code = util::read_file(filename)?;
+    let tree = node::get_tree(&code)?;
     let nodes = node::get_nodes(tree.walk(), Order::Pre, &code);
     let filename = "node-dump.txt";
-    dump_node(&nodes, filename);
+    dump_node(&nodes, filename)
 }
 
 fn main() {
-    dump_origin_source();
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        if let TypeinferError::Usage(_) = e {
+            eprintln!("{}", cli::USAGE);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
+    let opts = cli::parse_args(std::env::args().skip(1))?;
+    if opts.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    let mut config = Config::discover(&std::env::current_dir()?, &opts.overrides)?;
+    if let Some(filename) = &opts.trace {
+        // relative to the working directory, not to the configuration file
+        config.trace = std::env::current_dir()?
+            .join(filename)
+            .to_string_lossy()
+            .to_string();
+    }
+    if opts.command == Command::TypeAt {
+        return type_at(&opts, &config);
+    }
+    if opts.command == Command::Lsp {
+        if !opts.files.is_empty() || opts.baseline.is_some() || opts.write_baseline.is_some() {
+            return Err(TypeinferError::Usage(
+                "lsp analyzes the documents the editor opens and takes only `--set`".to_string(),
+            ));
+        }
+        return lsp::run(config);
+    }
+    if opts.command == Command::Watch {
+        if opts.baseline.is_some() || opts.write_baseline.is_some() || opts.dump {
+            return Err(TypeinferError::Usage(
+                "watch takes no `--baseline`, `--write-baseline` or `--dump`".to_string(),
+            ));
+        }
+        // without files, `include` is expanded again on every poll to pick up new files
+        let files = if opts.files.is_empty() && config.include.is_empty() {
+            vec![DEFAULT_FILE.to_string()]
+        } else {
+            opts.files
+        };
+        return watch::run(files, config, opts.format);
+    }
+    if opts.out_dir.is_some() != (opts.command == Command::Instrument) {
+        return Err(TypeinferError::Usage(
+            "instrument needs `--out-dir` and only instrument takes it".to_string(),
+        ));
+    }
+    if opts.command == Command::Annotate && opts.files.is_empty() && config.include.is_empty() {
+        return Err(TypeinferError::Usage(
+            "annotate rewrites only the given files or those matching `include`".to_string(),
+        ));
+    }
+    let files = if !opts.files.is_empty() {
+        opts.files.clone()
+    } else if !config.include.is_empty() {
+        config.included_files()?
+    } else {
+        vec![DEFAULT_FILE.to_string()]
+    };
+    let files = files
+        .into_iter()
+        .filter(|filename| !config.is_excluded(filename))
+        .collect::<Vec<_>>();
+    if opts.dump {
+        for filename in &files {
+            dump(filename)?;
+        }
+    }
+    let project = Project::load(&files, config.thread_count())?;
+    if opts.command == Command::Annotate {
+        return annotate(&project, &files, &config);
+    }
+    if opts.command == Command::Dts {
+        return declare(&project, &files, &config);
+    }
+    if let Some(out_dir) = &opts.out_dir {
+        return instrument(&project, out_dir, &config);
+    }
+    let mut diags = analysis::analyze_project(&project, &config)?;
+    if let Some(filename) = &opts.write_baseline {
+        Baseline::from_diagnostics(&diags)?.write(filename)?;
+        eprintln!("recorded {} diagnostics in {}", diags.len(), filename);
+        return Ok(());
+    }
+    if let Some(filename) = &opts.baseline {
+        diags = Baseline::load(filename)?.filter_new(diags)?;
+    }
+    let out = output::emit(&diags, opts.format)?;
+    if !out.is_empty() {
+        println!("{}", out);
+    }
+    Ok(())
+}
+
+/// Prints the type of the variable at the location given as the only file
+fn type_at(opts: &cli::Options, config: &Config) -> Result<()> {
+    let location = match opts.files.as_slice() {
+        [location] => location,
+        _ => {
+            return Err(TypeinferError::Usage(
+                "type-at takes a single FILE:LINE:COLUMN".to_string(),
+            ))
+        }
+    };
+    let (filename, line, column) = cli::parse_location(location)?;
+    let filename = &project::module_filename(filename);
+    let project = Project::load(&[filename.to_string()], config.thread_count())?;
+    let analysis = analysis::analyze_project_typs(&project, config)?;
+    let typ = match analysis.type_at(filename, line, column) {
+        Some(typ) => typ,
+        None => {
+            eprintln!("no inferred variable at {}", location);
+            std::process::exit(1);
+        }
+    };
+    match opts.format {
+        output::Format::Text => println!("{:?}", typ),
+        output::Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "file": filename,
+                "line": line,
+                "column": column,
+                "type": format!("{:?}", typ),
+            })
+        ),
+        output::Format::Sarif => {
+            return Err(TypeinferError::Usage(
+                "type-at prints text or json, not sarif".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
 
-    // 1. Debloat origin source code to remove control flows
-    let filename = "example/example.js";
+/// Rewrites `files` with JSDoc blocks; the modules they import are analyzed but left as they are
+fn annotate(project: &Project, files: &[String], config: &Config) -> Result<()> {
+    let analysis = analysis::analyze_project_typs(project, config)?;
+    for filename in files {
+        let Some(module) = project.module(filename) else {
+            continue;
+        };
+        let annotated = jsdoc::annotate(&analysis, module);
+        if annotated.code != module.origin {
+            util::write_file(filename, &annotated.code)?;
+        }
+        eprintln!(
+            "{}: annotated {} functions and {} declarations",
+            filename, annotated.funcs, annotated.decls
+        );
+    }
+    Ok(())
+}
+
+/// Writes the `.d.ts` file of every file next to it
+fn declare(project: &Project, files: &[String], config: &Config) -> Result<()> {
+    let analysis = analysis::analyze_project_typs(project, config)?;
+    for filename in files {
+        let Some(module) = project.module(filename) else {
+            continue;
+        };
+        let dts_filename = dts::dts_filename(filename);
+        util::write_file(
+            &dts_filename,
+            &dts::declarations(&analysis, project, module),
+        )?;
+        eprintln!(
+            "{}: declared {} exports in {}",
+            filename,
+            module.exports.len(),
+            dts_filename
+        );
+    }
+    Ok(())
+}
+
+/// Writes every module of the project below `out_dir`, at its path relative to the working
+/// directory, with runtime type assertions
+fn instrument(project: &Project, out_dir: &str, config: &Config) -> Result<()> {
+    let analysis = analysis::analyze_project_typs(project, config)?;
+    let mut asserts = 0;
+    for module in &project.modules {
+        let relative = Path::new(&module.filename);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(TypeinferError::Usage(format!(
+                "instrument writes only the files below the working directory, not `{}`",
+                module.filename
+            )));
+        }
+        let path = Path::new(out_dir).join(relative);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let instrumented = instrument::instrument(&analysis, module);
+        util::write_file(&path.to_string_lossy(), &instrumented.code)?;
+        asserts += instrumented.asserts;
+    }
+    eprintln!(
+        "instrumented {} files with {} assertions in {}",
+        project.modules.len(),
+        asserts,
+        out_dir
+    );
+    Ok(())
+}
+
+/// Writes the nodes of the original source, the debloated source and its nodes
+fn dump(filename: &str) -> Result<()> {
+    dump_origin_source(filename)?;
     let debloated_filename = "debloated.js";
-    let dump_filename = "debloated-dump.txt";
-    debloat::debloat(filename, debloated_filename);
-
-    // 2. Run infer
-    let code = util::read_file(&debloated_filename).unwrap();
-    let tree = node::get_tree(&code);
-    let mut nodes = node::get_nodes(tree.walk(), Order::Pre, &code);
-    assert_eq!(nodes[0].kind(), jssyntax::PROGRAM);
-    let target_callsites = callgraph::gather_callsites("foo", &nodes[0], &code);
-    println!("callsites of foo: {:?}", target_callsites);
-    nodes.remove(0);
-    dump_node(&nodes, dump_filename);
-    let mut vars = HashMap::new();
-    let param_typs = &target_callsites[0].1;
-    // TODO: FIXME (Replace with actual parameter name in every colllected callsites)
-    infer::run_func(&mut vars, param_typs, &nodes, &nodes[0], &code);
-}
-
-fn dump_node<'a>(nodes: &Vec<Node<'a>>, filename: &str) {
-    let mut node_str = format!("{:?}", nodes[0]);
-    for node in nodes.iter().skip(1) {
-        node_str = format!("{}\n{:?}", node_str, node);
-    }
-    util::write_file(filename, &node_str);
+    debloat::debloat(filename, debloated_filename)?;
+    let code = util::read_file(debloated_filename)?;
+    let tree = node::get_tree(&code)?;
+    let nodes = node::get_nodes(tree.walk(), Order::Pre, &code);
+    dump_node(&nodes, "debloated-dump.txt")
+}
+
+fn dump_node<'a>(nodes: &Vec<Node<'a>>, filename: &str) -> Result<()> {
+    let node_str = nodes
+        .iter()
+        .map(|node| format!("{:?}", node))
+        .collect::<Vec<_>>()
+        .join("\n");
+    util::write_file(filename, &node_str)
 }
@@ -0,0 +1,268 @@
+use crate::jssyntax::{
+    JSTyp, ARROW_FUNC, ASSIGNMENT_STMT, AUGMENTED_ASSIGNMENT_STMT, BINARY_EXPR, BREAK_STMT,
+    CALL_EXPR, COMMENT, CONTINUE_STMT, FUNC_DECL, FUNC_EXPR, IF_STATEMENT, MEMBER_EXPR, METHOD_DEF,
+    NULL, PARENTHESIZED_EXPR, PROGRAM, RETURN_STMT, STMT_BLK, STRING, SUBSCRIPT_EXPR, TERNARY_EXPR,
+    THROW_STMT, UNARY_EXPR, UNDEFINED, WHILE_STMT,
+};
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+/// Types of `null` and `undefined` that the guards around `node`, an expression of the original
+/// source, rule out, e.g., both for `o` of `o.w` in `if (o) { o.w }`, `o && o.w`, `o ? o.w : 0`
+/// or after `if (o == null) return;`. Guards outside the enclosing function are not considered,
+/// since a callback may run after the guarded value changes.
+pub fn excluded(node: Node, code: &str) -> Vec<JSTyp> {
+    let target = text(node, code);
+    let mut excluded = vec![];
+    let mut child = node;
+    while let Some(parent) = child.parent() {
+        let field = |name| parent.child_by_field_name(name);
+        let guarded = match parent.kind() {
+            BINARY_EXPR if field("right") == Some(child) => match operator(parent, code) {
+                "&&" => field("left").map(|left| (left, true)),
+                "||" => field("left").map(|left| (left, false)),
+                _ => None,
+            },
+            TERNARY_EXPR if field("consequence") == Some(child) => {
+                field("condition").map(|cond| (cond, true))
+            }
+            TERNARY_EXPR if field("alternative") == Some(child) => {
+                field("condition").map(|cond| (cond, false))
+            }
+            IF_STATEMENT if field("consequence") == Some(child) => {
+                field("condition").map(|cond| (cond, true))
+            }
+            IF_STATEMENT if field("alternative") == Some(child) => {
+                field("condition").map(|cond| (cond, false))
+            }
+            WHILE_STMT if field("body") == Some(child) => {
+                field("condition").map(|cond| (cond, true))
+            }
+            STMT_BLK | PROGRAM => {
+                excluded.extend(early_exits(child, code, &target));
+                // the guards around the block do not hold once `target` is assigned in it
+                if assigned_before(child, code, &target) {
+                    break;
+                }
+                None
+            }
+            FUNC_DECL | FUNC_EXPR | ARROW_FUNC | METHOD_DEF => break,
+            _ => None,
+        };
+        if let Some((cond, truthy)) = guarded {
+            excluded.extend(guard(cond, code, &target, truthy));
+        }
+        child = parent;
+    }
+    excluded.sort();
+    excluded.dedup();
+    excluded
+}
+
+/// Types ruled out for `target` by the statements before `stmt` that leave the block unless it is
+/// not `null` or `undefined`, e.g., `if (!o) return;`. An assignment of `target` in between ends
+/// the search.
+fn early_exits(stmt: Node, code: &str, target: &str) -> Vec<JSTyp> {
+    let mut excluded = vec![];
+    let mut prev = stmt.prev_named_sibling();
+    while let Some(stmt) = prev {
+        if assigns(stmt, code, target) {
+            break;
+        }
+        if stmt.kind() == IF_STATEMENT && stmt.child_by_field_name("alternative").is_none() {
+            let cond = stmt.child_by_field_name("condition");
+            let consequence = stmt.child_by_field_name("consequence");
+            if let (Some(cond), Some(consequence)) = (cond, consequence) {
+                if exits(consequence) {
+                    excluded.extend(guard(cond, code, target, false));
+                }
+            }
+        }
+        prev = stmt.prev_named_sibling();
+    }
+    excluded
+}
+
+/// Whether a statement before `stmt` in its block assigns `target`
+fn assigned_before(stmt: Node, code: &str, target: &str) -> bool {
+    let mut prev = stmt.prev_named_sibling();
+    while let Some(stmt) = prev {
+        if assigns(stmt, code, target) {
+            return true;
+        }
+        prev = stmt.prev_named_sibling();
+    }
+    false
+}
+
+/// Types ruled out for `target` when the condition `cond` is `truthy`, or falsy otherwise
+fn guard(cond: Node, code: &str, target: &str, truthy: bool) -> Vec<JSTyp> {
+    let nullish = || vec![JSTyp::Null, JSTyp::Undefined];
+    let field = |name| cond.child_by_field_name(name);
+    match cond.kind() {
+        PARENTHESIZED_EXPR => match cond.named_child(0) {
+            Some(expr) => guard(expr, code, target, truthy),
+            None => vec![],
+        },
+        UNARY_EXPR if operator(cond, code) == "!" => match field("argument") {
+            Some(argument) => guard(argument, code, target, !truthy),
+            None => vec![],
+        },
+        BINARY_EXPR => {
+            let (Some(left), Some(right)) = (field("left"), field("right")) else {
+                return vec![];
+            };
+            match operator(cond, code) {
+                "&&" if truthy => [left, right]
+                    .into_iter()
+                    .flat_map(|operand| guard(operand, code, target, true))
+                    .collect(),
+                "||" if !truthy => [left, right]
+                    .into_iter()
+                    .flat_map(|operand| guard(operand, code, target, false))
+                    .collect(),
+                op @ ("==" | "!=" | "===" | "!==") => {
+                    let strict = op.len() == 3;
+                    let equal = compared(left, right, code, target, strict)
+                        .or_else(|| compared(right, left, code, target, strict));
+                    // `o != null` is truthy and `o == null` falsy unless `o` is nullish
+                    match equal {
+                        Some(typs) if truthy == op.starts_with('!') => typs,
+                        _ => vec![],
+                    }
+                }
+                "instanceof" if truthy && text(left, code) == target => nullish(),
+                _ => vec![],
+            }
+        }
+        _ if truthy && refers(cond, code, target) => nullish(),
+        _ => vec![],
+    }
+}
+
+/// The nullish types `operand` equals when it is compared with `other`, e.g., both for `o` of
+/// `o == null` but only `undefined` for `o === undefined` and `typeof o === "undefined"`; `None`
+/// if it does not compare `target`
+fn compared(
+    operand: Node,
+    other: Node,
+    code: &str,
+    target: &str,
+    strict: bool,
+) -> Option<Vec<JSTyp>> {
+    let is_typeof = operand.kind() == UNARY_EXPR && operator(operand, code) == "typeof";
+    if is_typeof {
+        let argument = operand.child_by_field_name("argument")?;
+        let undefined =
+            other.kind() == STRING && text(other, code).trim_matches(['"', '\'']) == "undefined";
+        return (text(argument, code) == target && undefined).then(|| vec![JSTyp::Undefined]);
+    }
+    if text(operand, code) != target {
+        return None;
+    }
+    match other.kind() {
+        _ if !strict && matches!(other.kind(), NULL | UNDEFINED) => {
+            Some(vec![JSTyp::Null, JSTyp::Undefined])
+        }
+        NULL => Some(vec![JSTyp::Null]),
+        UNDEFINED => Some(vec![JSTyp::Undefined]),
+        _ => None,
+    }
+}
+
+/// Whether the truthy value of `node` requires `target` not to be `null` or `undefined`, e.g.,
+/// `o`, `o?.w` or `o.f()`
+fn refers(node: Node, code: &str, target: &str) -> bool {
+    if text(node, code) == target {
+        return true;
+    }
+    let object = match node.kind() {
+        MEMBER_EXPR | SUBSCRIPT_EXPR => node.child_by_field_name("object"),
+        CALL_EXPR => node.child_by_field_name("function"),
+        _ => None,
+    };
+    object.is_some_and(|object| refers(object, code, target))
+}
+
+/// Whether the statement `node` always leaves the enclosing block
+fn exits(node: Node) -> bool {
+    match node.kind() {
+        RETURN_STMT | THROW_STMT | BREAK_STMT | CONTINUE_STMT => true,
+        STMT_BLK => {
+            let mut cursor = node.walk();
+            let last = node
+                .named_children(&mut cursor)
+                .filter(|child| child.kind() != COMMENT)
+                .last();
+            last.is_some_and(exits)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the statement `node` assigns `target`
+fn assigns(node: Node, code: &str, target: &str) -> bool {
+    traverse(node.walk(), Order::Pre).any(|child| {
+        matches!(child.kind(), ASSIGNMENT_STMT | AUGMENTED_ASSIGNMENT_STMT)
+            && child
+                .child_by_field_name("left")
+                .is_some_and(|left| text(left, code) == target)
+    })
+}
+
+fn operator<'a>(node: Node, code: &'a str) -> &'a str {
+    node.child_by_field_name("operator")
+        .map_or("", |operator| &code[operator.byte_range()])
+}
+
+/// Source of `node` without whitespace, so that, e.g., `o . w` and `o.w` compare equal
+fn text(node: Node, code: &str) -> String {
+    code[node.byte_range()].split_whitespace().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+
+    /// Types ruled out for the object of the last member expression of `code`
+    fn excluded_at_last(code: &str) -> Vec<JSTyp> {
+        let tree = node::get_tree(code).unwrap();
+        let member = traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == MEMBER_EXPR)
+            .last()
+            .unwrap();
+        excluded(member.child_by_field_name("object").unwrap(), code)
+    }
+
+    #[test]
+    fn test_excluded() {
+        let nullish = vec![JSTyp::Null, JSTyp::Undefined];
+        assert_eq!(excluded_at_last("if (o) { f(o.w); }"), nullish);
+        assert_eq!(excluded_at_last("let w = o && o.w;"), nullish);
+        assert_eq!(excluded_at_last("let w = !o || o.w;"), nullish);
+        assert_eq!(excluded_at_last("let w = o != null ? o.w : 0;"), nullish);
+        assert_eq!(
+            excluded_at_last("if (o === null) {} else { o.w; }"),
+            vec![JSTyp::Null]
+        );
+        assert_eq!(
+            excluded_at_last("if (typeof o !== \"undefined\") { o.w; }"),
+            vec![JSTyp::Undefined]
+        );
+        assert_eq!(
+            excluded_at_last("if (!o) { return; }\nlet w = o.w;"),
+            nullish
+        );
+        assert_eq!(excluded_at_last("if (o?.w) { o.w; }"), nullish);
+        // the guard does not hold after `o` is assigned again, nor in a callback
+        assert_eq!(excluded_at_last("if (!o) return;\no = f();\no.w;"), vec![]);
+        assert_eq!(excluded_at_last("if (o) { g(() => o.w); }"), vec![]);
+        assert_eq!(
+            excluded_at_last("if (o) { o = null; let t = o.x; }"),
+            vec![]
+        );
+        assert_eq!(excluded_at_last("while (o) { o = o.next; o.w; }"), vec![]);
+        assert_eq!(excluded_at_last("if (o || p) { o.w; }"), vec![]);
+    }
+}
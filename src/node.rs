@@ -1,6 +1,8 @@
-use crate::debloat::{LOC_ANNOT, PARENT_NODE_ID_ANNOT};
-use crate::jssyntax::{COMMENT, ELSE_CLAUSE, IF_STATEMENT, STMT_BLK};
-use crate::node;
+use crate::debloat::{BYTES_ANNOT, LOC_ANNOT, PARENT_NODE_ID_ANNOT};
+use crate::error::{Result, TypeinferError};
+use crate::jssyntax::{
+    COMMENT, ELSE_CLAUSE, FUNC_DECL, GENERATOR_FUNC_DECL, IF_STATEMENT, STMT_BLK,
+};
 use tree_sitter::{Parser, Tree, TreeCursor};
 use tree_sitter::{Point, Range};
 use tree_sitter_traversal::{traverse, Order};
@@ -16,12 +18,24 @@ impl<'a> Node<'a> {
     }
 }
 
-pub fn get_tree(code: &str) -> Tree {
+pub fn get_parser() -> Result<Parser> {
     let mut parser = Parser::new();
     parser
         .set_language(tree_sitter_javascript::language())
-        .unwrap();
-    parser.parse(&code, None).unwrap()
+        .map_err(|e| TypeinferError::Parse(e.to_string()))?;
+    Ok(parser)
+}
+
+pub fn get_tree(code: &str) -> Result<Tree> {
+    reparse(&mut get_parser()?, code, None)
+}
+
+/// Parses `code` reusing the unchanged parts of `old_tree`, which must have been `edit`ed to
+/// match `code`
+pub fn reparse(parser: &mut Parser, code: &str, old_tree: Option<&Tree>) -> Result<Tree> {
+    parser
+        .parse(code, old_tree)
+        .ok_or_else(|| TypeinferError::Parse("parsing was cancelled".to_string()))
 }
 
 pub fn get_nodes<'a>(tree_cursor: TreeCursor<'a>, order: Order, code: &'a str) -> Vec<Node<'a>> {
@@ -63,6 +77,33 @@ pub fn run_subtree<'a>(
     }
 }
 
+/// `run_subtree` for visitors that can fail. The traversal stops at the first error.
+pub fn try_run_subtree<'a>(
+    node: &Node<'a>,
+    code: &'a str,
+    mut f: impl FnMut(&Node<'a>, bool) -> Result<Option<Range>>,
+) -> Result<()> {
+    let mut result = Ok(());
+    run_subtree(node, code, |child, last| {
+        if result.is_err() {
+            return Some(node.info.range());
+        }
+        f(child, last).unwrap_or_else(|e| {
+            result = Err(e);
+            Some(node.info.range())
+        })
+    });
+    result
+}
+
+/// returns the child stored in the grammar field `field` (e.g., `left` of a binary expression)
+pub fn get_field<'a>(node: &Node<'a>, field: &str, code: &'a str) -> Option<Node<'a>> {
+    node.info.child_by_field_name(field).map(|child| Node {
+        info: child,
+        text: &code[child.byte_range()],
+    })
+}
+
 /// returns true if the parent path includes branch
 pub fn is_in_ctrl_flow<'a>(node: &Node<'a>) -> bool {
     let mut p = node.info.parent();
@@ -77,10 +118,18 @@ pub fn is_in_ctrl_flow<'a>(node: &Node<'a>) -> bool {
     false
 }
 
-pub fn get_annot<'a>(node: &Node<'a>, code: &'a str) -> &'a str {
+pub fn get_annot<'a>(node: &Node<'a>, code: &'a str) -> Result<&'a str> {
+    get_annotated_stmt(node, code).map(|(_, annot)| annot)
+}
+
+/// returns the debloated statement that `node` belongs to along with its annotation
+pub fn get_annotated_stmt<'a>(
+    node: &Node<'a>,
+    code: &'a str,
+) -> Result<(tree_sitter::Node<'a>, &'a str)> {
     if let Some(next_sib) = node.info.next_sibling() {
         if next_sib.kind() == COMMENT {
-            return &code[next_sib.byte_range()];
+            return Ok((node.info, &code[next_sib.byte_range()]));
         }
     }
 
@@ -88,31 +137,81 @@ pub fn get_annot<'a>(node: &Node<'a>, code: &'a str) -> &'a str {
     while let Some(parent) = p {
         if let Some(next_sib) = parent.next_sibling() {
             if next_sib.kind() == COMMENT {
-                return &code[next_sib.byte_range()];
+                return Ok((parent, &code[next_sib.byte_range()]));
             }
         }
         p = parent.parent();
     }
 
     // function comment
-    if let Some(p) = node.info.parent() {
-        let stmt_blk_node = p.next_sibling().unwrap();
-        assert_eq!(stmt_blk_node.kind(), STMT_BLK);
-        let children = node::get_nodes(stmt_blk_node.walk(), Order::Pre, &code);
-        return &code[children[2].info.byte_range()];
+    if let Some(parent) = node.info.parent() {
+        if let Some(stmt_blk_node) = parent.next_sibling().filter(|n| n.kind() == STMT_BLK) {
+            if let Some(annot) = func_annot(stmt_blk_node, code) {
+                let func = parent.parent().unwrap_or(parent);
+                return Ok((func, annot));
+            }
+        }
     }
-    unreachable!();
+
+    // name of a function, which shares the comment of its parameters
+    if let Some(func) = node
+        .info
+        .parent()
+        .filter(|parent| matches!(parent.kind(), FUNC_DECL | GENERATOR_FUNC_DECL))
+    {
+        if let Some(annot) = func
+            .child_by_field_name("body")
+            .and_then(|body| func_annot(body, code))
+        {
+            return Ok((func, annot));
+        }
+    }
+    Err(TypeinferError::MissingAnnot(node.text.to_string()))
+}
+
+/// The comment of the line of `{` of the function body `body`, which ends the line unless the
+/// body is on one line, e.g., `{ return n * 2 ; // [Loc] ...`
+fn func_annot<'a>(body: tree_sitter::Node<'a>, code: &'a str) -> Option<&'a str> {
+    let mut cursor = body.walk();
+    let annot = body
+        .children(&mut cursor)
+        .find(|child| child.kind() == COMMENT)
+        .filter(|comment| comment.start_position().row == body.start_position().row)?;
+    Some(&code[annot.byte_range()])
 }
 
-pub fn get_loc<'a>(annot: &'a str) -> &'a str {
-    &annot[LOC_ANNOT.len() + 1..annot.find(",").unwrap()]
+pub fn get_loc<'a>(annot: &'a str) -> Result<&'a str> {
+    annot
+        .find(",")
+        .and_then(|end| annot.get(LOC_ANNOT.len() + 1..end))
+        .ok_or_else(|| TypeinferError::MalformedAnnot(annot.to_string()))
 }
 
-pub fn get_parent_id(annot: &str) -> usize {
+pub fn get_parent_id(annot: &str) -> Result<usize> {
+    let malformed = || TypeinferError::MalformedAnnot(annot.to_string());
     let parent_id_annot = {
-        let annot =
-            &annot[annot.find(PARENT_NODE_ID_ANNOT).unwrap() + PARENT_NODE_ID_ANNOT.len() + 1..];
-        &annot[..annot.find(",").unwrap()]
+        let start = annot.find(PARENT_NODE_ID_ANNOT).ok_or_else(malformed)?;
+        let annot = annot
+            .get(start + PARENT_NODE_ID_ANNOT.len() + 1..)
+            .ok_or_else(malformed)?;
+        &annot[..annot.find(",").ok_or_else(malformed)?]
     };
-    parent_id_annot.parse::<usize>().unwrap()
+    parent_id_annot.parse::<usize>().map_err(|_| malformed())
+}
+
+/// returns the original byte range recorded by `[Bytes] <start>-<end>`
+pub fn get_bytes(annot: &str) -> Result<(usize, usize)> {
+    let malformed = || TypeinferError::MalformedAnnot(annot.to_string());
+    let start = annot.find(BYTES_ANNOT).ok_or_else(malformed)?;
+    let bytes = annot
+        .get(start + BYTES_ANNOT.len() + 1..)
+        .ok_or_else(malformed)?;
+    let bytes = &bytes[..bytes.find(",").ok_or_else(malformed)?];
+    match bytes.split_once("-") {
+        Some((start, end)) => Ok((
+            start.parse::<usize>().map_err(|_| malformed())?,
+            end.parse::<usize>().map_err(|_| malformed())?,
+        )),
+        None => Err(malformed()),
+    }
 }
@@ -0,0 +1,296 @@
+use crate::diagnostic::{Diagnostic, Label, Severity, Span, RULES};
+use crate::error::{Result, TypeinferError};
+use crate::lsp;
+use crate::report;
+use crate::util;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::str::FromStr;
+
+pub const TOOL_NAME: &str = "typeinfer";
+const TOOL_URI: &str = "https://github.com/hyunsooda/typeinfer";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable, colored
+    Text,
+    /// One JSON object per diagnostic and line
+    Json,
+    /// SARIF 2.1.0 log for code scanning UIs
+    Sarif,
+}
+impl FromStr for Format {
+    type Err = TypeinferError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            _ => Err(TypeinferError::Usage(format!(
+                "unknown format `{}` (expected text, json or sarif)",
+                s
+            ))),
+        }
+    }
+}
+
+pub fn emit(diags: &[Diagnostic], format: Format) -> Result<String> {
+    match format {
+        Format::Text => Ok(diags
+            .iter()
+            .map(|diag| report::render_diagnostic(diag, true))
+            .collect::<Result<Vec<_>>>()?
+            .join("\n\n")),
+        Format::Json => Ok(to_json_lines(diags)),
+        Format::Sarif => Ok(serde_json::to_string_pretty(&to_sarif(diags)).unwrap_or_default()),
+    }
+}
+
+fn span2json(span: &Span) -> Value {
+    json!({
+        "file": span.file,
+        "start": { "line": span.start_line, "column": span.start_column, "byte": span.start_byte },
+        "end": { "line": span.end_line, "column": span.end_column, "byte": span.end_byte },
+    })
+}
+
+fn label2json(label: &Label) -> Value {
+    json!({
+        "message": label.message,
+        "location": span2json(&label.span),
+    })
+}
+
+pub fn diagnostic2json(diag: &Diagnostic) -> Value {
+    json!({
+        "rule": diag.rule,
+        "severity": diag.severity.to_string(),
+        "message": diag.message,
+        "location": span2json(&diag.span),
+        "operand_types": diag.operand_typs.iter().map(|typ| format!("{:?}", typ)).collect::<Vec<_>>(),
+        "labels": diag.labels.iter().map(label2json).collect::<Vec<_>>(),
+        "notes": diag.notes.iter().map(label2json).collect::<Vec<_>>(),
+    })
+}
+
+/// One diagnostic per line
+pub fn to_json_lines(diags: &[Diagnostic]) -> String {
+    diags
+        .iter()
+        .map(|diag| diagnostic2json(diag).to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+/// Base of the relative URIs of the log, the working directory
+const SRCROOT: &str = "%SRCROOT%";
+
+/// One-based UTF-16 column of `byte` in `code`, which is how SARIF counts columns by default;
+/// `None` if `byte` is not a character boundary of `code`, e.g., of a file modified since
+fn utf16_column(code: &str, byte: usize) -> Option<usize> {
+    let line = code.get(..byte)?.rsplit('\n').next()?;
+    Some(line.chars().map(char::len_utf16).sum::<usize>() + 1)
+}
+
+/// `span` as a SARIF location. The columns are counted in the source of its file among `sources`;
+/// a file that could not be read keeps its byte columns.
+fn sarif_location(span: &Span, sources: &BTreeMap<String, String>) -> Value {
+    let source = sources.get(&span.file);
+    let column = |byte, column| {
+        source
+            .and_then(|code| utf16_column(code, byte))
+            .unwrap_or(column)
+    };
+    let artifact = if Path::new(&span.file).is_absolute() {
+        json!({ "uri": lsp::filename2uri(&span.file) })
+    } else {
+        json!({ "uri": lsp::encode_path(&span.file), "uriBaseId": SRCROOT })
+    };
+    json!({
+        "physicalLocation": {
+            "artifactLocation": artifact,
+            "region": {
+                "startLine": span.start_line,
+                "startColumn": column(span.start_byte, span.start_column),
+                "endLine": span.end_line,
+                "endColumn": column(span.end_byte, span.end_column),
+                "byteOffset": span.start_byte,
+                "byteLength": span.end_byte - span.start_byte,
+            },
+        },
+    })
+}
+
+fn sarif_result(diag: &Diagnostic, sources: &BTreeMap<String, String>) -> Value {
+    let related = diag
+        .labels
+        .iter()
+        .chain(diag.notes.iter())
+        .enumerate()
+        .map(|(id, label)| {
+            let mut location = sarif_location(&label.span, sources);
+            location["id"] = json!(id);
+            location["message"] = json!({ "text": label.message });
+            location
+        })
+        .collect::<Vec<_>>();
+    let mut result = json!({
+        "ruleId": diag.rule,
+        "level": sarif_level(diag.severity),
+        "message": { "text": diag.message },
+        "locations": [sarif_location(&diag.span, sources)],
+        "relatedLocations": related,
+        "properties": {
+            "operandTypes": diag.operand_typs.iter().map(|typ| format!("{:?}", typ)).collect::<Vec<_>>(),
+        },
+    });
+    if let Some(idx) = RULES.iter().position(|(id, _, _)| *id == diag.rule) {
+        result["ruleIndex"] = json!(idx);
+    }
+    result
+}
+
+pub fn to_sarif(diags: &[Diagnostic]) -> Value {
+    let files = diags
+        .iter()
+        .flat_map(|diag| {
+            let labels = diag.labels.iter().chain(diag.notes.iter());
+            std::iter::once(&diag.span).chain(labels.map(|label| &label.span))
+        })
+        .map(|span| span.file.clone())
+        .collect::<BTreeSet<_>>();
+    let sources = files
+        .into_iter()
+        .filter_map(|file| Some((file.clone(), util::read_file(&file).ok()?)))
+        .collect::<BTreeMap<_, _>>();
+    let srcroot = std::env::current_dir()
+        .map(|dir| format!("{}/", lsp::filename2uri(&dir.to_string_lossy())))
+        .unwrap_or_default();
+    let rules = RULES
+        .iter()
+        .map(|(id, severity, description)| {
+            json!({
+                "id": id,
+                "shortDescription": { "text": description },
+                "defaultConfiguration": { "level": sarif_level(*severity) },
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": TOOL_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": TOOL_URI,
+                    "rules": rules,
+                },
+            },
+            "originalUriBaseIds": { SRCROOT: { "uri": srcroot } },
+            "columnKind": "utf16CodeUnits",
+            "results": diags
+                .iter()
+                .map(|diag| sarif_result(diag, &sources))
+                .collect::<Vec<_>>(),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::config::Config;
+    use crate::diagnostic::{CMP_MISMATCH, UNSUPPORTED_CONSTRUCT};
+
+    #[test]
+    fn test_json_lines() {
+        let diags = analysis::analyze_file("example/example.js", &Config::default()).unwrap();
+        let lines = to_json_lines(&diags);
+        let lines = lines.split("\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), diags.len());
+
+        let first: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["rule"], CMP_MISMATCH);
+        assert_eq!(first["severity"], "warning");
+        assert_eq!(first["message"], "Undefined == Number");
+        assert_eq!(first["location"]["file"], "example/example.js");
+        assert_eq!(first["location"]["start"]["line"], 3);
+        assert_eq!(first["location"]["start"]["column"], 7);
+        assert_eq!(first["operand_types"], json!(["Undefined", "Number"]));
+        assert_eq!(first["labels"][1]["message"], "Number");
+    }
+
+    #[test]
+    fn test_sarif() {
+        let diags = analysis::analyze_file("example/example.js", &Config::default()).unwrap();
+        let sarif = to_sarif(&diags);
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let run = &sarif["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), RULES.len());
+        assert!(rules.iter().any(|rule| rule["id"] == UNSUPPORTED_CONSTRUCT));
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), diags.len());
+        let result = &results[0];
+        assert_eq!(result["ruleId"], CMP_MISMATCH);
+        assert_eq!(
+            rules[result["ruleIndex"].as_u64().unwrap() as usize]["id"],
+            CMP_MISMATCH
+        );
+        assert_eq!(result["level"], "warning");
+        let region = &result["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(
+            (region["startLine"].clone(), region["startColumn"].clone()),
+            (json!(3), json!(7))
+        );
+        assert_eq!(
+            result["relatedLocations"][0]["message"]["text"],
+            "Undefined"
+        );
+        assert_eq!(
+            result["properties"]["operandTypes"],
+            json!(["Undefined", "Number"])
+        );
+    }
+
+    #[test]
+    fn test_sarif_utf16() {
+        let diags = analysis::analyze_file("test/output/unicode.js", &Config::default()).unwrap();
+        let sarif = to_sarif(&diags);
+        let run = &sarif["runs"][0];
+        assert_eq!(run["columnKind"], "utf16CodeUnits");
+        assert!(run["originalUriBaseIds"][SRCROOT]["uri"]
+            .as_str()
+            .unwrap()
+            .starts_with("file:///"));
+
+        // `a - 1` of `  let s = "é" + (a - 1);`, whose `é` is two bytes but one UTF-16 unit
+        let location = &run["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"],
+            json!({ "uri": "test/output/unicode.js", "uriBaseId": SRCROOT })
+        );
+        let region = &location["region"];
+        assert_eq!(
+            (region["startColumn"].clone(), region["endColumn"].clone()),
+            (json!(18), json!(23))
+        );
+        assert_eq!(diags[0].span.start_column, 19);
+    }
+}
@@ -0,0 +1,646 @@
+use crate::error::Result;
+use crate::jssyntax::{
+    ARGS, ASSIGNMENT_STMT, CALL_EXPR, DEFAULT, EXPORT_CLAUSE, EXPORT_SPECIFIER, EXPORT_STMT,
+    EXPR_STMT, FUNC_DECL, IDENT, IMPORT_CLAUSE, IMPORT_SPECIFIER, IMPORT_STMT, LEXICAL_DECL,
+    MEMBER_EXPR, NAMED_IMPORTS, NAMESPACE_IMPORT, OBJECT, OBJECT_PATTERN, PAIR, PAIR_PATTERN,
+    SHORTHAND_PROPERTY_IDENT, SHORTHAND_PROPERTY_IDENT_PATTERN, STRING, VARIABLE_DECLARATION,
+    VAR_DECL,
+};
+use crate::node::{self, Node};
+use crate::parallel;
+use crate::util;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use tree_sitter::Tree;
+
+/// Name of `export default` and of the value of `module.exports = ...`
+pub const DEFAULT_EXPORT: &str = "default";
+/// Name of the binding of `import * as ns` and of `const ns = require(...)` of a package, which is
+/// the whole module
+pub const NAMESPACE: &str = "*";
+const REQUIRE: &str = "require";
+const MODULE_EXPORTS: &str = "module.exports";
+const EXPORTS: &str = "exports";
+/// Tried in order after the specifier itself, e.g., `./lib` is `./lib.js`
+const RESOLVE_SUFFIXES: [&str; 4] = [".js", ".mjs", ".cjs", "/index.js"];
+
+/// A binding imported from another module of the project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// filename of the exporting module
+    pub module: String,
+    /// name the binding is exported by
+    pub name: String,
+}
+
+/// A source file and the bindings it shares with the other modules. Only top-level imports and
+/// exports whose module is found through a relative path are recorded, and the top-level imports
+/// of packages apart.
+#[derive(Debug)]
+pub struct Module {
+    pub filename: String,
+    /// original source
+    pub origin: String,
+    /// syntax tree of `origin`
+    pub tree: Tree,
+    /// local name of the binding -> where it comes from
+    pub imports: BTreeMap<String, Import>,
+    /// local name of a binding imported from a package, e.g., `lodash`, which is not part of the
+    /// project -> the package and the name it is exported by
+    pub packages: BTreeMap<String, Import>,
+    /// local name of `import * as ns` or `const ns = require(...)` of a module of the project ->
+    /// filename of the module, whose exports are called as members, e.g., `ns.f()`
+    pub namespaces: BTreeMap<String, String>,
+    /// exported name -> local name of the exported function or variable
+    pub exports: BTreeMap<String, String>,
+}
+
+impl Module {
+    pub fn parse(filename: &str, origin: String) -> Result<Self> {
+        let tree = node::get_tree(&origin)?;
+        Ok(Self::with_tree(filename, origin, tree))
+    }
+
+    /// Collects the imports and exports of ES modules (`import`, `export`) and CommonJS modules
+    /// (`require`, `module.exports`, `exports`). `tree` is the syntax tree of `origin`, e.g.,
+    /// reparsed incrementally after an edit.
+    pub fn with_tree(filename: &str, origin: String, tree: Tree) -> Self {
+        let code = origin.as_str();
+        let root = tree.root_node();
+        let mut cursor = root.walk();
+        let mut imports = BTreeMap::new();
+        let mut packages = BTreeMap::new();
+        let mut namespaces = BTreeMap::new();
+        let mut exports = BTreeMap::new();
+        for stmt in root.named_children(&mut cursor) {
+            let stmt = Node {
+                info: stmt,
+                text: &code[stmt.byte_range()],
+            };
+            match stmt.kind() {
+                IMPORT_STMT => collect_es_imports(
+                    filename,
+                    &stmt,
+                    code,
+                    &mut imports,
+                    &mut packages,
+                    &mut namespaces,
+                ),
+                LEXICAL_DECL | VARIABLE_DECLARATION => collect_requires(
+                    filename,
+                    &stmt,
+                    code,
+                    &mut imports,
+                    &mut packages,
+                    &mut namespaces,
+                ),
+                EXPORT_STMT => collect_es_exports(&stmt, code, &mut exports),
+                EXPR_STMT => collect_commonjs_exports(&stmt, code, &mut exports),
+                _ => {}
+            }
+        }
+        drop(cursor);
+        Self {
+            filename: filename.to_string(),
+            origin,
+            tree,
+            imports,
+            packages,
+            namespaces,
+            exports,
+        }
+    }
+}
+
+/// Modules of an analysis, in load order
+#[derive(Debug, Default)]
+pub struct Project {
+    pub modules: Vec<Module>,
+}
+
+impl Project {
+    /// Loads `filenames` and every module they import, transitively, parsing on `jobs` threads
+    pub fn load(filenames: &[String], jobs: usize) -> Result<Self> {
+        let mut project = Self::default();
+        let filenames = filenames
+            .iter()
+            .map(|filename| module_filename(filename))
+            .collect::<Vec<_>>();
+        project.parse(filenames, jobs)?;
+        project.load_imports(jobs)?;
+        Ok(project)
+    }
+
+    /// Loads the imported modules that are not loaded yet, transitively. The modules imported by
+    /// the modules loaded last are parsed together on `jobs` threads.
+    pub fn load_imports(&mut self, jobs: usize) -> Result<()> {
+        let mut loaded = 0;
+        while loaded < self.modules.len() {
+            let imported = self.modules[loaded..]
+                .iter()
+                .flat_map(|module| {
+                    module
+                        .imports
+                        .values()
+                        .map(|import| &import.module)
+                        .chain(module.namespaces.values())
+                        .cloned()
+                })
+                .collect::<Vec<_>>();
+            loaded = self.modules.len();
+            self.parse(imported, jobs)?;
+        }
+        Ok(())
+    }
+
+    /// Reads and parses the modules of `filenames` that are not loaded yet, in order
+    fn parse(&mut self, mut filenames: Vec<String>, jobs: usize) -> Result<()> {
+        let mut seen = vec![];
+        filenames.retain(|filename| {
+            let new = self.module(filename).is_none() && !seen.contains(filename);
+            seen.push(filename.clone());
+            new
+        });
+        let modules = parallel::map(
+            &filenames,
+            jobs,
+            || (),
+            |_, filename| Module::parse(filename, util::read_file(filename)?),
+        );
+        for module in modules {
+            self.modules.push(module?);
+        }
+        Ok(())
+    }
+
+    /// The module of `filename`, however it is spelled, e.g., `./lib.js` for `lib.js`
+    pub fn module(&self, filename: &str) -> Option<&Module> {
+        let filename = module_filename(filename);
+        self.modules
+            .iter()
+            .find(|module| module.filename == filename)
+    }
+
+    /// Exports of the namespaces of `module` and the namespace they are members of, e.g., `ns` and
+    /// `f` for `ns.f` of `import * as ns from "./lib"`
+    pub fn namespace_imports(&self, module: &Module) -> Vec<(String, Import)> {
+        let mut imports = vec![];
+        for (ns, filename) in &module.namespaces {
+            let Some(imported) = self.module(filename) else {
+                continue;
+            };
+            for name in imported.exports.keys() {
+                let import = Import {
+                    module: filename.clone(),
+                    name: name.clone(),
+                };
+                imports.push((ns.clone(), import));
+            }
+        }
+        imports
+    }
+
+    /// Local name of the function `import` refers to in its module
+    pub fn resolve_import(&self, import: &Import) -> Option<&str> {
+        self.module(&import.module)?
+            .exports
+            .get(&import.name)
+            .map(|name| name.as_str())
+    }
+}
+
+/// Filename of the module `specifier` refers to from `importer`. Only relative specifiers are
+/// resolved; packages are not part of the project.
+pub fn resolve(importer: &str, specifier: &str) -> Option<String> {
+    if !specifier.starts_with("./") && !specifier.starts_with("../") {
+        return None;
+    }
+    let dir = Path::new(importer).parent().unwrap_or(Path::new(""));
+    let path = normalize(&dir.join(specifier));
+    let path = path.to_string_lossy();
+    std::iter::once(path.to_string())
+        .chain(
+            RESOLVE_SUFFIXES
+                .iter()
+                .map(|suffix| format!("{}{}", path, suffix)),
+        )
+        .find(|candidate| Path::new(candidate).is_file())
+}
+
+/// Whether `specifier` names a package, e.g., `lodash` or `@org/sdk/client`, rather than a file
+pub fn is_package(specifier: &str) -> bool {
+    !specifier.is_empty() && !specifier.starts_with('.') && !specifier.starts_with('/')
+}
+
+/// Filename of the module of `filename`, e.g., `lib.js` for `./lib.js`, as imports name it
+pub fn module_filename(filename: &str) -> String {
+    normalize(Path::new(filename)).to_string_lossy().to_string()
+}
+
+/// Removes `.` and resolves `..` without touching the file system so that a module has a single
+/// filename however it is imported
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn children<'a>(node: &Node<'a>, code: &'a str) -> Vec<Node<'a>> {
+    let mut cursor = node.info.walk();
+    node.info
+        .named_children(&mut cursor)
+        .map(|child| Node {
+            info: child,
+            text: &code[child.byte_range()],
+        })
+        .collect()
+}
+
+/// Content of a string literal
+fn string_value<'a>(node: &Node<'a>) -> Option<&'a str> {
+    if node.kind() != STRING || node.text.len() < 2 {
+        return None;
+    }
+    Some(&node.text[1..node.text.len() - 1])
+}
+
+/// `import d, { a, b as c } from "./lib"` and `import * as ns from "./lib"`, or from a package
+fn collect_es_imports<'a>(
+    filename: &str,
+    stmt: &Node<'a>,
+    code: &'a str,
+    imports: &mut BTreeMap<String, Import>,
+    packages: &mut BTreeMap<String, Import>,
+    namespaces: &mut BTreeMap<String, String>,
+) {
+    let Some(specifier) = node::get_field(stmt, "source", code)
+        .as_ref()
+        .and_then(string_value)
+    else {
+        return;
+    };
+    let (imports, module) = match resolve(filename, specifier) {
+        Some(module) => (imports, module),
+        None if is_package(specifier) => (packages, specifier.to_string()),
+        None => return,
+    };
+    let is_package = is_package(specifier);
+    let mut import = |local: &str, name: &str| {
+        imports.insert(
+            local.to_string(),
+            Import {
+                module: module.clone(),
+                name: name.to_string(),
+            },
+        );
+    };
+    for clause in children(stmt, code)
+        .iter()
+        .filter(|child| child.kind() == IMPORT_CLAUSE)
+    {
+        for child in children(clause, code) {
+            match child.kind() {
+                IDENT => import(child.text, DEFAULT_EXPORT),
+                NAMED_IMPORTS => {
+                    for specifier in children(&child, code)
+                        .iter()
+                        .filter(|specifier| specifier.kind() == IMPORT_SPECIFIER)
+                    {
+                        if let Some(name) = node::get_field(specifier, "name", code) {
+                            let local = node::get_field(specifier, "alias", code)
+                                .map_or(name.text, |alias| alias.text);
+                            import(local, name.text);
+                        }
+                    }
+                }
+                NAMESPACE_IMPORT => {
+                    if let Some(local) = children(&child, code).first() {
+                        if is_package {
+                            import(local.text, NAMESPACE);
+                        } else {
+                            namespaces.insert(local.text.to_string(), module.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// `const f = require("./lib")` and `const { a, b: c } = require("./lib")`. `require` of a package
+/// binds the whole module; `require` of a module of the project binds its `module.exports`, whose
+/// properties are its other exports.
+fn collect_requires<'a>(
+    filename: &str,
+    stmt: &Node<'a>,
+    code: &'a str,
+    imports: &mut BTreeMap<String, Import>,
+    packages: &mut BTreeMap<String, Import>,
+    namespaces: &mut BTreeMap<String, String>,
+) {
+    for decl in children(stmt, code)
+        .iter()
+        .filter(|decl| decl.kind() == VAR_DECL)
+    {
+        let Some(specifier) = node::get_field(decl, "value", code)
+            .filter(|value| value.kind() == CALL_EXPR)
+            .and_then(|call| required_specifier(&call, code))
+        else {
+            continue;
+        };
+        let (imports, module, whole) = match resolve(filename, specifier) {
+            Some(module) => (&mut *imports, module, DEFAULT_EXPORT),
+            None if is_package(specifier) => (&mut *packages, specifier.to_string(), NAMESPACE),
+            None => continue,
+        };
+        if whole == DEFAULT_EXPORT {
+            if let Some(name) =
+                node::get_field(decl, "name", code).filter(|name| name.kind() == IDENT)
+            {
+                namespaces.insert(name.text.to_string(), module.clone());
+            }
+        }
+        let mut import = |local: &str, name: &str| {
+            imports.insert(
+                local.to_string(),
+                Import {
+                    module: module.clone(),
+                    name: name.to_string(),
+                },
+            );
+        };
+        match node::get_field(decl, "name", code) {
+            Some(name) if name.kind() == IDENT => import(name.text, whole),
+            Some(pattern) if pattern.kind() == OBJECT_PATTERN => {
+                for property in children(&pattern, code) {
+                    match property.kind() {
+                        SHORTHAND_PROPERTY_IDENT_PATTERN => import(property.text, property.text),
+                        PAIR_PATTERN => {
+                            if let (Some(key), Some(value)) = (
+                                node::get_field(&property, "key", code),
+                                node::get_field(&property, "value", code),
+                            ) {
+                                if value.kind() == IDENT {
+                                    import(value.text, key.text);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Specifier of `require("./lib")`
+fn required_specifier<'a>(call: &Node<'a>, code: &'a str) -> Option<&'a str> {
+    let func = node::get_field(call, "function", code)?;
+    if func.kind() != IDENT || func.text != REQUIRE {
+        return None;
+    }
+    let args = node::get_field(call, "arguments", code).filter(|args| args.kind() == ARGS)?;
+    let specifier = children(&args, code).into_iter().next()?;
+    string_value(&specifier)
+}
+
+/// `export function f() {}`, `export default function f() {}`, `export const c = 1`,
+/// `export default f` and `export { f, g as h }`. Re-exports from other modules are not followed.
+fn collect_es_exports<'a>(stmt: &Node<'a>, code: &'a str, exports: &mut BTreeMap<String, String>) {
+    if node::get_field(stmt, "source", code).is_some() {
+        return;
+    }
+    let mut cursor = stmt.info.walk();
+    let is_default = stmt
+        .info
+        .children(&mut cursor)
+        .any(|child| child.kind() == DEFAULT);
+    if let Some(decl) = node::get_field(stmt, "declaration", code) {
+        if let (FUNC_DECL, Some(name)) = (decl.kind(), node::get_field(&decl, "name", code)) {
+            let exported = if is_default {
+                DEFAULT_EXPORT
+            } else {
+                name.text
+            };
+            exports.insert(exported.to_string(), name.text.to_string());
+        }
+        if matches!(decl.kind(), LEXICAL_DECL | VARIABLE_DECLARATION) {
+            for declarator in children(&decl, code)
+                .iter()
+                .filter(|declarator| declarator.kind() == VAR_DECL)
+            {
+                if let Some(name) =
+                    node::get_field(declarator, "name", code).filter(|name| name.kind() == IDENT)
+                {
+                    exports.insert(name.text.to_string(), name.text.to_string());
+                }
+            }
+        }
+    }
+    if let Some(value) = node::get_field(stmt, "value", code).filter(|value| value.kind() == IDENT)
+    {
+        exports.insert(DEFAULT_EXPORT.to_string(), value.text.to_string());
+    }
+    for clause in children(stmt, code)
+        .iter()
+        .filter(|child| child.kind() == EXPORT_CLAUSE)
+    {
+        for specifier in children(clause, code)
+            .iter()
+            .filter(|specifier| specifier.kind() == EXPORT_SPECIFIER)
+        {
+            if let Some(name) = node::get_field(specifier, "name", code) {
+                let exported =
+                    node::get_field(specifier, "alias", code).map_or(name.text, |alias| alias.text);
+                exports.insert(exported.to_string(), name.text.to_string());
+            }
+        }
+    }
+}
+
+/// `module.exports = f`, `module.exports = { f, g: h }`, `module.exports.f = f` and
+/// `exports.f = f`
+fn collect_commonjs_exports<'a>(
+    stmt: &Node<'a>,
+    code: &'a str,
+    exports: &mut BTreeMap<String, String>,
+) {
+    let assignment = match children(stmt, code).into_iter().next() {
+        Some(assignment) if assignment.kind() == ASSIGNMENT_STMT => assignment,
+        _ => return,
+    };
+    let (lhs, rhs) = match (
+        node::get_field(&assignment, "left", code),
+        node::get_field(&assignment, "right", code),
+    ) {
+        (Some(lhs), Some(rhs)) if lhs.kind() == MEMBER_EXPR => (lhs, rhs),
+        _ => return,
+    };
+    if lhs.text == MODULE_EXPORTS {
+        match rhs.kind() {
+            IDENT => {
+                exports.insert(DEFAULT_EXPORT.to_string(), rhs.text.to_string());
+            }
+            OBJECT => {
+                for property in children(&rhs, code) {
+                    match property.kind() {
+                        SHORTHAND_PROPERTY_IDENT => {
+                            exports.insert(property.text.to_string(), property.text.to_string());
+                        }
+                        PAIR => {
+                            if let (Some(key), Some(value)) = (
+                                node::get_field(&property, "key", code),
+                                node::get_field(&property, "value", code),
+                            ) {
+                                if value.kind() == IDENT {
+                                    exports.insert(key.text.to_string(), value.text.to_string());
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+    let object = node::get_field(&lhs, "object", code);
+    let property = node::get_field(&lhs, "property", code);
+    if let (Some(object), Some(property)) = (object, property) {
+        if (object.text == MODULE_EXPORTS || object.text == EXPORTS) && rhs.kind() == IDENT {
+            exports.insert(property.text.to_string(), rhs.text.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let project = Project::load(&["test/project/main.js".to_string()], 2).unwrap();
+        let filenames = project
+            .modules
+            .iter()
+            .map(|module| module.filename.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            filenames,
+            vec![
+                "test/project/main.js",
+                "test/project/lib/index.js",
+                "test/project/math.js",
+                "test/project/legacy.cjs",
+            ]
+        );
+
+        let main = project.module("test/project/main.js").unwrap();
+        let imported = main
+            .imports
+            .iter()
+            .map(|(local, import)| {
+                (
+                    local.as_str(),
+                    import.module.as_str(),
+                    project.resolve_import(import),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            imported,
+            vec![
+                ("greet", "test/project/lib/index.js", Some("greet")),
+                ("half", "test/project/math.js", Some("halve")),
+                ("legacyCount", "test/project/legacy.cjs", Some("count")),
+                ("legacyName", "test/project/legacy.cjs", Some("name")),
+                ("twice", "test/project/math.js", Some("double")),
+            ]
+        );
+        assert_eq!(resolve("test/project/main.js", "lodash"), None);
+        assert_eq!(
+            resolve("test/project/lib/index.js", "../math"),
+            Some("test/project/math.js".to_string())
+        );
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let filename = "test/project/namespaces.js";
+        let project = Project::load(&[filename.to_string()], 1).unwrap();
+        let module = project.module(filename).unwrap();
+        let namespaces = module
+            .namespaces
+            .iter()
+            .map(|(ns, module)| (ns.as_str(), module.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            namespaces,
+            vec![
+                ("math", "test/project/math.js"),
+                ("shapes", "test/project/shapes.cjs"),
+            ]
+        );
+        let members = project
+            .namespace_imports(module)
+            .into_iter()
+            .map(|(ns, import)| format!("{}.{}", ns, import.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            members,
+            vec!["math.default", "math.halve", "shapes.label", "shapes.scale"]
+        );
+    }
+
+    #[test]
+    fn test_load_normalized() {
+        // the same module given as `./` and reached through an import is loaded once
+        let filenames = ["./test/project/main.js", "test/project/./math.js"];
+        let project = Project::load(&filenames.map(|filename| filename.to_string()), 1).unwrap();
+        assert_eq!(project.modules.len(), 4);
+        assert_eq!(project.modules[1].filename, "test/project/math.js");
+        assert!(project.module("./test/project/math.js").is_some());
+    }
+
+    #[test]
+    fn test_packages() {
+        let code = r#"import _ from "lodash";
+import * as fp from "lodash/fp";
+import { get as pick } from "@org/sdk";
+const express = require("express");
+const { join } = require("path");
+import { missing } from "./missing";
+"#;
+        let module = Module::parse("main.js", code.to_string()).unwrap();
+        let packages = module
+            .packages
+            .iter()
+            .map(|(local, import)| (local.as_str(), import.module.as_str(), import.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            packages,
+            vec![
+                ("_", "lodash", DEFAULT_EXPORT),
+                ("express", "express", NAMESPACE),
+                ("fp", "lodash/fp", NAMESPACE),
+                ("join", "path", "join"),
+                ("pick", "@org/sdk", "get"),
+            ]
+        );
+        assert!(module.imports.is_empty());
+    }
+}
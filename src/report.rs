@@ -1,54 +1,825 @@
+use crate::builtins::{Param, Violation};
+use crate::debloat;
+use crate::diagnostic::{self, Diagnostic, Label, Severity, Span};
+use crate::error::{Result, TypeinferError};
 use crate::jssyntax::{JSOp, JSTyp};
+use crate::narrow;
 use crate::node::{self, Node};
+use crate::stubs::Expected;
 use crate::util;
 use colored::*;
 
-use std::cell::RefCell;
-
-thread_local!(static VIOLATION_HISTORY: RefCell<Vec<String>> = RefCell::new(vec![]));
-
-/// Report type viloation
-pub fn report_typ_op_violation<'a>(
-    node: &Node<'a>,
-    code: &str,
-    lhs_typ: &JSTyp,
-    rhs_typ: &JSTyp,
-    op: &JSOp,
-    prefix: &str,
-) {
-    let annot = node::get_annot(node, code);
-    let loc = node::get_loc(annot);
-    VIOLATION_HISTORY.with(|history| {
-        history.borrow_mut().push(format!(
-            "{} {:?} {} {:?} \n{} ({})",
-            format!("[{}]", prefix),
-            lhs_typ,
-            op.to_string(),
-            rhs_typ,
-            loc2code(loc),
-            loc,
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tree_sitter::{Point, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+/// Original source that debloated code maps back to
+#[derive(Clone)]
+struct Source {
+    code: String,
+    tree: Tree,
+}
+
+/// What throws a TypeError on `null` or `undefined` besides reading a property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dereference {
+    /// `f()`
+    Call,
+    /// `let { a } = o` or a destructured parameter
+    Destructure,
+    /// `for (const x of xs)`
+    Iterate,
+}
+
+/// A debloated node mapped to the original source
+struct Orig<'s, 'a> {
+    filename: &'a str,
+    source: &'s Source,
+    /// `None` if the node is not found, e.g., one introduced by debloating
+    node: Option<tree_sitter::Node<'s>>,
+    /// byte range of the enclosing statement
+    stmt: (usize, usize),
+}
+
+/// A variable of the original source and a type it was inferred with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub span: Span,
+    /// debloated name, which tells apart the variables of a function sharing a name
+    pub var: String,
+    pub typ: JSTyp,
+}
+
+/// Types inferred for the original sources, to query them by position
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    pub occurrences: Vec<Occurrence>,
+    /// statements and the types of the variables before them, by original name
+    pub envs: Vec<(Span, BTreeMap<String, JSTyp>)>,
+    /// names of the function declarations and the types they return
+    pub returns: Vec<(Span, JSTyp)>,
+}
+
+impl Types {
+    pub fn extend(&mut self, other: Types) {
+        for occurrence in other.occurrences {
+            if !self.occurrences.contains(&occurrence) {
+                self.occurrences.push(occurrence);
+            }
+        }
+        for env in other.envs {
+            if !self.envs.contains(&env) {
+                self.envs.push(env);
+            }
+        }
+        for ret in other.returns {
+            if !self.returns.contains(&ret) {
+                self.returns.push(ret);
+            }
+        }
+    }
+}
+
+/// Collects the diagnostics of an analysis. Locations of the debloated code are resolved to the
+/// original source when reported. Analyses running on several threads each report to a `fork`,
+/// whose diagnostics are `extend`ed back in a fixed order.
+#[derive(Default)]
+pub struct Reporter {
+    diagnostics: Vec<Diagnostic>,
+    /// shared by the forks; copied only if a fork has to read a missing source
+    sources: Arc<HashMap<String, Source>>,
+    /// why a variable holds `undefined`, keyed by its debloated name
+    undefined_origins: HashMap<String, Label>,
+    /// types of the original sources, if recorded
+    typs: Option<Types>,
+}
+
+impl Reporter {
+    /// Report type viloation
+    pub fn report_typ_op_violation<'a>(
+        &mut self,
+        node: &Node<'a>,
+        code: &'a str,
+        lhs_typ: &JSTyp,
+        rhs_typ: &JSTyp,
+        op: &JSOp,
+        rule: &'static str,
+    ) -> Result<()> {
+        let span = self.get_orig_span(node, code)?;
+        let mut labels = vec![];
+        let mut notes = vec![];
+        for (field, typ) in [("left", lhs_typ), ("right", rhs_typ)] {
+            if let Some(operand) = node::get_field(node, field, code) {
+                labels.push(Label {
+                    span: self.get_orig_span(&operand, code)?,
+                    message: format!("{:?}", typ),
+                });
+                if *typ == JSTyp::Undefined {
+                    if let Some(origin) = self.undefined_origins.get(operand.text) {
+                        if !notes.contains(origin) {
+                            notes.push(origin.clone());
+                        }
+                    }
+                }
+            }
+        }
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message: format!("{:?} {} {:?}", lhs_typ, op.to_string(), rhs_typ),
+            span,
+            operand_typs: vec![lhs_typ.clone(), rhs_typ.clone()],
+            labels,
+            notes,
+        });
+        Ok(())
+    }
+
+    /// Note a construct the analysis does not understand yet. Its value is treated as `Unknown`
+    /// so that the rest of the function is still analyzed.
+    pub fn report_unsupported<'a>(&mut self, node: &Node<'a>, code: &'a str) -> Result<()> {
+        let rule = diagnostic::UNSUPPORTED_CONSTRUCT;
+        let span = self.get_orig_span(node, code)?;
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message: format!("unsupported construct `{}`", node.kind()),
+            span,
+            operand_typs: vec![],
+            labels: vec![],
+            notes: vec![],
+        });
+        Ok(())
+    }
+
+    /// Note a module whose debloated code `code` does not parse to a program, e.g., because of a
+    /// construct the debloater does not handle. None of its functions is analyzed. The note is at
+    /// the original statement of the first syntax error, or at the start of the module.
+    pub fn report_unparsed(&mut self, filename: &str, tree: &Tree, code: &str) {
+        let errors = traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.is_error())
+            .collect::<Vec<_>>();
+        // the innermost error, not the program it may have turned into
+        let innermost = errors.iter().find(|error| {
+            !errors.iter().any(|other| {
+                other.id() != error.id()
+                    && other.start_byte() >= error.start_byte()
+                    && other.end_byte() <= error.end_byte()
+            })
+        });
+        let annotated = innermost
+            .and_then(|error| code.lines().nth(error.start_position().row))
+            .and_then(|line| line.find(debloat::LOC_ANNOT).map(|idx| &line[idx..]))
+            .and_then(|annot| get_annot_range(annot).ok());
+        let source = self.sources.get(filename);
+        let span = match (annotated, source) {
+            (Some((_, start_byte, end_byte)), Some(source)) => {
+                byte_span(filename, &source.code, start_byte, end_byte)
+            }
+            _ => byte_span(filename, "", 0, 0),
+        };
+        let rule = diagnostic::UNSUPPORTED_CONSTRUCT;
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message: "unsupported construct; the functions of this module are not analyzed"
+                .to_string(),
+            span,
+            operand_typs: vec![],
+            labels: vec![],
+            notes: vec![],
+        });
+    }
+
+    /// Note a function, named by `name`, that is not analyzed for `reason`, e.g., a generator or a
+    /// function the inference failed on. Its calls are `Unknown`. A name that does not map to the
+    /// original source is noted at the start of `filename`.
+    pub fn report_unanalyzed<'a>(
+        &mut self,
+        name: &Node<'a>,
+        code: &'a str,
+        filename: &str,
+        reason: &str,
+    ) {
+        let span = self
+            .get_orig_span(name, code)
+            .unwrap_or_else(|_| byte_span(filename, "", 0, 0));
+        let rule = diagnostic::UNSUPPORTED_CONSTRUCT;
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message: format!("`{}` is not analyzed: {}", name.text, reason),
+            span,
+            operand_typs: vec![],
+            labels: vec![],
+            notes: vec![],
+        });
+    }
+
+    /// Report that the parameter `param` of `func` was observed with `typeof` `observed` at
+    /// runtime although its callsites pass only values of `inferred`
+    pub fn report_trace_mismatch<'a>(
+        &mut self,
+        param: &Node<'a>,
+        code: &'a str,
+        func: &str,
+        observed: &str,
+        inferred: &[JSTyp],
+    ) -> Result<()> {
+        let rule = diagnostic::TRACE_MISMATCH;
+        let span = self.get_orig_span(param, code)?;
+        let inferred = inferred
+            .iter()
+            .map(|typ| format!("{:?}", typ))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message: format!(
+                "`{}` of `{}` is a `{}` at runtime but inferred as {}",
+                debloat::orig_ident(param.text),
+                func,
+                observed,
+                inferred
+            ),
+            span,
+            operand_typs: vec![],
+            labels: vec![],
+            notes: vec![],
+        });
+        Ok(())
+    }
+
+    /// Checks the argument `arg` of the call `node` of the builtin `name` against `param` and
+    /// reports it if a value of `typ` is coerced or makes the builtin throw
+    pub fn check_builtin_arg<'a>(
+        &mut self,
+        node: &Node<'a>,
+        arg: &Node<'a>,
+        code: &'a str,
+        name: &str,
+        param: Param,
+        typ: &JSTyp,
+    ) -> Result<Option<Violation>> {
+        let Some(violation) = param.check(typ) else {
+            return Ok(None);
+        };
+        let rule = match violation {
+            Violation::Coerced => diagnostic::BUILTIN_COERCION,
+            Violation::Throws => diagnostic::BUILTIN_TYPE_ERROR,
+        };
+        let message = format!("`{}` expects {}, found {:?}", name, param.expected(), typ);
+        self.report_arg(node, arg, code, rule, message, typ)?;
+        Ok(Some(violation))
+    }
+
+    /// Report the argument `arg` of the call `node` of `name`, whose stub declaration expects
+    /// `expected` but is passed a value of `typ`
+    pub fn report_stub_arg<'a>(
+        &mut self,
+        node: &Node<'a>,
+        arg: &Node<'a>,
+        code: &'a str,
+        name: &str,
+        expected: &Expected,
+        typ: &JSTyp,
+    ) -> Result<()> {
+        let rule = diagnostic::STUB_ARG_MISMATCH;
+        let message = format!(
+            "`{}` expects {}, found {:?}",
+            name,
+            expected.describe(),
+            typ
+        );
+        self.report_arg(node, arg, code, rule, message, typ)
+    }
+
+    /// Report reading the property `name` of `object`, the object of the member expression
+    /// `node`, which is `null` or `undefined` if every type of `typs` is, or may be otherwise
+    pub fn report_nullish_property<'a>(
+        &mut self,
+        node: &Node<'a>,
+        object: &Node<'a>,
+        code: &'a str,
+        name: &str,
+        typs: &[JSTyp],
+    ) -> Result<()> {
+        let described = describe(typs);
+        let (rule, message) = if typs.iter().all(JSTyp::is_nullish) {
+            let message = format!("cannot read `{}` of {}", name, described);
+            (diagnostic::NULLISH_PROPERTY, message)
+        } else {
+            let message = format!("reading `{}` of {} may throw", name, described);
+            (diagnostic::MAYBE_NULLISH_PROPERTY, message)
+        };
+        self.report_nullish(node, object, object.text, code, (rule, message), typs)
+    }
+
+    /// Report `dereference` of `value`, a part of `node` that reads the variable `var`, which is
+    /// `null` or `undefined` if every type of `typs` is, or may be otherwise
+    pub fn report_nullish_dereference<'a>(
+        &mut self,
+        node: &Node<'a>,
+        value: &Node<'a>,
+        var: &str,
+        code: &'a str,
+        dereference: Dereference,
+        typs: &[JSTyp],
+    ) -> Result<()> {
+        let described = describe(typs);
+        let (verb, gerund) = match dereference {
+            Dereference::Call => ("call", "calling"),
+            Dereference::Destructure => ("destructure", "destructuring"),
+            Dereference::Iterate => ("iterate over", "iterating over"),
+        };
+        let (rule, message) = if typs.iter().all(JSTyp::is_nullish) {
+            let message = format!("cannot {} {}", verb, described);
+            (diagnostic::NULLISH_DEREFERENCE, message)
+        } else {
+            let message = format!("{} {} may throw", gerund, described);
+            (diagnostic::MAYBE_NULLISH_DEREFERENCE, message)
+        };
+        self.report_nullish(node, value, var, code, (rule, message), typs)
+    }
+
+    /// Report `node`, which throws if `value` of the types `typs` is `null` or `undefined`
+    fn report_nullish<'a>(
+        &mut self,
+        node: &Node<'a>,
+        value: &Node<'a>,
+        var: &str,
+        code: &'a str,
+        (rule, message): (&'static str, String),
+        typs: &[JSTyp],
+    ) -> Result<()> {
+        let span = self.get_orig_span(node, code)?;
+        let labels = vec![Label {
+            span: self.get_orig_span(value, code)?,
+            message: describe(typs),
+        }];
+        let mut notes = vec![];
+        if typs.contains(&JSTyp::Undefined) {
+            notes.extend(self.undefined_origins.get(var).cloned());
+        }
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message,
+            span,
+            operand_typs: typs.to_vec(),
+            labels,
+            notes,
+        });
+        Ok(())
+    }
+
+    /// Report the call `node` with the argument `arg` labelled with its type `typ`
+    fn report_arg<'a>(
+        &mut self,
+        node: &Node<'a>,
+        arg: &Node<'a>,
+        code: &'a str,
+        rule: &'static str,
+        message: String,
+        typ: &JSTyp,
+    ) -> Result<()> {
+        let span = self.get_orig_span(node, code)?;
+        let labels = vec![Label {
+            span: self.get_orig_span(arg, code)?,
+            message: format!("{:?}", typ),
+        }];
+        let mut notes = vec![];
+        if *typ == JSTyp::Undefined {
+            notes.extend(self.undefined_origins.get(arg.text).cloned());
+        }
+        self.push(Diagnostic {
+            rule,
+            severity: diagnostic::default_severity(rule),
+            message,
+            span,
+            operand_typs: vec![typ.clone()],
+            labels,
+            notes,
+        });
+        Ok(())
+    }
+
+    /// Records why the variable `var` holds `undefined`; `node` is where the value comes from
+    pub fn trace_undefined<'a>(
+        &mut self,
+        var: &str,
+        node: &Node<'a>,
+        code: &'a str,
+        reason: &str,
+    ) -> Result<()> {
+        let span = self.get_orig_span(node, code)?;
+        self.trace_undefined_at(var, span, reason);
+        Ok(())
+    }
+
+    /// `trace_undefined` with a location of the original source
+    pub fn trace_undefined_at(&mut self, var: &str, span: Span, reason: &str) {
+        let message = format!(
+            "`{}` is Undefined because {}",
+            debloat::orig_ident(var),
+            reason
+        );
+        self.undefined_origins
+            .insert(var.to_string(), Label { span, message });
+    }
+
+    /// `to` holds `undefined` for the same reason as `from`, if known
+    pub fn copy_undefined_origin(&mut self, to: &str, from: &str) {
+        if let Some(origin) = self.undefined_origins.get(from).cloned() {
+            self.undefined_origins.insert(to.to_string(), origin);
+        }
+    }
+
+    /// Records the types of variables from now on, e.g., to show them on hover
+    pub fn record_typs(&mut self) {
+        self.typs.get_or_insert_with(Types::default);
+    }
+
+    pub fn records_typs(&self) -> bool {
+        self.typs.is_some()
+    }
+
+    /// Records that the identifier `node` has the type `typ` if types are recorded. Identifiers
+    /// introduced by debloating have no original occurrence and are not recorded.
+    pub fn record_typ<'a>(&mut self, node: &Node<'a>, code: &'a str, typ: &JSTyp) -> Result<()> {
+        if self.typs.is_none() {
+            return Ok(());
+        }
+        let span = self.get_orig_span(node, code)?;
+        let source = self.get_source(&span.file)?;
+        if source.code.get(span.start_byte..span.end_byte) != Some(debloat::orig_ident(node.text)) {
+            return Ok(());
+        }
+        let occurrences = &mut self.typs.get_or_insert_with(Types::default).occurrences;
+        let occurrence = Occurrence {
+            span,
+            var: node.text.to_string(),
+            typ: typ.clone(),
+        };
+        if !occurrences.contains(&occurrence) {
+            occurrences.push(occurrence);
+        }
+        Ok(())
+    }
+
+    /// Records that the function named `name` returns `typ` if types are recorded
+    pub fn record_return<'a>(&mut self, name: &Node<'a>, code: &'a str, typ: &JSTyp) -> Result<()> {
+        if self.typs.is_none() {
+            return Ok(());
+        }
+        let span = self.get_orig_span(name, code)?;
+        let returns = &mut self.typs.get_or_insert_with(Types::default).returns;
+        let ret = (span, typ.clone());
+        if !returns.contains(&ret) {
+            returns.push(ret);
+        }
+        Ok(())
+    }
+
+    /// Records `env`, the types of the variables before the statement `node`, if types are
+    /// recorded. A debloated statement maps to the whole original statement it comes from, e.g.,
+    /// the condition of an `if` to the `if` statement.
+    pub fn record_env<'a>(
+        &mut self,
+        node: &Node<'a>,
+        code: &'a str,
+        env: BTreeMap<String, JSTyp>,
+    ) -> Result<()> {
+        if self.typs.is_none() {
+            return Ok(());
+        }
+        let (_, annot) = node::get_annotated_stmt(node, code)?;
+        let (filename, start_byte, end_byte) = get_annot_range(annot)?;
+        let source = self.get_source(filename)?;
+        let span = byte_span(filename, &source.code, start_byte, end_byte);
+        let envs = &mut self.typs.get_or_insert_with(Types::default).envs;
+        let env = (span, env);
+        if !envs.contains(&env) {
+            envs.push(env);
+        }
+        Ok(())
+    }
+
+    /// Registers the original source of `filename` so that it is not read from disk again
+    pub fn add_source(&mut self, filename: &str, code: &str) -> Result<()> {
+        let tree = node::get_tree(code)?;
+        Arc::make_mut(&mut self.sources).insert(
+            filename.to_string(),
+            Source {
+                code: code.to_string(),
+                tree,
+            },
+        );
+        Ok(())
+    }
+
+    /// An empty reporter sharing the sources of `self`
+    pub fn fork(&self) -> Self {
+        Self {
+            diagnostics: vec![],
+            sources: Arc::clone(&self.sources),
+            undefined_origins: HashMap::new(),
+            typs: self.typs.as_ref().map(|_| Types::default()),
+        }
+    }
+
+    /// Appends the diagnostics that are not reported yet, e.g., those of a fork
+    pub fn extend(&mut self, diags: Vec<Diagnostic>) {
+        for diag in diags {
+            self.push(diag);
+        }
+    }
+
+    /// The same violation is found again when a function is inferred with several callsites
+    fn push(&mut self, diag: Diagnostic) {
+        if !self.diagnostics.contains(&diag) {
+            self.diagnostics.push(diag);
+        }
+    }
+
+    pub fn diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    /// The diagnostics and the recorded types, empty if types are not recorded
+    pub fn into_parts(self) -> (Vec<Diagnostic>, Types) {
+        (self.diagnostics, self.typs.unwrap_or_default())
+    }
+
+    /// Maps a debloated node to the original source. The annotation of the enclosing statement
+    /// gives the original byte range of the statement; within it, the node is the n-th node of
+    /// its kind in both the debloated and the original statement.
+    pub fn get_orig_span<'a>(&mut self, node: &Node<'a>, code: &'a str) -> Result<Span> {
+        let orig = self.get_orig_node(node, code)?;
+        let (start_byte, end_byte) = match orig.node {
+            Some(node) => (node.start_byte(), node.end_byte()),
+            None => orig.stmt,
+        };
+        Ok(byte_span(
+            orig.filename,
+            &orig.source.code,
+            start_byte,
+            end_byte,
         ))
-    });
-    println!(
-        "{} {:?} {} {:?} \n{} ({})",
-        format!("[{}]", prefix).red(),
-        lhs_typ,
-        op.to_string(),
-        rhs_typ,
-        loc2code(loc),
-        loc.yellow(),
-    );
-}
-
-fn loc2code(loc: &str) -> String {
-    let (filename, row) = {
-        let loc = loc.split(":").collect::<Vec<_>>();
-        (loc[0], loc[1].parse::<usize>().unwrap())
+    }
+
+    /// Types of `typs` that the guards around the original of the debloated expression `node`
+    /// leave, e.g., neither `null` nor `undefined` for `o` of `o && o.w`
+    pub fn narrow<'a>(
+        &mut self,
+        node: &Node<'a>,
+        code: &'a str,
+        typs: &[JSTyp],
+    ) -> Result<Vec<JSTyp>> {
+        let orig = self.get_orig_node(node, code)?;
+        let excluded = orig
+            .node
+            .map(|node| narrow::excluded(node, &orig.source.code))
+            .unwrap_or_default();
+        Ok(typs
+            .iter()
+            .filter(|typ| !excluded.contains(typ))
+            .cloned()
+            .collect())
+    }
+
+    /// The original of the debloated `node`
+    fn get_orig_node<'s, 'a>(&'s mut self, node: &Node<'a>, code: &'a str) -> Result<Orig<'s, 'a>> {
+        let (stmt, annot) = node::get_annotated_stmt(node, code)?;
+        let (filename, start_byte, end_byte) = get_annot_range(annot)?;
+
+        let nth = traverse(stmt.walk(), Order::Pre)
+            .filter(|n| n.kind() == node.kind())
+            .position(|n| n.id() == node.info.id());
+        let source = self.get_source(filename)?;
+        let orig = nth.and_then(|nth| {
+            traverse(source.tree.walk(), Order::Pre)
+                .filter(|n| {
+                    n.kind() == node.kind()
+                        && n.start_byte() >= start_byte
+                        && n.end_byte() <= end_byte
+                })
+                .nth(nth)
+        });
+        Ok(Orig {
+            filename,
+            source,
+            node: orig,
+            stmt: (start_byte, end_byte),
+        })
+    }
+
+    fn get_source(&mut self, filename: &str) -> Result<&Source> {
+        if !self.sources.contains_key(filename) {
+            let code = util::read_file(filename)?;
+            let tree = node::get_tree(&code)?;
+            Arc::make_mut(&mut self.sources).insert(filename.to_string(), Source { code, tree });
+        }
+        Ok(&self.sources[filename])
+    }
+}
+
+/// Types as in messages, e.g., `Null | Object`
+fn describe(typs: &[JSTyp]) -> String {
+    typs.iter()
+        .map(|typ| format!("{:?}", typ))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Original file and byte range of the statement annotated with `annot`
+fn get_annot_range(annot: &str) -> Result<(&str, usize, usize)> {
+    let loc = node::get_loc(annot)?;
+    let filename = match loc.rsplitn(3, ":").nth(2) {
+        Some(filename) => filename,
+        None => return Err(TypeinferError::MalformedAnnot(annot.to_string())),
+    };
+    let (start_byte, end_byte) = node::get_bytes(annot)?;
+    Ok((filename, start_byte, end_byte))
+}
+
+/// Span of the bytes `start_byte..end_byte` of `code`, the content of `filename`
+pub fn byte_span(filename: &str, code: &str, start_byte: usize, end_byte: usize) -> Span {
+    let start = byte2point(code, start_byte);
+    let end = byte2point(code, end_byte);
+    Span {
+        file: filename.to_string(),
+        start_byte,
+        end_byte,
+        start_line: start.row + 1,
+        start_column: start.column + 1,
+        end_line: end.row + 1,
+        end_column: end.column + 1,
+    }
+}
+
+/// Row and byte column of `byte` in `code`, as tree-sitter counts them
+pub fn byte2point(code: &str, byte: usize) -> Point {
+    let before = &code[..byte.min(code.len())];
+    let row = before.matches("\n").count();
+    let column = before.len() - before.rfind("\n").map(|idx| idx + 1).unwrap_or(0);
+    Point { row, column }
+}
+
+const CONTEXT_LINES: usize = 2;
+
+fn paint(text: &str, colored: bool, style: fn(&str) -> ColoredString) -> String {
+    if colored {
+        style(text).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whitespace that lines up with the first `column - 1` bytes of `line`, keeping tabs
+fn indent(line: &str, column: usize) -> String {
+    line.get(..column.saturating_sub(1))
+        .unwrap_or(line)
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+/// Number of characters of `line` between the 1-based byte columns `start` and `end`, at least 1
+fn width(line: &str, start: usize, end: usize) -> usize {
+    line.get(start.saturating_sub(1)..end.saturating_sub(1).min(line.len()))
+        .map(|s| s.chars().count())
+        .unwrap_or(0)
+        .max(1)
+}
+
+/// Marks `span` on `line` with `mark`, e.g., `    ^^^ Number`
+fn underline(line: &str, span: &Span, mark: char, message: &str) -> String {
+    let end = if span.end_line == span.start_line {
+        span.end_column
+    } else {
+        line.len() + 1
+    };
+    let marks = mark.to_string().repeat(width(line, span.start_column, end));
+    if message.is_empty() {
+        format!("{}{}", indent(line, span.start_column), marks)
+    } else {
+        format!("{}{} {}", indent(line, span.start_column), marks, message)
+    }
+}
+
+/// Renders `diag` as a code frame
+/// ```text
+/// warning[cmp-mismatch]: Undefined == Number
+///  --> example/example.js:3:7
+///   |
+/// 1 | function foo(a) {
+/// 2 |   let b,c,d;
+/// 3 |   if (a == 10) {
+///   |       ~~~~~~~
+///   |       ^ Undefined
+///   |            ^^ Number
+/// 4 |     if (a+10 < 30) {
+/// 5 |       b = 200 + 100 === 10;
+///   |
+///   = note: `a` is Undefined because `foo` is called with `undefined` at example/example.js:19:11
+/// ```
+/// The whole expression is underlined with `~` and each operand is marked with `^` and its type.
+pub fn render_diagnostic(diag: &Diagnostic, colored: bool) -> Result<String> {
+    let code = util::read_file(&diag.span.file)?;
+    let lines = code.lines().collect::<Vec<_>>();
+    let span = &diag.span;
+    let first = span.start_line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (span.end_line + CONTEXT_LINES).min(lines.len());
+    let gutter_width = last.to_string().len();
+    let mark_style = match diag.severity {
+        Severity::Error => |s: &str| s.red().bold(),
+        Severity::Warning => |s: &str| s.yellow().bold(),
+        Severity::Note => |s: &str| s.blue().bold(),
+    };
+    let gutter = |lineno: Option<usize>| {
+        let num = lineno.map(|n| n.to_string()).unwrap_or_default();
+        paint(&format!("{:>w$} |", num, w = gutter_width), colored, |s| {
+            s.blue().bold()
+        })
+    };
+
+    let header = format!("{}[{}]", diag.severity, diag.rule);
+    let header = if colored {
+        match diag.severity {
+            Severity::Error => header.white().on_red().bold(),
+            Severity::Warning => header.red(),
+            Severity::Note => header.blue(),
+        }
+        .to_string()
+    } else {
+        header
     };
-    let code = util::read_file(filename).unwrap();
-    code.split("\n").collect::<Vec<_>>()[row - 1].to_string()
+    let mut frame = vec![
+        format!("{}: {}", header, diag.message),
+        format!(
+            "{:w$}{} {}",
+            "",
+            paint("-->", colored, |s| s.blue().bold()),
+            span,
+            w = gutter_width
+        ),
+        gutter(None),
+    ];
+    for lineno in first..=last {
+        let line = lines[lineno - 1];
+        frame.push(
+            format!("{} {}", gutter(Some(lineno)), line)
+                .trim_end()
+                .to_string(),
+        );
+        if lineno == span.start_line {
+            let mark = if diag.labels.is_empty() { '^' } else { '~' };
+            frame.push(format!(
+                "{} {}",
+                gutter(None),
+                paint(&underline(line, span, mark, ""), colored, mark_style)
+            ));
+        }
+        for label in diag
+            .labels
+            .iter()
+            .filter(|label| label.span.start_line == lineno)
+        {
+            frame.push(format!(
+                "{} {}",
+                gutter(None),
+                paint(
+                    &underline(line, &label.span, '^', &label.message),
+                    colored,
+                    |s| { s.cyan() }
+                )
+            ));
+        }
+    }
+    if !diag.notes.is_empty() {
+        frame.push(gutter(None));
+    }
+    for note in &diag.notes {
+        frame.push(format!(
+            "{:w$} = {}: {} at {}",
+            "",
+            paint("note", colored, |s| s.bold()),
+            note.message,
+            note.span,
+            w = gutter_width
+        ));
+    }
+    Ok(frame.join("\n"))
 }
 
-pub fn get_report_history() -> Vec<String> {
-    VIOLATION_HISTORY.with(|history| history.borrow().clone())
+pub fn print_diagnostic(diag: &Diagnostic) -> Result<()> {
+    println!("{}", render_diagnostic(diag, true)?);
+    Ok(())
 }
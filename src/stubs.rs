@@ -0,0 +1,363 @@
+use crate::error::{Result, TypeinferError};
+use crate::jssyntax::JSTyp;
+use crate::project::{Module, DEFAULT_EXPORT, NAMESPACE};
+use crate::util;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Errors are messages of `TypeinferError::Stub`
+type Checked<T> = std::result::Result<T, String>;
+
+/// A stub file: declarations of the exports of a package, or of globals without `module`, e.g.,
+/// ```json
+/// {
+///   "module": "lodash",
+///   "declarations": {
+///     "chunk": { "params": ["array", "number?"], "returns": "array" },
+///     "VERSION": "string"
+///   }
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StubFile {
+    #[serde(default)]
+    module: Option<String>,
+    declarations: BTreeMap<String, RawDecl>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawDecl {
+    /// type of a value, e.g., `"string"`
+    Value(String),
+    Function {
+        params: Vec<String>,
+        /// type of every argument after `params`
+        #[serde(default)]
+        rest: Option<String>,
+        #[serde(default)]
+        returns: Option<String>,
+    },
+    Object {
+        properties: BTreeMap<String, RawDecl>,
+    },
+}
+
+/// Values a declared parameter accepts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// `any`
+    Any,
+    /// `function`, which no typed value is
+    Function,
+    /// e.g., `number | string`; an optional parameter `number?` also accepts `undefined`
+    Typs(Vec<JSTyp>),
+}
+
+impl Expected {
+    pub fn accepts(&self, typ: &JSTyp) -> bool {
+        match (self, typ) {
+            (_, JSTyp::Unknown | JSTyp::Never) | (Self::Any, _) => true,
+            (Self::Function, _) => false,
+            // an array is an object
+            (Self::Typs(typs), JSTyp::Array) => {
+                typs.contains(&JSTyp::Array) || typs.contains(&JSTyp::Object)
+            }
+            (Self::Typs(typs), _) => typs.contains(typ),
+        }
+    }
+
+    /// The accepted types, as in messages
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Any => "any value".to_string(),
+            Self::Function => "a function".to_string(),
+            Self::Typs(typs) => typs
+                .iter()
+                .map(|typ| format!("{:?}", typ))
+                .collect::<Vec<_>>()
+                .join(" | "),
+        }
+    }
+}
+
+/// Signature of a declared function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub params: Vec<Expected>,
+    /// every argument after `params`
+    pub rest: Option<Expected>,
+    pub ret: JSTyp,
+}
+
+impl Signature {
+    /// What the argument at `idx` must be; `None` past the parameters
+    pub fn param(&self, idx: usize) -> Option<&Expected> {
+        self.params.get(idx).or(self.rest.as_ref())
+    }
+}
+
+/// A declared value, function or object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decl {
+    Value(JSTyp),
+    Function(Signature),
+    /// properties of an object, e.g., the functions of a namespace
+    Object(BTreeMap<String, Decl>),
+}
+
+impl Decl {
+    /// Declaration of a value declared as both `self` and `other`, e.g., by two environments
+    fn join(self, other: Decl) -> Decl {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Object(mut a), Self::Object(b)) => {
+                for (name, decl) in b {
+                    let decl = match a.remove(&name) {
+                        Some(prev) => prev.join(decl),
+                        None => decl,
+                    };
+                    a.insert(name, decl);
+                }
+                Self::Object(a)
+            }
+            (Self::Object(a), Self::Value(JSTyp::Object))
+            | (Self::Value(JSTyp::Object), Self::Object(a)) => Self::Object(a),
+            // arguments are checked only against what both accept
+            (Self::Function(a), Self::Function(b)) => Self::Function(Signature {
+                params: vec![Expected::Any; a.params.len().max(b.params.len())],
+                rest: a.rest.or(b.rest).map(|_| Expected::Any),
+                ret: if a.ret == b.ret {
+                    a.ret
+                } else {
+                    JSTyp::Unknown
+                },
+            }),
+            _ => Self::Value(JSTyp::Unknown),
+        }
+    }
+
+    /// Type of the declared value; a function is not typed
+    pub fn typ(&self) -> JSTyp {
+        match self {
+            Self::Value(typ) => typ.clone(),
+            Self::Function(_) => JSTyp::Unknown,
+            Self::Object(_) => JSTyp::Object,
+        }
+    }
+}
+
+/// Declarations of the packages and globals the project uses but cannot see, loaded from the
+/// stub files of the configured directories
+#[derive(Debug, Default, PartialEq)]
+pub struct Stubs {
+    /// package -> exported name -> declaration
+    modules: BTreeMap<String, BTreeMap<String, Decl>>,
+    globals: BTreeMap<String, Decl>,
+    /// globals of the environment profiles, which the stub files override
+    env: BTreeMap<String, Decl>,
+}
+
+impl Stubs {
+    /// Loads every `*.json` file below `dirs`, in path order
+    pub fn load(dirs: &[impl AsRef<Path>]) -> Result<Self> {
+        let mut stubs = Self::default();
+        for dir in dirs {
+            let pattern = dir.as_ref().join("**").join("*.json");
+            let mut paths = glob::glob(&pattern.to_string_lossy())
+                .map_err(|e| TypeinferError::Stub(e.to_string()))?
+                .flatten()
+                .collect::<Vec<_>>();
+            paths.sort();
+            for path in paths {
+                let filename = path.to_string_lossy();
+                stubs
+                    .add_file(&util::read_file(&filename)?)
+                    .map_err(|e| TypeinferError::Stub(format!("{}: {}", filename, e)))?;
+            }
+        }
+        Ok(stubs)
+    }
+
+    /// Adds the declarations of a stub file. A name declared twice is an error.
+    pub fn add(&mut self, content: &str) -> Result<()> {
+        self.add_file(content).map_err(TypeinferError::Stub)
+    }
+
+    fn add_file(&mut self, content: &str) -> Checked<()> {
+        let file: StubFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let decls = match &file.module {
+            Some(module) => self.modules.entry(module.clone()).or_default(),
+            None => &mut self.globals,
+        };
+        for (name, raw) in file.declarations {
+            let decl = convert(&raw).map_err(|e| format!("`{}`: {}", name, e))?;
+            if decls.insert(name.clone(), decl).is_some() {
+                let owner = file.module.as_deref().unwrap_or("the globals");
+                return Err(format!("`{}` of {} is declared twice", name, owner));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the globals of an environment profile. A global of several profiles has the
+    /// declaration of all of them.
+    pub fn add_env(&mut self, content: &str) -> Result<()> {
+        let file: StubFile =
+            serde_json::from_str(content).map_err(|e| TypeinferError::Stub(e.to_string()))?;
+        for (name, raw) in file.declarations {
+            let decl =
+                convert(&raw).map_err(|e| TypeinferError::Stub(format!("`{}`: {}", name, e)))?;
+            let decl = match self.env.remove(&name) {
+                Some(prev) => prev.join(decl),
+                None => decl,
+            };
+            self.env.insert(name, decl);
+        }
+        Ok(())
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Decl> {
+        self.globals.get(name).or_else(|| self.env.get(name))
+    }
+
+    /// Declarations `module` sees by the names it uses: the globals and the bindings it imports
+    /// from declared packages, which shadow the globals
+    pub fn bindings(&self, module: &Module) -> BTreeMap<String, Decl> {
+        let mut bindings = self.env.clone();
+        bindings.extend(self.globals.clone());
+        for (local, import) in &module.packages {
+            if let Some(decl) = self.import(&import.module, &import.name) {
+                bindings.insert(local.clone(), decl);
+            }
+        }
+        bindings
+    }
+
+    /// Declaration of the binding `name` of the package `module`. The namespace is the whole
+    /// package, and so is the default export of a package that does not declare one, as
+    /// bundlers do for CommonJS packages.
+    pub fn import(&self, module: &str, name: &str) -> Option<Decl> {
+        let decls = self.modules.get(module)?;
+        match name {
+            NAMESPACE => Some(Decl::Object(decls.clone())),
+            DEFAULT_EXPORT if !decls.contains_key(DEFAULT_EXPORT) => {
+                Some(Decl::Object(decls.clone()))
+            }
+            _ => decls.get(name).cloned(),
+        }
+    }
+}
+
+fn convert(raw: &RawDecl) -> Checked<Decl> {
+    Ok(match raw {
+        RawDecl::Value(typ) => Decl::Value(value_typ(typ)?),
+        RawDecl::Function {
+            params,
+            rest,
+            returns,
+        } => Decl::Function(Signature {
+            params: params
+                .iter()
+                .map(|param| expected(param))
+                .collect::<Checked<_>>()?,
+            rest: rest.as_deref().map(expected).transpose()?,
+            ret: match returns {
+                Some(typ) => value_typ(typ)?,
+                None => JSTyp::Unknown,
+            },
+        }),
+        RawDecl::Object { properties } => Decl::Object(
+            properties
+                .iter()
+                .map(|(name, raw)| Ok((name.clone(), convert(raw)?)))
+                .collect::<Checked<_>>()?,
+        ),
+    })
+}
+
+/// A parameter type, e.g., `number | string`, `object?`, `function` or `any`
+fn expected(text: &str) -> Checked<Expected> {
+    let (text, optional) = match text.trim().strip_suffix('?') {
+        Some(text) => (text, true),
+        None => (text.trim(), false),
+    };
+    if text == "function" && !optional {
+        return Ok(Expected::Function);
+    }
+    let mut typs = vec![];
+    for name in text.split('|').map(str::trim) {
+        match name {
+            // `undefined` or a function is not told apart from an untyped value
+            "any" | "function" => return Ok(Expected::Any),
+            _ => typs.push(name.parse::<JSTyp>().map_err(|_| unknown_typ(name))?),
+        }
+    }
+    if optional {
+        typs.push(JSTyp::Undefined);
+    }
+    Ok(Expected::Typs(typs))
+}
+
+/// Type of a declared value or return value. A union, e.g., `string | null`, is `Unknown`, as is a
+/// function.
+fn value_typ(text: &str) -> Checked<JSTyp> {
+    let names = text.split('|').map(str::trim).collect::<Vec<_>>();
+    for name in &names {
+        if !matches!(*name, "any" | "function") {
+            name.parse::<JSTyp>().map_err(|_| unknown_typ(name))?;
+        }
+    }
+    Ok(match names.as_slice() {
+        [name] => name.parse().unwrap_or(JSTyp::Unknown),
+        _ => JSTyp::Unknown,
+    })
+}
+
+fn unknown_typ(name: &str) -> String {
+    format!("unknown type `{}`", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stubs() {
+        let stubs = Stubs::load(&["test/stubs/stubs"]).unwrap();
+        let Some(Decl::Function(chunk)) = stubs.import("lodash", "chunk") else {
+            panic!("`chunk` of lodash is not a function");
+        };
+        assert_eq!(chunk.param(0), Some(&Expected::Typs(vec![JSTyp::Array])));
+        assert_eq!(
+            chunk.param(1),
+            Some(&Expected::Typs(vec![JSTyp::Number, JSTyp::Undefined]))
+        );
+        assert_eq!(chunk.param(2), None);
+        assert_eq!(chunk.ret, JSTyp::Array);
+        // lodash has no default export
+        assert_eq!(
+            stubs.import("lodash", DEFAULT_EXPORT),
+            stubs.import("lodash", NAMESPACE)
+        );
+        assert!(stubs.import("lodash", "missing").is_none());
+        assert!(matches!(stubs.global("sdk"), Some(Decl::Object(_))));
+
+        assert!(Expected::Typs(vec![JSTyp::Object]).accepts(&JSTyp::Array));
+        assert!(!Expected::Function.accepts(&JSTyp::Number));
+        assert_eq!(
+            expected("number | string").unwrap().describe(),
+            "Number | String"
+        );
+        assert_eq!(value_typ("string | null").unwrap(), JSTyp::Unknown);
+
+        let mut stubs = Stubs::default();
+        let err = stubs.add(r#"{"declarations": {"f": {"params": ["integer"]}}}"#);
+        assert!(err.is_err());
+        let twice = r#"{"declarations": {"f": "string"}}"#;
+        stubs.add(twice).unwrap();
+        assert!(stubs.add(twice).is_err());
+    }
+}
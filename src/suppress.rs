@@ -0,0 +1,137 @@
+use crate::diagnostic::{self, Diagnostic, Span, UNUSED_SUPPRESSION};
+use crate::jssyntax::COMMENT;
+use crate::report;
+use tree_sitter::Tree;
+use tree_sitter_traversal::{traverse, Order};
+
+pub const IGNORE_NEXT_LINE: &str = "typeinfer-ignore-next-line";
+pub const IGNORE_LINE: &str = "typeinfer-ignore-line";
+pub const DISABLE: &str = "typeinfer-disable";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Scope {
+    /// 1-based line of the original source
+    Line(usize),
+    File,
+}
+
+/// A suppression comment of the original source
+#[derive(Debug, Clone)]
+struct Suppression {
+    scope: Scope,
+    /// rule ids to suppress; every rule if empty
+    rules: Vec<String>,
+    /// the comment
+    span: Span,
+    directive: String,
+    used: bool,
+}
+impl Suppression {
+    fn matches(&self, diag: &Diagnostic) -> bool {
+        let in_scope = match self.scope {
+            Scope::Line(line) => diag.span.file == self.span.file && diag.span.start_line == line,
+            Scope::File => diag.span.file == self.span.file,
+        };
+        in_scope && (self.rules.is_empty() || self.rules.iter().any(|rule| rule == diag.rule))
+    }
+}
+
+/// `// typeinfer-ignore-next-line [rule...]`, `// typeinfer-ignore-line [rule...]` and
+/// `/* typeinfer-disable [rule...] */` where rules are separated by spaces or commas
+fn parse_suppression(comment: &str, span: Span) -> Option<Suppression> {
+    let text = comment.trim();
+    let text = if let Some(text) = text.strip_prefix("//") {
+        text
+    } else {
+        text.strip_prefix("/*")?.strip_suffix("*/")?
+    };
+    let mut words = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty());
+    let directive = words.next()?;
+    let scope = match directive {
+        IGNORE_NEXT_LINE => Scope::Line(span.end_line + 1),
+        IGNORE_LINE => Scope::Line(span.start_line),
+        DISABLE => Scope::File,
+        _ => return None,
+    };
+    Some(Suppression {
+        scope,
+        rules: words.map(|word| word.to_string()).collect(),
+        directive: text.trim().to_string(),
+        span,
+        used: false,
+    })
+}
+
+fn collect(filename: &str, code: &str, tree: &Tree) -> Vec<Suppression> {
+    traverse(tree.walk(), Order::Pre)
+        .filter(|node| node.kind() == COMMENT)
+        .filter_map(|node| {
+            let span = report::byte_span(filename, code, node.start_byte(), node.end_byte());
+            parse_suppression(&code[node.byte_range()], span)
+        })
+        .collect()
+}
+
+/// Drops the diagnostics silenced by the suppression comments of `code`, the original source of
+/// `filename`, and warns about suppressions that silence nothing
+pub fn apply(filename: &str, code: &str, tree: &Tree, diags: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut suppressions = collect(filename, code, tree);
+    let mut diags = diags
+        .into_iter()
+        .filter(|diag| {
+            let mut suppressed = false;
+            for suppression in suppressions.iter_mut().filter(|s| s.matches(diag)) {
+                suppression.used = true;
+                suppressed = true;
+            }
+            !suppressed
+        })
+        .collect::<Vec<_>>();
+    for suppression in suppressions.into_iter().filter(|s| !s.used) {
+        diags.push(Diagnostic {
+            rule: UNUSED_SUPPRESSION,
+            severity: diagnostic::default_severity(UNUSED_SUPPRESSION),
+            message: format!("`{}` suppresses no diagnostic", suppression.directive),
+            span: suppression.span,
+            operand_typs: vec![],
+            labels: vec![],
+            notes: vec![],
+        });
+    }
+    diags
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis;
+    use crate::config::Config;
+    use crate::diagnostic::UNUSED_SUPPRESSION;
+
+    #[test]
+    fn test_suppression() {
+        let diags =
+            analysis::analyze_file("test/suppress/suppress.js", &Config::default()).unwrap();
+        let found = diags
+            .iter()
+            .map(|diag| (diag.rule, diag.span.start_line, diag.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("arith-coercion", 11, "Bool + Number"),
+                (
+                    UNUSED_SUPPRESSION,
+                    12,
+                    "`typeinfer-ignore-next-line cmp-mismatch` suppresses no diagnostic"
+                ),
+                (
+                    UNUSED_SUPPRESSION,
+                    15,
+                    "`typeinfer-ignore-line` suppresses no diagnostic"
+                ),
+            ]
+        );
+    }
+}
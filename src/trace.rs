@@ -0,0 +1,193 @@
+use crate::error::{Result, TypeinferError};
+use crate::instrument;
+use crate::jssyntax::JSTyp;
+use crate::util;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Results of `typeof` a trace may record
+const TYPEOF_NAMES: [&str; 8] = [
+    "undefined",
+    "boolean",
+    "number",
+    "bigint",
+    "string",
+    "symbol",
+    "object",
+    "function",
+];
+
+/// Seeds of a function beyond which its traced parameters are joined instead of combined
+const MAX_SEEDS: usize = 16;
+
+/// A line of a trace file: the runtime type of an argument of a call, e.g.,
+/// `{"function": "scale", "file": "src/lib.js", "param": 0, "typeof": "string"}`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Observation {
+    pub function: String,
+    pub file: String,
+    /// 0-based index of the parameter
+    pub param: usize,
+    /// result of `typeof` on the argument
+    #[serde(rename = "typeof")]
+    pub typ: String,
+}
+
+/// Runtime types of the parameters of the traced functions, e.g., recorded by an instrumented run
+/// of a test suite
+#[derive(Debug, Default, PartialEq)]
+pub struct Traces {
+    /// (file, function) -> `typeof` names observed per parameter
+    funcs: BTreeMap<(String, String), Vec<BTreeSet<String>>>,
+}
+
+impl Traces {
+    pub fn load(filename: &str) -> Result<Self> {
+        Self::parse(&util::read_file(filename)?)
+            .map_err(|e| TypeinferError::Trace(format!("{}: {}", filename, e)))
+    }
+
+    /// Parses JSON lines of `Observation`s; blank lines are skipped
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut traces = Self::default();
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let observation: Observation = serde_json::from_str(line)
+                .map_err(|e| TypeinferError::Trace(format!("line {}: {}", idx + 1, e)))?;
+            if !TYPEOF_NAMES.contains(&observation.typ.as_str()) {
+                return Err(TypeinferError::Trace(format!(
+                    "line {}: unknown typeof `{}`",
+                    idx + 1,
+                    observation.typ
+                )));
+            }
+            let params = traces
+                .funcs
+                .entry((file_key(&observation.file), observation.function))
+                .or_default();
+            if params.len() <= observation.param {
+                params.resize(observation.param + 1, BTreeSet::new());
+            }
+            params[observation.param].insert(observation.typ);
+        }
+        Ok(traces)
+    }
+
+    /// `typeof` names observed per parameter of `function` of `file`
+    pub fn params(&self, file: &str, function: &str) -> Option<&[BTreeSet<String>]> {
+        self.funcs
+            .get(&(file_key(file), function.to_string()))
+            .map(|params| params.as_slice())
+    }
+
+    /// Parameter types to infer `function` with: every combination of the observed types, or a
+    /// single seed where a parameter observed with several types is `Unknown` if there are too
+    /// many combinations. A parameter never observed is `Unknown`.
+    pub fn seeds(&self, file: &str, function: &str, n_params: usize) -> Vec<Vec<JSTyp>> {
+        let Some(params) = self.params(file, function) else {
+            return vec![];
+        };
+        let observed = (0..n_params)
+            .map(|idx| match params.get(idx) {
+                Some(names) if !names.is_empty() => {
+                    names.iter().map(|name| typeof2typ(name)).collect()
+                }
+                _ => vec![JSTyp::Unknown],
+            })
+            .collect::<Vec<Vec<_>>>();
+        let n_seeds = observed.iter().map(|typs| typs.len()).product::<usize>();
+        if n_seeds > MAX_SEEDS {
+            let joined = observed
+                .into_iter()
+                .map(|typs| match typs.as_slice() {
+                    [typ] => typ.clone(),
+                    _ => JSTyp::Unknown,
+                })
+                .collect();
+            return vec![joined];
+        }
+        let mut seeds = vec![vec![]];
+        for typs in observed {
+            seeds = seeds
+                .into_iter()
+                .flat_map(|seed: Vec<JSTyp>| {
+                    typs.iter().map(move |typ| {
+                        let mut seed = seed.clone();
+                        seed.push(typ.clone());
+                        seed
+                    })
+                })
+                .collect();
+        }
+        seeds
+    }
+}
+
+/// Whether a value of one of `typs` may produce `name` with `typeof`. An unknown type may produce
+/// anything.
+pub fn is_observable(name: &str, typs: &[JSTyp]) -> bool {
+    typs.iter()
+        .any(|typ| typ.is_unknown() || instrument::typeof_name(typ) == name)
+}
+
+/// A `typeof` name as a type; `object` may also be `null` or an array
+fn typeof2typ(name: &str) -> JSTyp {
+    match name {
+        // functions are not typed
+        "function" => JSTyp::Unknown,
+        _ => name.parse().unwrap_or(JSTyp::Unknown),
+    }
+}
+
+/// Traces may name a file by another path than the project
+fn file_key(file: &str) -> String {
+    std::fs::canonicalize(file).map_or(file.to_string(), |path| path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces() {
+        let traces = Traces::parse(
+            r#"{"function": "f", "file": "a.js", "param": 0, "typeof": "number"}
+
+{"function": "f", "file": "a.js", "param": 0, "typeof": "string"}
+{"function": "f", "file": "a.js", "param": 2, "typeof": "object"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            traces.seeds("a.js", "f", 3),
+            vec![
+                vec![JSTyp::Number, JSTyp::Unknown, JSTyp::Object],
+                vec![JSTyp::String, JSTyp::Unknown, JSTyp::Object],
+            ]
+        );
+        assert!(traces.seeds("a.js", "g", 1).is_empty());
+        assert!(is_observable("object", &[JSTyp::Null]));
+        assert!(!is_observable("string", &[JSTyp::Number]));
+
+        let many = (0..5)
+            .flat_map(|param| {
+                ["number", "string"].map(|name| {
+                    format!(
+                        r#"{{"function": "f", "file": "a.js", "param": {}, "typeof": "{}"}}"#,
+                        param, name
+                    )
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let traces = Traces::parse(&many).unwrap();
+        assert_eq!(traces.seeds("a.js", "f", 5), vec![vec![JSTyp::Unknown; 5]]);
+
+        let err =
+            Traces::parse(r#"{"function": "f", "file": "a.js", "param": 0, "typeof": "int"}"#);
+        assert!(err.is_err());
+        assert!(Traces::parse("{").is_err());
+    }
+}
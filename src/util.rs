@@ -1,3 +1,4 @@
+use crate::error::Result;
 use std::fs::{self, File};
 use std::io::Read;
 use std::io::Write;
@@ -10,16 +11,22 @@ pub fn read_file(filename: &str) -> std::io::Result<String> {
     Ok(data)
 }
 
-pub fn write_file(filename: &str, content: &str) {
-    fs::write(filename, content).unwrap()
+pub fn write_file(filename: &str, content: &str) -> Result<()> {
+    fs::write(filename, content)?;
+    Ok(())
 }
 
-pub fn jscode2file(filename: &str, code: &str) {
-    let mut f = File::create(filename).unwrap();
-    f.write_all(code.as_bytes()).unwrap();
-    Command::new("js-beautify")
-        .arg("-r")
-        .arg(filename)
-        .output()
-        .unwrap();
+pub fn jscode2file(filename: &str, code: &str) -> Result<()> {
+    let mut f = File::create(filename)?;
+    f.write_all(code.as_bytes())?;
+    // Beautifying is cosmetic; the debloated code is valid without it
+    let _ = Command::new("js-beautify").arg("-r").arg(filename).output();
+    Ok(())
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms unlike `DefaultHasher`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
 }
@@ -0,0 +1,378 @@
+use crate::analysis;
+use crate::baseline;
+use crate::config::Config;
+use crate::diagnostic::Diagnostic;
+use crate::error::{Result, TypeinferError};
+use crate::node;
+use crate::output::{self, Format};
+use crate::project::{self, Module, Project};
+use crate::report;
+use crate::util;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+use tree_sitter::{InputEdit, Parser, Tree};
+
+/// Interval between two polls of the modification times
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A file as of its last poll
+struct Watched {
+    mtime: SystemTime,
+    origin: String,
+    tree: Tree,
+}
+
+/// Diagnostics that differ from the previous analysis
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub appeared: Vec<Diagnostic>,
+    pub disappeared: Vec<Diagnostic>,
+}
+
+pub struct Watcher {
+    /// given on the command line; the `include` globs of the configuration otherwise
+    files: Vec<String>,
+    config: Config,
+    parser: Parser,
+    watched: BTreeMap<String, Watched>,
+    /// modules imported by the watched files in the previous analysis
+    imported: Vec<String>,
+    /// diagnostics of the previous analysis along with their keys
+    diags: Vec<(String, Diagnostic)>,
+    /// whether a change is not analyzed yet, e.g., because an import could not be read
+    pending: bool,
+}
+
+impl Watcher {
+    pub fn new(files: Vec<String>, config: Config) -> Result<Self> {
+        Ok(Self {
+            files,
+            config,
+            parser: node::get_parser()?,
+            watched: BTreeMap::new(),
+            imported: vec![],
+            diags: vec![],
+            pending: false,
+        })
+    }
+
+    /// The globs are expanded on every poll so that new files are picked up
+    fn current_files(&self) -> Result<Vec<String>> {
+        let mut files = if self.files.is_empty() {
+            self.config.included_files()?
+        } else {
+            self.files
+                .iter()
+                .map(|filename| project::module_filename(filename))
+                .collect()
+        };
+        files.retain(|filename| !self.config.is_excluded(filename));
+        // a module no longer imported is still watched; it may be imported again
+        for filename in &self.imported {
+            if !files.contains(filename) {
+                files.push(filename.clone());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Reparses the files modified since the last poll and analyzes the project again if any
+    /// was. The first poll analyzes every file.
+    pub fn poll(&mut self) -> Result<Option<Changes>> {
+        let files = self.current_files()?;
+        let n_watched = self.watched.len();
+        self.watched.retain(|filename, _| files.contains(filename));
+        let mut changed = self.pending || self.watched.len() != n_watched;
+        for filename in &files {
+            let mtime = match std::fs::metadata(filename).and_then(|meta| meta.modified()) {
+                Ok(mtime) => mtime,
+                // removed, e.g., for the moment of an atomic save
+                Err(_) => {
+                    changed |= self.watched.remove(filename).is_some();
+                    self.imported.retain(|imported| imported != filename);
+                    continue;
+                }
+            };
+            match self.watched.get_mut(filename) {
+                Some(watched) if watched.mtime == mtime => {}
+                Some(watched) => {
+                    // the modification time is kept until the file is read so that a file that
+                    // cannot be read is read again on the next poll
+                    let origin = util::read_file(filename)?;
+                    watched.mtime = mtime;
+                    if origin != watched.origin {
+                        let edit = input_edit(&watched.origin, &origin);
+                        watched.tree.edit(&edit);
+                        watched.tree =
+                            node::reparse(&mut self.parser, &origin, Some(&watched.tree))?;
+                        watched.origin = origin;
+                        changed = true;
+                    }
+                }
+                None => {
+                    let origin = util::read_file(filename)?;
+                    let tree = node::reparse(&mut self.parser, &origin, None)?;
+                    self.watched.insert(
+                        filename.clone(),
+                        Watched {
+                            mtime,
+                            origin,
+                            tree,
+                        },
+                    );
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return Ok(None);
+        }
+        self.pending = true;
+
+        let mut project = Project::default();
+        for (filename, watched) in &self.watched {
+            project.modules.push(Module::with_tree(
+                filename,
+                watched.origin.clone(),
+                watched.tree.clone(),
+            ));
+        }
+        project.load_imports(self.config.thread_count())?;
+        // newly imported modules are watched from now on
+        for module in &project.modules {
+            if self.watched.contains_key(&module.filename) {
+                continue;
+            }
+            let Ok(mtime) = std::fs::metadata(&module.filename).and_then(|meta| meta.modified())
+            else {
+                continue;
+            };
+            self.imported.push(module.filename.clone());
+            self.watched.insert(
+                module.filename.clone(),
+                Watched {
+                    mtime,
+                    origin: module.origin.clone(),
+                    tree: module.tree.clone(),
+                },
+            );
+        }
+        let diags = analysis::analyze_project(&project, &self.config)?
+            .into_iter()
+            .map(|diag| (self.key(&diag), diag))
+            .collect::<Vec<_>>();
+        let changes = Changes {
+            appeared: unmatched(&diags, &self.diags),
+            disappeared: unmatched(&self.diags, &diags),
+        };
+        self.diags = diags;
+        self.pending = false;
+        Ok(Some(changes))
+    }
+
+    /// Key a diagnostic is compared on between two analyses, which does not depend on its
+    /// location so that a diagnostic moved by an edit above it is the same
+    fn key(&self, diag: &Diagnostic) -> String {
+        let fingerprint = match self.watched.get(&diag.span.file) {
+            Some(watched) => baseline::fingerprint(diag, &watched.origin, &watched.tree),
+            None => format!("{}-{}", diag.span.start_byte, diag.span.end_byte),
+        };
+        format!("{}\n{}\n{}", diag.span.file, fingerprint, diag.message)
+    }
+}
+
+/// Diagnostics of `diags` that `others` has no diagnostic with the same key for. A key `others`
+/// has `n` times matches at most `n` diagnostics.
+fn unmatched(diags: &[(String, Diagnostic)], others: &[(String, Diagnostic)]) -> Vec<Diagnostic> {
+    let mut known = HashMap::new();
+    for (key, _) in others {
+        *known.entry(key).or_insert(0) += 1;
+    }
+    diags
+        .iter()
+        .filter(|(key, _)| match known.get_mut(key) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                false
+            }
+            _ => true,
+        })
+        .map(|(_, diag)| diag.clone())
+        .collect()
+}
+
+/// The single edit turning `old` into `new`: everything between their common prefix and suffix
+pub fn input_edit(old: &str, new: &str) -> InputEdit {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    let old_end_byte = old.len() - suffix;
+    let new_end_byte = new.len() - suffix;
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte,
+        new_end_byte,
+        start_position: report::byte2point(old, prefix),
+        old_end_position: report::byte2point(old, old_end_byte),
+        new_end_position: report::byte2point(new, new_end_byte),
+    }
+}
+
+/// `+` and the frame of every new diagnostic, `-` and the header of every fixed one
+fn render_changes(changes: &Changes, format: Format) -> Result<String> {
+    let mut lines = vec![];
+    for (sign, status, diags) in [
+        ("+", "appeared", &changes.appeared),
+        ("-", "disappeared", &changes.disappeared),
+    ] {
+        for diag in diags.iter() {
+            match format {
+                Format::Json => {
+                    let mut value = output::diagnostic2json(diag);
+                    value["status"] = json!(status);
+                    lines.push(value.to_string());
+                }
+                _ if sign == "+" => {
+                    lines.push(format!("+ {}", report::render_diagnostic(diag, true)?));
+                }
+                _ => lines.push(format!(
+                    "- {}[{}]: {} at {}",
+                    diag.severity, diag.rule, diag.message, diag.span
+                )),
+            }
+        }
+    }
+    let separator = if format == Format::Json { "\n" } else { "\n\n" };
+    Ok(lines.join(separator))
+}
+
+/// Polls `files`, or the files of `config` if none are given, until interrupted
+pub fn run(files: Vec<String>, config: Config, format: Format) -> Result<()> {
+    if format == Format::Sarif {
+        return Err(TypeinferError::Usage(
+            "watch prints text or json, not sarif".to_string(),
+        ));
+    }
+    let mut watcher = Watcher::new(files, config)?;
+    loop {
+        match watcher.poll() {
+            Ok(Some(changes)) => {
+                let out = render_changes(&changes, format)?;
+                if !out.is_empty() {
+                    println!("{}", out);
+                }
+                eprintln!(
+                    "watching {} files: {} new, {} fixed",
+                    watcher.watched.len(),
+                    changes.appeared.len(),
+                    changes.disappeared.len()
+                );
+            }
+            Ok(None) => {}
+            // e.g., a file removed while it is read; the change is analyzed on the next poll
+            Err(e) => eprintln!("{}", e),
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_input_edit() {
+        let old = "function foo(a) {\n  let b = a + 1;\n}\n";
+        let new = "function foo(a) {\n  let b = a + \"é\" + 1;\n}\n";
+        let mut tree = node::get_tree(old).unwrap();
+        let edit = input_edit(old, new);
+        assert_eq!((edit.start_byte, edit.old_end_byte), (32, 32));
+        assert_eq!(edit.new_end_byte, 32 + "\"é\" + ".len());
+        tree.edit(&edit);
+        let mut parser = node::get_parser().unwrap();
+        let reparsed = node::reparse(&mut parser, new, Some(&tree)).unwrap();
+        let fresh = node::get_tree(new).unwrap();
+        assert_eq!(reparsed.root_node().to_sexp(), fresh.root_node().to_sexp());
+    }
+
+    #[test]
+    fn test_poll() {
+        let dir = std::env::temp_dir().join(format!("typeinfer-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("watched.js").to_string_lossy().to_string();
+        let touch = |code: &str, secs: u64| {
+            util::write_file(&filename, code).unwrap();
+            let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            File::options()
+                .write(true)
+                .open(&filename)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        };
+        let messages = |diags: &[Diagnostic]| {
+            diags
+                .iter()
+                .map(|diag| diag.message.clone())
+                .collect::<Vec<_>>()
+        };
+        let code = "function foo(a) {\n  let b = a + 1;\n}\nfoo(true);\n";
+        touch(code, 1);
+        let mut watcher = Watcher::new(vec![filename.clone()], Config::default()).unwrap();
+
+        let changes = watcher.poll().unwrap().unwrap();
+        assert_eq!(messages(&changes.appeared), vec!["Bool + Number"]);
+        assert!(watcher.poll().unwrap().is_none());
+
+        // the same content with a new modification time is not analyzed again
+        touch(code, 2);
+        assert!(watcher.poll().unwrap().is_none());
+
+        // a line added above a diagnostic moves it without changing it
+        let code = format!("// moved\n{}", code);
+        touch(&code, 3);
+        let changes = watcher.poll().unwrap().unwrap();
+        assert!(changes.appeared.is_empty());
+        assert!(changes.disappeared.is_empty());
+
+        touch(&code.replace("foo(true)", "foo(\"a\")"), 4);
+        let changes = watcher.poll().unwrap().unwrap();
+        assert_eq!(messages(&changes.appeared), vec!["String + Number"]);
+        assert_eq!(messages(&changes.disappeared), vec!["Bool + Number"]);
+
+        // a removed file is forgotten and its diagnostics disappear
+        std::fs::remove_file(&filename).unwrap();
+        let changes = watcher.poll().unwrap().unwrap();
+        assert!(watcher.watched.is_empty());
+        assert_eq!(messages(&changes.disappeared), vec!["String + Number"]);
+
+        // an import that cannot be read, e.g., while it is written, fails the poll, and the change
+        // is analyzed once it can
+        let lib = dir.join("lib.js").to_string_lossy().to_string();
+        std::fs::write(&lib, [0xff]).unwrap();
+        touch("import { g } from \"./lib.js\";\ng();\n", 5);
+        assert!(watcher.poll().is_err());
+        util::write_file(&lib, "export function g() {\n  return 1;\n}\n").unwrap();
+        assert!(watcher.poll().unwrap().is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
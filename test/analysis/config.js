@@ -0,0 +1,9 @@
+function foo(a, b) {
+  let t = true;
+  let c = t + b;
+  let v = VERSION - 1;
+  let d = b == a;
+}
+
+foo("x", 1);
+foo(null, 1);
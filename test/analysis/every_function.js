@@ -0,0 +1,10 @@
+function foo(a) {
+  let b = a == 1;
+}
+function bar(a) {
+  let b = a == 1;
+  let c = false + true;
+}
+foo("x");
+foo("y");
+foo(null);
@@ -0,0 +1,18 @@
+function outer(a) {
+  function* inner() {
+    yield a;
+  }
+  return a + 1;
+}
+function step(b) {
+  return b + 1;
+}
+function* gen() {
+  yield step(1);
+}
+var i = 0;
+do {
+  i = step(i);
+  outer(true);
+} while (i < 3);
+step("x");
@@ -0,0 +1,13 @@
+function area() {
+  return this.w * this.h;
+}
+
+function main() {
+  let rect = { w: 2, h: "3", area };
+  let a = rect.area();
+  let other = { w: 1, h: 2, size: area };
+  let b = other.size();
+  return a + b;
+}
+
+main();
@@ -0,0 +1,9 @@
+export function scale(value, factor) {
+  return value * factor;
+}
+
+export function label(name) {
+  return "#" + name;
+}
+
+label("a");
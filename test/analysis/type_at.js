@@ -0,0 +1,9 @@
+function area(w, h) {
+  let unit = "cm";
+  let size = w * h;
+  console.log(size, unit);
+  return size + unit;
+}
+
+area(2, 3);
+area("2", 3);
@@ -0,0 +1,6 @@
+function f(a) {
+  return a + 1;
+}
+do {
+  f("1");
+} while (false);
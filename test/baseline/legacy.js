@@ -0,0 +1,7 @@
+function foo(a) {
+  let b = a == 1;
+  let c = a + 1;
+  let d = a + 1;
+}
+
+foo("x");
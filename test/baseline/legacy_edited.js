@@ -0,0 +1,11 @@
+// unrelated code was added above foo
+
+function foo(a) {
+  let b =   a ==   1;
+  let c = a + 1;
+
+  let d = a + 1;
+  let e = null - 1;
+}
+
+foo("x");
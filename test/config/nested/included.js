@@ -0,0 +1,5 @@
+function foo(a) {
+  let b = a + 1;
+}
+
+foo(true);
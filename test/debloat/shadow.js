@@ -0,0 +1,12 @@
+var id = "s";
+var a = "t";
+function foo({ id }, xs) {
+  for (const [a] of xs) {
+    let b = a;
+  }
+  for (let i = 0; i < xs.length; i++) {
+    let c = i;
+  }
+  return id + 1;
+}
+foo({ id: 1 }, [[1]]);
@@ -0,0 +1,7 @@
+export declare const VERSION: string;
+export declare let limits: number[];
+export declare function area(w: number | string, h: number): unknown;
+export declare function rect(w: number, h: number): { w: number; h: number; size: unknown; tags: string[] };
+declare function pad(text: string, width: number): string;
+declare function describe(value: number | string): number | string;
+export { pad as padded, describe as default };
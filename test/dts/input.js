@@ -0,0 +1,31 @@
+export const VERSION = "1.2.0";
+export let limits = [1, 2, 3];
+
+export function area(w, h) {
+  return w * h;
+}
+
+export function rect(w, h) {
+  let size = area(w, h);
+  return { w, h, size, tags: ["rect"] };
+}
+
+function pad(text, width) {
+  if (width > 0) {
+    return text + " ";
+  }
+  return text;
+}
+
+function describe(value) {
+  return value;
+}
+
+export { pad as padded };
+export default describe;
+
+rect(2, 3);
+pad("a", 2);
+describe(1);
+describe("one");
+area("2", 3);
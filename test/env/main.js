@@ -0,0 +1,8 @@
+function save(key, count) {
+  let title = document.title;
+  localStorage.setItem(key, count);
+  let mode = process.env.NODE_ENV;
+  return title + mode;
+}
+
+save("visits", 1);
@@ -0,0 +1,12 @@
+function foo(n, s) {
+  let a = Math.max(n, 1) + s.length;
+  let b = parseInt(s, 10) + Math.PI;
+  let c = [n, 2].map((x) => x * 2).join("-");
+  let d = Math.max(s, n);
+  let e = Object.keys(null);
+  let parts = s.split("");
+  parts.forEach(n);
+  return a + b;
+}
+
+foo(1, "12");
@@ -0,0 +1,14 @@
+function foo(a) {
+  let s = "id";
+  s += 1;
+  let n = null;
+  n ??= 3;
+  n -= true;
+  n <<= 2;
+  let o = { w: 1 };
+  o.w += "px";
+  let w = o.w;
+  return n;
+}
+
+foo(1);
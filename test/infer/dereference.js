@@ -0,0 +1,39 @@
+function foo(flag, cb, { id }) {
+  let b = null;
+  if (flag) {
+    b = { w: 1, items: [1] };
+  }
+  if (b) {
+    let w = b.w;
+  }
+  let u = b != null && b.w;
+  let v = b.w;
+  for (const x of b.items) {
+    let y = x;
+  }
+  let none;
+  for (const x of none) {
+    let y = x;
+  }
+  let { w } = none;
+  cb();
+  let none2;
+  none2.bar;
+  g(none2.quux);
+  if (none2.b) {
+  }
+  let o = null;
+  let p = o?.x.y;
+  o?.x.y();
+  let q = o?.x[0];
+  if (!b) {
+    return 0;
+  }
+  return b.w;
+}
+
+function g(x) {
+  return 1;
+}
+
+foo(true);
@@ -0,0 +1,22 @@
+function foo(flag, p) {
+  let a = { w: 1 };
+  let b = { w: "s" };
+  let o = a;
+  if (flag) {
+    o = b;
+  }
+  let x = a.w * b.w;
+  let q = o.w;
+  let alias = a;
+  alias.w = "t";
+  let y = a.w;
+  let c = new Date();
+  let d = c;
+  d.t = 1;
+  let z = c.t;
+  p.n = 2;
+  let m = p.n;
+  return x;
+}
+
+foo(true, {});
@@ -0,0 +1,18 @@
+function foo(flag) {
+  let rect = { w: 2, h: "3", pos: { x: 1 } };
+  let area = rect.w * rect.h;
+  rect.h = 4;
+  let x = rect.pos.x + rect.h;
+  let label = rect.name;
+  let none;
+  let n = none.length;
+  let maybe = null;
+  if (flag) {
+    maybe = { w: 1 };
+  }
+  let w = maybe.w;
+  let safe = none?.length;
+  return area;
+}
+
+foo(true);
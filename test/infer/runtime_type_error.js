@@ -0,0 +1,14 @@
+function foo(a) {
+  let big = 10n;
+  let n;
+  n = big + 1;
+  let m;
+  m = n * 2;
+  let s;
+  s = Symbol("s");
+  let t;
+  t = s + "x";
+  return t;
+}
+
+foo(1);
@@ -0,0 +1,10 @@
+function foo(a, b) {
+  let c;
+  let d = c;
+  let e = undefined;
+  let x = d + 1;
+  let y = e * 2;
+  let z = b - a;
+}
+
+foo(1);
@@ -0,0 +1,9 @@
+function foo(a, c = 1) {
+  let {x} = a;
+  let n = a.length + 1;
+  let m = undeclared * 2;
+  n = m + true;
+  return n;
+}
+
+foo(1);
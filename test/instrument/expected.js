@@ -0,0 +1,19 @@
+function scale(value, factor) { if (!(typeof value === "number" || typeof value === "string")) throw new TypeError("type assertion failed at test/instrument/input.js:1:16: `value` is inferred as Number | String, found " + typeof value); if (!(typeof factor === "number")) throw new TypeError("type assertion failed at test/instrument/input.js:1:23: `factor` is inferred as Number, found " + typeof factor);
+  let scaled = value * factor; if (!(typeof scaled === "number")) throw new TypeError("type assertion failed at test/instrument/input.js:2:7: `scaled` is inferred as Number, found " + typeof scaled);
+  let label = "x" + scaled; if (!(typeof label === "string")) throw new TypeError("type assertion failed at test/instrument/input.js:3:7: `label` is inferred as String, found " + typeof label);
+  if (factor > 1) label = label + "!";
+  scaled += 1; if (!(typeof scaled === "number")) throw new TypeError("type assertion failed at test/instrument/input.js:5:3: `scaled` is inferred as Number, found " + typeof scaled);
+  return label;
+}
+
+function wrap(items) { if (!(typeof items === "number" || typeof items === "string")) throw new TypeError("type assertion failed at test/instrument/input.js:9:15: `items` is inferred as Number | String, found " + typeof items);
+  let box = { items: items }; if (!((typeof box === "object" && box !== null && !Array.isArray(box)))) throw new TypeError("type assertion failed at test/instrument/input.js:10:7: `box` is inferred as Object, found " + typeof box);
+  let list = [items]; if (!(Array.isArray(list))) throw new TypeError("type assertion failed at test/instrument/input.js:11:7: `list` is inferred as Array, found " + typeof list);
+  box = null; if (!(box === null)) throw new TypeError("type assertion failed at test/instrument/input.js:12:3: `box` is inferred as Null, found " + typeof box);
+  return list;
+}
+
+scale(2, 3);
+scale("2", 3);
+wrap("a");
+wrap(1);
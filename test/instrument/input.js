@@ -0,0 +1,19 @@
+function scale(value, factor) {
+  let scaled = value * factor;
+  let label = "x" + scaled
+  if (factor > 1) label = label + "!";
+  scaled += 1;
+  return label;
+}
+
+function wrap(items) {
+  let box = { items: items };
+  let list = [items];
+  box = null;
+  return list;
+}
+
+scale(2, 3);
+scale("2", 3);
+wrap("a");
+wrap(1);
@@ -0,0 +1,14 @@
+function half(n) {
+  "use strict";
+  return n / 2;
+}
+
+function twice(n) {
+  'use strict'
+  "another directive"
+  let d = n * 2;
+  return d;
+}
+
+half(4);
+twice(3);
@@ -0,0 +1,14 @@
+function half(n) {
+  "use strict"; if (!(typeof n === "number")) throw new TypeError("type assertion failed at test/instrument/strict.js:1:15: `n` is inferred as Number, found " + typeof n);
+  return n / 2;
+}
+
+function twice(n) {
+  'use strict'
+  "another directive"; if (!(typeof n === "number")) throw new TypeError("type assertion failed at test/instrument/strict.js:6:16: `n` is inferred as Number, found " + typeof n);
+  let d = n * 2; if (!(typeof d === "number")) throw new TypeError("type assertion failed at test/instrument/strict.js:9:7: `d` is inferred as Number, found " + typeof d);
+  return d;
+}
+
+half(4);
+twice(3);
@@ -0,0 +1,41 @@
+// greets `name` a few times
+/**
+ * @param {string} name
+ * @param {number} times
+ * @returns {string}
+ */
+export function greet(name, times) {
+  /** @type {string} */
+  let greeting = "hello " + name; // keeps this comment
+  /** @type {number} */
+  let count = times;
+  count = count + 1;
+  return greeting;
+}
+
+/** Already documented */
+function keep(a) {
+  return a;
+}
+
+/**
+ * @param {boolean} flag
+ * @returns {number|string}
+ */
+function pick(flag) {
+  /** @type {number|undefined} */
+  let x;
+  x = 1;
+  let a = 1, b = 2;
+  if (flag) {
+    return "yes";
+  }
+  return x;
+}
+
+var z = 1; /** @param {number} n @returns {number} */ function inline(n) { return n * 2; }
+
+greet("world", 2);
+keep(null);
+pick(true);
+inline(3);
@@ -0,0 +1,29 @@
+// greets `name` a few times
+export function greet(name, times) {
+  let greeting = "hello " + name; // keeps this comment
+  let count = times;
+  count = count + 1;
+  return greeting;
+}
+
+/** Already documented */
+function keep(a) {
+  return a;
+}
+
+function pick(flag) {
+  let x;
+  x = 1;
+  let a = 1, b = 2;
+  if (flag) {
+    return "yes";
+  }
+  return x;
+}
+
+var z = 1; function inline(n) { return n * 2; }
+
+greet("world", 2);
+keep(null);
+pick(true);
+inline(3);
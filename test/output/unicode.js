@@ -0,0 +1,4 @@
+function f(a) {
+  let s = "é" + (a - 1);
+}
+f("x");
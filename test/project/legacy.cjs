@@ -0,0 +1,9 @@
+function count() {
+  return 10n;
+}
+
+function name() {
+  return "legacy";
+}
+
+module.exports = { count, name };
@@ -0,0 +1,14 @@
+import { greet } from "./lib";
+import twice, { halve as half } from "./math.js";
+const { count: legacyCount, name: legacyName } = require("./legacy.cjs");
+
+function main() {
+  let n = twice(2);
+  let s = greet("world");
+  let diff = s - n;
+  let total = legacyCount() + n;
+  let label = legacyName();
+  let ratio = half(true);
+}
+
+main();
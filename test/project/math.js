@@ -0,0 +1,9 @@
+export default function double(x) {
+  return x * 2;
+}
+
+function halve(x) {
+  return x / 2;
+}
+
+export { halve };
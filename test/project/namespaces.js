@@ -0,0 +1,10 @@
+import * as math from "./math.js";
+const shapes = require("./shapes.cjs");
+
+function main() {
+  let ratio = math.halve(true);
+  let scaled = shapes.scale(undefined);
+  let diff = shapes.label() - 1;
+}
+
+main();
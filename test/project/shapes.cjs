@@ -0,0 +1,9 @@
+function scale(x) {
+  return x * 2;
+}
+
+function label() {
+  return "shape";
+}
+
+module.exports = { scale, label };
@@ -0,0 +1,13 @@
+import _ from "lodash";
+import { padStart } from "lodash";
+
+function report(items, width) {
+  let rows = _.chunk(items, width);
+  let label = padStart(width, 8, " ");
+  let total = sdk.users.count() + rows.length;
+  sdk.track(total);
+  onReady(total);
+  return label + _.VERSION;
+}
+
+report([1, 2, 3], 2);
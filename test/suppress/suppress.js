@@ -0,0 +1,18 @@
+/* typeinfer-disable bigint-mix */
+function foo(a) {
+  let s = "";
+  // typeinfer-ignore-next-line arith-coercion
+  let t = s + 1;
+  let u = a == 1; // typeinfer-ignore-line
+  let big = 1n + 1;
+  // typeinfer-ignore-next-line cmp-mismatch, arith-coercion
+  let v = a + 1;
+  let w = a == 1; // typeinfer-ignore-line cmp-mismatch
+  let x = true + 1;
+  // typeinfer-ignore-next-line cmp-mismatch
+  let y = 1 + 2;
+  let z = 1;
+  let q = 2; // typeinfer-ignore-line
+}
+
+foo("x");